@@ -1,9 +1,10 @@
 use goldfix::{
     FixEngine,
-    config::{EngineConfig, SessionConfig, LogConfig, SessionRole},
+    config::{EngineConfig, SessionConfig, LogConfig},
     transport::TransportConfig,
-    message::{Field, field},
+    message::{Field, Message, field, parser::MessageParser},
 };
+use std::collections::HashMap;
 use std::path::PathBuf;
 use std::time::{Duration, Instant};
 use tokio::sync::mpsc;
@@ -12,9 +13,13 @@ use std::sync::atomic::{AtomicUsize, Ordering};
 
 const NUM_MESSAGES: usize = 10_000;
 const BATCH_SIZE: usize = 100;
+const PARSE_ITERATIONS: usize = 100_000;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // Parser benchmarks need no counterparty, run them first
+    benchmark_parsing()?;
+
     let transport_config = TransportConfig {
         use_ssl: false,
         cert_file: None,
@@ -32,18 +37,19 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             log_events: true,
             log_messages: true,
         },
+        store_directory: PathBuf::from("store"),
+        store_fsync: Default::default(),
+        store_retention: Default::default(),
         sessions: vec![
             SessionConfig {
-                begin_string: "FIX.4.2".to_string(),
                 sender_comp_id: "PERF_CLIENT".to_string(),
                 target_comp_id: "PERF_SERVER".to_string(),
                 target_addr: "127.0.0.1:8002".to_string(),
-                heart_bt_int: 30,
                 reset_on_logon: true,
                 reset_on_logout: true,
                 reset_on_disconnect: true,
                 transport_config: Some(transport_config),
-                role: SessionRole::Initiator,
+                ..Default::default()
             }
         ],
     };
@@ -80,7 +86,7 @@ async fn test_message_throughput(engine: &FixEngine) -> Result<(), Box<dyn std::
 
     // Spawn message processor
     tokio::spawn(async move {
-        while let Some(_) = rx.recv().await {
+        while rx.recv().await.is_some() {
             processed_count_clone.fetch_add(1, Ordering::SeqCst);
         }
     });
@@ -93,7 +99,7 @@ async fn test_message_throughput(engine: &FixEngine) -> Result<(), Box<dyn std::
             let msg_id = batch * BATCH_SIZE + i;
             let message_pool = Arc::clone(&message_pool);
             let mut msg = message_pool.get_message(field::values::MARKET_DATA_REQUEST).await;
-            msg.set_field(Field::new(field::MD_REQ_ID, &format!("REQ_{}", msg_id)))?;
+            msg.set_field(Field::new(field::MD_REQ_ID, format!("REQ_{}", msg_id)))?;
 
             let tx = tx.clone();
             let message_pool = Arc::clone(&message_pool);
@@ -112,4 +118,71 @@ async fn test_message_throughput(engine: &FixEngine) -> Result<(), Box<dyn std::
     }
 
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Parse the same execution report with the previous `&str` splitting approach, the owned
+/// parser and the borrowed byte view.
+fn benchmark_parsing() -> Result<(), Box<dyn std::error::Error>> {
+    let mut msg = Message::new(field::values::EXECUTION_REPORT);
+    msg.set_field(Field::new(field::ORDER_ID, "ORD-000123"))?;
+    msg.set_field(Field::new(field::CL_ORD_ID, "CL-000123"))?;
+    msg.set_field(Field::new(field::EXEC_ID, "EXEC-000123"))?;
+    msg.set_field(Field::new(field::EXEC_TYPE, "2"))?;
+    msg.set_field(Field::new(field::ORD_STATUS, "2"))?;
+    msg.set_field(Field::new(field::SYMBOL, "AAPL"))?;
+    msg.set_field(Field::new(field::SIDE, field::values::BUY))?;
+    msg.set_field(Field::new(field::ORDER_QTY, "100"))?;
+    msg.set_field(Field::new(field::PRICE, "150.25"))?;
+    msg.set_field(Field::new(field::TEXT, "benchmark fill"))?;
+    let frame = msg.to_string()?.into_bytes();
+
+    println!("Parser benchmark ({} iterations, {} byte frame):", PARSE_ITERATIONS, frame.len());
+
+    let start = Instant::now();
+    for _ in 0..PARSE_ITERATIONS {
+        let fields = legacy_parse(&frame);
+        std::hint::black_box(fields);
+    }
+    report("split + String per field", start.elapsed());
+
+    let start = Instant::now();
+    for _ in 0..PARSE_ITERATIONS {
+        let message = Message::from_bytes(&frame)?;
+        std::hint::black_box(message);
+    }
+    report("byte parser, owned Message", start.elapsed());
+
+    let start = Instant::now();
+    for _ in 0..PARSE_ITERATIONS {
+        let view = MessageParser::parse_bytes(&frame)?;
+        std::hint::black_box(view.get(field::CL_ORD_ID));
+    }
+    report("byte parser, borrowed view", start.elapsed());
+
+    Ok(())
+}
+
+/// The string based approach the parser used before working on bytes
+fn legacy_parse(frame: &[u8]) -> HashMap<i32, String> {
+    let data = String::from_utf8(frame.to_vec()).unwrap();
+    let mut fields = HashMap::new();
+    for field_str in data.split('\u{1}').collect::<Vec<&str>>() {
+        let parts: Vec<&str> = field_str.split('=').collect();
+        if parts.len() != 2 {
+            continue;
+        }
+        if let Ok(tag) = parts[0].parse::<i32>() {
+            fields.insert(tag, parts[1].to_string());
+        }
+    }
+    fields
+}
+
+fn report(label: &str, elapsed: Duration) {
+    println!(
+        "  {:<28} {:>10.2?} total, {:>8.0} ns/msg",
+        label,
+        elapsed,
+        elapsed.as_nanos() as f64 / PARSE_ITERATIONS as f64
+    );
+}
@@ -1,6 +1,6 @@
 use goldfix::{
     FixEngine,
-    config::{EngineConfig, SessionConfig, LogConfig, SessionRole},
+    config::{EngineConfig, SessionConfig, LogConfig},
     transport::TransportConfig,
     message::{Field, field},
     session::state,  // Fixed import
@@ -27,18 +27,17 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             log_events: true,
             log_messages: true,
         },
+        store_directory: PathBuf::from("store"),
+        store_fsync: Default::default(),
+        store_retention: Default::default(),
         sessions: vec![
             SessionConfig {
-                begin_string: "FIX.4.2".to_string(),
                 sender_comp_id: "RECOVERY_CLIENT".to_string(),
                 target_comp_id: "RECOVERY_SERVER".to_string(),
                 target_addr: "127.0.0.1:8001".to_string(),
-                heart_bt_int: 30,
                 reset_on_logon: false,  // Don't reset on logon for recovery testing
-                reset_on_logout: false,
-                reset_on_disconnect: false,
                 transport_config: Some(transport_config),
-                role: SessionRole::Initiator,
+                ..Default::default()
             }
         ],
     };
@@ -68,7 +67,7 @@ async fn test_recovery_scenarios(engine: &FixEngine) -> Result<(), Box<dyn std::
     // Send a batch of messages before disconnection
     for i in 1..=5 {
         let mut msg = message_pool.get_message(field::values::NEW_ORDER_SINGLE).await;
-        msg.set_field(Field::new(field::ORDER_ID, &format!("ORDER_{}", i)))?;
+        msg.set_field(Field::new(field::ORDER_ID, format!("ORDER_{}", i)))?;
         msg.set_field(Field::new(field::SYMBOL, "AAPL"))?;
         msg.set_field(Field::new(field::SIDE, "1"))?;  // Buy
         msg.set_field(Field::new(field::ORDER_QTY, "100"))?;
@@ -85,7 +84,7 @@ async fn test_recovery_scenarios(engine: &FixEngine) -> Result<(), Box<dyn std::
     // Test 3: Session state recovery
     println!("Testing session state recovery...");
     // Force disconnect and reconnect
-    let session = engine.get_session("RECOVERY_CLIENT_RECOVERY_SERVER").await?;
+    let session = engine.get_session(&"FIX.4.2:RECOVERY_CLIENT->RECOVERY_SERVER".parse()?).await?;
     session.disconnect().await?;
     tokio::time::sleep(Duration::from_secs(2)).await;
 
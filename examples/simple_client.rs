@@ -27,18 +27,20 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             log_events: true,
             log_messages: true,
         },
+        store_directory: PathBuf::from("store"),
+        store_fsync: Default::default(),
+        store_retention: Default::default(),
         sessions: vec![
             SessionConfig {
-                begin_string: "FIX.4.2".to_string(),
                 sender_comp_id: "CLIENT".to_string(),
                 target_comp_id: "SERVER".to_string(),
                 target_addr: "127.0.0.1:8000".to_string(),
-                heart_bt_int: 30,
                 reset_on_logon: true,
                 reset_on_logout: true,
                 reset_on_disconnect: true,
                 transport_config: Some(transport_config),
                 role: SessionRole::Initiator,  // Explicitly set as initiator
+                ..Default::default()
             }
         ],
     };
@@ -1,6 +1,6 @@
 use goldfix::{
     FixEngine,
-    config::{EngineConfig, SessionConfig, LogConfig, SessionRole},
+    config::{EngineConfig, SessionConfig, LogConfig},
     transport::TransportConfig,
 };
 use std::path::PathBuf;
@@ -27,18 +27,19 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             log_events: true,
             log_messages: true,
         },
+        store_directory: PathBuf::from("store"),
+        store_fsync: Default::default(),
+        store_retention: Default::default(),
         sessions: vec![
             SessionConfig {
-                begin_string: "FIX.4.2".to_string(),
                 sender_comp_id: "SSL_CLIENT".to_string(),
                 target_comp_id: "SSL_SERVER".to_string(),
                 target_addr: "127.0.0.1:8443".to_string(),
-                heart_bt_int: 30,
                 reset_on_logon: true,
                 reset_on_logout: true,
                 reset_on_disconnect: true,
                 transport_config: Some(transport_config),
-                role: SessionRole::Initiator,
+                ..Default::default()
             }
         ],
     };
@@ -76,16 +77,14 @@ async fn test_error_scenarios(engine: &FixEngine) -> Result<(), Box<dyn std::err
 
     // Add session with invalid cert (should fail)
     let result = engine.add_session(SessionConfig {
-        begin_string: "FIX.4.2".to_string(),
         sender_comp_id: "INVALID_CERT_CLIENT".to_string(),
         target_comp_id: "SSL_SERVER".to_string(),
         target_addr: "127.0.0.1:8443".to_string(),
-        heart_bt_int: 30,
         reset_on_logon: true,
         reset_on_logout: true,
         reset_on_disconnect: true,
         transport_config: Some(config_invalid_cert),
-        role: SessionRole::Initiator,
+        ..Default::default()
     }).await;
 
     assert!(result.is_err(), "Expected error with invalid certificate");
@@ -102,16 +101,14 @@ async fn test_error_scenarios(engine: &FixEngine) -> Result<(), Box<dyn std::err
     };
 
     let result = engine.add_session(SessionConfig {
-        begin_string: "FIX.4.2".to_string(),
         sender_comp_id: "WRONG_HOST_CLIENT".to_string(),
         target_comp_id: "SSL_SERVER".to_string(),
         target_addr: "wrong.example.com:8443".to_string(),
-        heart_bt_int: 30,
         reset_on_logon: true,
         reset_on_logout: true,
         reset_on_disconnect: true,
         transport_config: Some(config_wrong_hostname),
-        role: SessionRole::Initiator,
+        ..Default::default()
     }).await;
 
     assert!(result.is_err(), "Expected error with wrong hostname");
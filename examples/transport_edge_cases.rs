@@ -1,6 +1,6 @@
 use goldfix::{
     FixEngine,
-    config::{EngineConfig, SessionConfig, LogConfig, SessionRole},
+    config::{EngineConfig, SessionConfig, LogConfig},
     transport::TransportConfig,
 };
 use std::path::PathBuf;
@@ -22,16 +22,14 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     };
 
     let session_config = SessionConfig {
-        begin_string: "FIX.4.2".to_string(),
         sender_comp_id: "EDGE_CLIENT".to_string(),
         target_comp_id: "EDGE_SERVER".to_string(),
         target_addr: "127.0.0.1:8444".to_string(),
-        heart_bt_int: 30,
         reset_on_logon: true,
         reset_on_logout: true,
         reset_on_disconnect: true,
         transport_config: Some(config),
-        role: SessionRole::Initiator,
+        ..Default::default()
     };
 
     // Test case 2: Connection timeout
@@ -47,16 +45,14 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     };
 
     let timeout_session = SessionConfig {
-        begin_string: "FIX.4.2".to_string(),
         sender_comp_id: "TIMEOUT_CLIENT".to_string(),
         target_comp_id: "TIMEOUT_SERVER".to_string(),
         target_addr: "192.0.2.1:12345".to_string(), // Non-existent address
-        heart_bt_int: 30,
         reset_on_logon: true,
         reset_on_logout: true,
         reset_on_disconnect: true,
         transport_config: Some(timeout_config),
-        role: SessionRole::Initiator,
+        ..Default::default()
     };
 
     // Test case 3: Buffer overflow handling
@@ -75,16 +71,14 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     };
 
     let buffer_session = SessionConfig {
-        begin_string: "FIX.4.2".to_string(),
         sender_comp_id: "BUFFER_CLIENT".to_string(),
         target_comp_id: "BUFFER_SERVER".to_string(),
         target_addr: addr.to_string(),
-        heart_bt_int: 30,
         reset_on_logon: true,
         reset_on_logout: true,
         reset_on_disconnect: true,
         transport_config: Some(buffer_config),
-        role: SessionRole::Initiator,
+        ..Default::default()
     };
 
     // Create and configure the engine
@@ -95,6 +89,9 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             log_events: true,
             log_messages: true,
         },
+        store_directory: PathBuf::from("store"),
+        store_fsync: Default::default(),
+        store_retention: Default::default(),
         sessions: vec![
             session_config,
             timeout_session,
@@ -2,17 +2,21 @@ use crate::{
     config::{SessionConfig, SessionRole},
     logging::Logger,
     message::MessagePool,
-    session::Session,
+    session::{Session, SessionID, SessionRegistry},
     store::MessageStore,
     Result,
 };
 use std::sync::Arc;
-use tokio::sync::Mutex;
+use std::time::Duration;
 use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::Mutex;
 use tokio::task::JoinHandle;
 
+// How long a replaced session may take to release its store before the new connection is rejected
+const STORE_RELEASE_TIMEOUT: Duration = Duration::from_secs(1);
+
 pub struct Acceptor {
-    sessions: Arc<Mutex<Vec<Session>>>,
+    registry: SessionRegistry,
     logger: Arc<Logger>,
     store: Arc<MessageStore>,
     message_pool: Arc<MessagePool>,
@@ -21,9 +25,14 @@ pub struct Acceptor {
 }
 
 impl Acceptor {
-    pub fn new(logger: Arc<Logger>, store: Arc<MessageStore>, message_pool: Arc<MessagePool>) -> Self {
+    pub fn new(
+        logger: Arc<Logger>,
+        store: Arc<MessageStore>,
+        message_pool: Arc<MessagePool>,
+        registry: SessionRegistry,
+    ) -> Self {
         Acceptor {
-            sessions: Arc::new(Mutex::new(Vec::new())),
+            registry,
             logger,
             store,
             message_pool,
@@ -33,36 +42,64 @@ impl Acceptor {
     }
 
     async fn handle_connection(
-        sessions: Arc<Mutex<Vec<Session>>>,
+        registry: SessionRegistry,
         socket: TcpStream,
         logger: Arc<Logger>,
         store: Arc<MessageStore>,
         message_pool: Arc<MessagePool>,
+        opening: Arc<Mutex<()>>,
     ) {
         let peer_addr = socket.peer_addr().unwrap_or_else(|_| "unknown".parse().unwrap());
 
         let session_config = SessionConfig {
-            begin_string: "FIX.4.2".to_string(),
             sender_comp_id: "ACCEPTOR".to_string(),
             target_comp_id: "INITIATOR".to_string(),
             target_addr: peer_addr.to_string(),
-            heart_bt_int: 30,
             reset_on_logon: true,
             reset_on_logout: true,
             reset_on_disconnect: true,
-            transport_config: None,
             role: SessionRole::Acceptor,
+            ..Default::default()
+        };
+
+        // One connection at a time swaps the session for an identity, so two can't race for its store
+        let _opening = opening.lock().await;
+
+        // A new connection for an existing identity replaces the previous session, which first
+        // has to let go of the session's store
+        let session_id = SessionID::from_config(&session_config);
+        let replacing = match registry.remove(&session_id) {
+            Some(previous) => {
+                previous.disconnect().await.ok();
+                true
+            }
+            None => false,
         };
 
-        let session = Session::new(
-            session_config,
-            Arc::clone(&logger),
-            Arc::clone(&store),
-            Arc::clone(&message_pool),
-        );
+        let deadline = tokio::time::Instant::now() + STORE_RELEASE_TIMEOUT;
+        let session = loop {
+            match Session::new(
+                session_config.clone(),
+                Arc::clone(&logger),
+                Arc::clone(&store),
+                Arc::clone(&message_pool),
+                registry.event_sender(),
+            ) {
+                Ok(session) => break session,
+                // The previous session's actor releases the store once its last handle is gone
+                Err(_) if replacing && tokio::time::Instant::now() < deadline => {
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                }
+                Err(e) => {
+                    logger.log_event("ERROR", &format!(
+                        "Rejecting connection from {}: failed to open session store: {}", peer_addr, e
+                    )).ok();
+                    return;
+                }
+            }
+        };
 
-        let mut sessions = sessions.lock().await;
-        sessions.push(session);
+        registry.replace(session);
     }
 
     pub async fn start(&mut self, bind_addr: &str) -> Result<()> {
@@ -70,26 +107,29 @@ impl Acceptor {
         self.listener = Some(Arc::new(listener));
         let listener = Arc::clone(self.listener.as_ref().unwrap());
 
-        let sessions = Arc::clone(&self.sessions);
+        let registry = self.registry.clone();
         let logger = Arc::clone(&self.logger);
         let store = Arc::clone(&self.store);
         let message_pool = Arc::clone(&self.message_pool);
+        let opening = Arc::new(Mutex::new(()));
 
         let handle = tokio::spawn(async move {
             loop {
                 if let Ok((socket, _)) = listener.accept().await {
-                    let sessions = Arc::clone(&sessions);
+                    let registry = registry.clone();
                     let logger = Arc::clone(&logger);
                     let store = Arc::clone(&store);
                     let message_pool = Arc::clone(&message_pool);
+                    let opening = Arc::clone(&opening);
 
                     tokio::spawn(async move {
                         Self::handle_connection(
-                            sessions,
+                            registry,
                             socket,
                             logger,
                             store,
                             message_pool,
+                            opening,
                         ).await;
                     });
                 }
@@ -107,17 +147,15 @@ impl Acceptor {
 
         self.listener = None;
 
-        let mut sessions = self.sessions.lock().await;
-        for session in sessions.iter_mut() {
+        for session in self.sessions() {
             session.stop().await?;
         }
         Ok(())
     }
 
     pub async fn active_session_count(&self) -> usize {
-        let sessions = self.sessions.lock().await;
         let mut count = 0;
-        for session in sessions.iter() {
+        for session in self.sessions() {
             if session.is_connected().await {
                 count += 1;
             }
@@ -126,8 +164,15 @@ impl Acceptor {
     }
 
     pub async fn get_sessions(&self) -> Vec<Session> {
-        let sessions = self.sessions.lock().await;
-        sessions.clone()
+        self.sessions().iter().map(|session| (**session).clone()).collect()
+    }
+
+    // Acceptor sessions in the shared registry
+    fn sessions(&self) -> Vec<Arc<Session>> {
+        self.registry.sessions()
+            .into_iter()
+            .filter(|session| session.config.is_acceptor())
+            .collect()
     }
 }
 
@@ -136,6 +181,7 @@ mod tests {
     use super::*;
     use std::path::PathBuf;
     use crate::config::LogConfig;
+    use crate::session::SessionEvent;
 
     #[tokio::test]
     async fn test_acceptor_lifecycle() {
@@ -145,12 +191,53 @@ mod tests {
             log_events: true,
             log_messages: true,
         }));
-        let store = Arc::new(MessageStore::new());
+        let temp_dir = tempfile::tempdir().unwrap();
+        let store = Arc::new(MessageStore::with_dir(temp_dir.path().to_path_buf()));
         let message_pool = Arc::new(MessagePool::new());
-        let mut acceptor = Acceptor::new(logger, store, message_pool);
+        let mut acceptor = Acceptor::new(logger, store, message_pool, SessionRegistry::new());
 
         assert!(acceptor.start("127.0.0.1:0").await.is_ok());
         assert!(acceptor.stop().await.is_ok());
         assert_eq!(acceptor.active_session_count().await, 0);
     }
+
+    #[tokio::test]
+    async fn test_acceptor_second_connection_replaces_session() {
+        let logger = Arc::new(Logger::new(&LogConfig {
+            log_directory: PathBuf::from("/tmp"),
+            log_level: "DEBUG".to_string(),
+            log_events: true,
+            log_messages: true,
+        }));
+        let temp_dir = tempfile::tempdir().unwrap();
+        let store = Arc::new(MessageStore::with_dir(temp_dir.path().to_path_buf()));
+        let registry = SessionRegistry::new();
+        let mut events = registry.subscribe();
+        let mut acceptor = Acceptor::new(logger, store, Arc::new(MessagePool::new()), registry.clone());
+        acceptor.start("127.0.0.1:0").await.unwrap();
+        let addr = acceptor.listener.as_ref().unwrap().local_addr().unwrap();
+
+        // Both connections hold the same identity, and so the same file store
+        let _first = TcpStream::connect(addr).await.unwrap();
+        created(&mut events).await;
+        let first_peer = registry.sessions()[0].config.target_addr.clone();
+
+        let second = TcpStream::connect(addr).await.unwrap();
+        created(&mut events).await;
+
+        let sessions = registry.sessions();
+        assert_eq!(sessions.len(), 1);
+        assert_ne!(sessions[0].config.target_addr, first_peer);
+        assert_eq!(sessions[0].config.target_addr, second.local_addr().unwrap().to_string());
+        drop(sessions);
+
+        acceptor.stop().await.ok();
+    }
+
+    // Wait for the next session to be registered, skipping the replaced one's disconnect
+    async fn created(events: &mut tokio::sync::broadcast::Receiver<SessionEvent>) {
+        tokio::time::timeout(Duration::from_secs(2), async {
+            while !matches!(events.recv().await.unwrap(), SessionEvent::Created(_)) {}
+        }).await.unwrap();
+    }
 }
\ No newline at end of file
@@ -5,6 +5,13 @@ use crate::transport::TransportConfig;
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EngineConfig {
     pub log_config: LogConfig,
+    /// Root of the message store, holding one directory per session
+    #[serde(default = "default_store_directory")]
+    pub store_directory: PathBuf,
+    #[serde(default)]
+    pub store_fsync: FsyncPolicy,
+    #[serde(default)]
+    pub store_retention: RetentionPolicy,
     pub sessions: Vec<SessionConfig>,
 }
 
@@ -16,25 +23,128 @@ pub struct LogConfig {
     pub log_messages: bool,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
 pub enum SessionRole {
     #[serde(rename = "initiator")]
+    #[default]
     Initiator,
     #[serde(rename = "acceptor")]
     Acceptor,
 }
 
-impl Default for SessionRole {
+/// When a session's persisted state (its status) is written to disk. Sequence numbers are kept
+/// by the message store, as durably as its [`FsyncPolicy`] makes them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum StateSyncPolicy {
+    /// Write after every change, coalescing changes made while a write is in progress
+    #[serde(rename = "every_message")]
+    #[default]
+    EveryMessage,
+    /// Write at most once per interval
+    #[serde(rename = "periodic")]
+    Periodic { interval_ms: u64 },
+    /// Write only when the session logs out or stops
+    #[serde(rename = "on_shutdown")]
+    OnShutdown,
+}
+
+/// How long stored messages are kept and what happens to them on a sequence reset
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetentionPolicy {
+    /// Compaction drops messages sent longer ago than this
+    pub max_age_secs: Option<u64>,
+    /// Compaction keeps only this many of the newest messages, none if 0
+    pub max_messages: Option<usize>,
+    /// Write the messages to a compressed archive before a reset drops them
+    pub archive_on_reset: bool,
+    /// Delete a session's oldest archives beyond this many
+    pub max_archives: Option<usize>,
+}
+
+/// When appends to the message store are forced to disk, and so how often a file store writes
+/// its sequence numbers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum FsyncPolicy {
+    /// After every append
+    #[serde(rename = "always")]
+    #[default]
+    Always,
+    /// On the first append once the interval has passed since the last sync
+    #[serde(rename = "interval")]
+    Interval { interval_ms: u64 },
+    /// Left to the operating system, and done when the store is closed
+    #[serde(rename = "never")]
+    Never,
+}
+
+/// What a session does when its next outbound MsgSeqNum reaches the limit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum SeqNumRollover {
+    /// Send a SequenceReset with NewSeqNo 1 and carry on from there
+    #[serde(rename = "sequence_reset")]
+    SequenceReset,
+    /// Log out, resetting the sequence numbers once the Logout is answered
+    #[serde(rename = "logout")]
+    #[default]
+    Logout,
+}
+
+/// The highest outbound MsgSeqNum of a session and what happens on reaching it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SeqNumLimit {
+    /// At least 2, leaving room for the Logon
+    pub max_seq_num: u64,
+    pub action: SeqNumRollover,
+}
+
+impl Default for SeqNumLimit {
     fn default() -> Self {
-        SessionRole::Initiator
+        SeqNumLimit {
+            max_seq_num: u64::MAX,
+            action: SeqNumRollover::default(),
+        }
     }
 }
 
+/// Where a session keeps its sent messages and sequence numbers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum StoreKind {
+    /// Files in the engine's store directory
+    #[serde(rename = "file")]
+    #[default]
+    File,
+    /// Memory only, lost on restart
+    #[serde(rename = "memory")]
+    Memory,
+    /// Only sequence numbers are tracked, no messages can be resent
+    #[serde(rename = "null")]
+    Null,
+    /// The `store.db` SQLite database in the engine's store directory, shared by all sessions
+    #[cfg(feature = "sqlite")]
+    #[serde(rename = "sqlite")]
+    Sqlite,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SessionConfig {
     pub begin_string: String,
     pub sender_comp_id: String,
     pub target_comp_id: String,
+    /// Optional identity qualifiers (SenderSubID, TargetSubID, SenderLocationID,
+    /// TargetLocationID) stamped on outbound messages and required on inbound ones
+    #[serde(default)]
+    pub sender_sub_id: Option<String>,
+    #[serde(default)]
+    pub target_sub_id: Option<String>,
+    #[serde(default)]
+    pub sender_location_id: Option<String>,
+    #[serde(default)]
+    pub target_location_id: Option<String>,
+    /// Distinguishes sessions that otherwise share the same identity
+    #[serde(default)]
+    pub session_qualifier: Option<String>,
     pub target_addr: String,
     pub heart_bt_int: u32,
     pub reset_on_logon: bool,
@@ -43,6 +153,104 @@ pub struct SessionConfig {
     pub transport_config: Option<TransportConfig>,
     #[serde(default)]
     pub role: SessionRole,
+    /// Hold application messages sent while logged out and deliver them after the next logon
+    #[serde(default)]
+    pub queue_while_disconnected: bool,
+    /// Seconds to wait for the counterparty's Logon reply before closing the connection
+    #[serde(default = "default_logon_timeout")]
+    pub logon_timeout: u64,
+    /// Seconds to wait for the counterparty's Logout reply before closing the connection
+    #[serde(default = "default_logout_timeout")]
+    pub logout_timeout: u64,
+    #[serde(default)]
+    pub state_sync: StateSyncPolicy,
+    #[serde(default)]
+    pub store: StoreKind,
+    /// Also keep received messages, apart from the sent ones, for audit
+    #[serde(default)]
+    pub store_incoming: bool,
+    #[serde(default)]
+    pub seq_num_limit: SeqNumLimit,
+    #[serde(default)]
+    pub validation: ValidationConfig,
+}
+
+/// Which checks are applied to inbound messages of a session
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ValidationConfig {
+    /// Reject messages whose header, body and trailer fields are not in order
+    pub validate_fields_out_of_order: bool,
+    /// Reject fields present without a value
+    pub validate_fields_have_values: bool,
+    /// Reject user-defined fields (tag 5000 and above) that are not defined. Off by default,
+    /// as the built-in field list is far from a full dictionary.
+    pub validate_user_defined_fields: bool,
+    /// Accept undefined fields below the user-defined range. On by default for the same reason.
+    pub allow_unknown_msg_fields: bool,
+    /// Reject messages whose SendingTime is more than `max_latency` seconds from now
+    pub check_latency: bool,
+    pub max_latency: u64,
+    /// Reject messages whose SenderCompID/TargetCompID do not match the session
+    pub check_comp_id: bool,
+}
+
+impl Default for ValidationConfig {
+    fn default() -> Self {
+        ValidationConfig {
+            validate_fields_out_of_order: true,
+            validate_fields_have_values: true,
+            validate_user_defined_fields: false,
+            allow_unknown_msg_fields: true,
+            check_latency: true,
+            max_latency: 120,
+            check_comp_id: true,
+        }
+    }
+}
+
+fn default_store_directory() -> PathBuf {
+    PathBuf::from("store")
+}
+
+fn default_logon_timeout() -> u64 {
+    10
+}
+
+fn default_logout_timeout() -> u64 {
+    2
+}
+
+/// A FIX.4.2 initiator with the defaults a config file would get. The CompIDs and
+/// `target_addr` are left empty and must be set; nothing is reset unless asked for.
+impl Default for SessionConfig {
+    fn default() -> Self {
+        SessionConfig {
+            begin_string: "FIX.4.2".to_string(),
+            sender_comp_id: String::new(),
+            target_comp_id: String::new(),
+            sender_sub_id: None,
+            target_sub_id: None,
+            sender_location_id: None,
+            target_location_id: None,
+            session_qualifier: None,
+            target_addr: String::new(),
+            heart_bt_int: 30,
+            reset_on_logon: false,
+            reset_on_logout: false,
+            reset_on_disconnect: false,
+            transport_config: None,
+            role: SessionRole::default(),
+            queue_while_disconnected: false,
+            logon_timeout: default_logon_timeout(),
+            logout_timeout: default_logout_timeout(),
+            state_sync: StateSyncPolicy::default(),
+            store: StoreKind::default(),
+            store_incoming: false,
+            seq_num_limit: SeqNumLimit::default(),
+            validation: ValidationConfig::default(),
+        }
+    }
 }
 
 impl SessionConfig {
@@ -70,6 +278,12 @@ impl SessionConfig {
         if self.heart_bt_int == 0 {
             return Err(crate::error::FixError::ConfigError("heart_bt_int must be greater than 0".into()));
         }
+        if self.validation.check_latency && self.validation.max_latency == 0 {
+            return Err(crate::error::FixError::ConfigError("max_latency must be greater than 0".into()));
+        }
+        if self.seq_num_limit.max_seq_num < 2 {
+            return Err(crate::error::FixError::ConfigError("seq_num_limit.max_seq_num must be at least 2".into()));
+        }
         Ok(())
     }
 }
@@ -99,4 +313,27 @@ impl EngineConfig {
 
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_seq_num_limit() {
+        let mut config = SessionConfig {
+            sender_comp_id: "SENDER".to_string(),
+            target_comp_id: "TARGET".to_string(),
+            target_addr: "127.0.0.1:9876".to_string(),
+            ..Default::default()
+        };
+        assert!(config.validate().is_ok());
+
+        for max_seq_num in [0, 1] {
+            config.seq_num_limit.max_seq_num = max_seq_num;
+            assert!(matches!(config.validate(), Err(crate::error::FixError::ConfigError(_))));
+        }
+        config.seq_num_limit.max_seq_num = 2;
+        assert!(config.validate().is_ok());
+    }
+}
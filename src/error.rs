@@ -19,6 +19,8 @@ pub enum FixError {
     CertificateError(String),
     SessionNotFound(String),
     InvalidConfiguration(String),  // Added InvalidConfiguration variant
+    NotLoggedOn(String),
+    VersionConflict(String),
 }
 
 impl fmt::Display for FixError {
@@ -36,6 +38,8 @@ impl fmt::Display for FixError {
             FixError::CertificateError(msg) => write!(f, "Certificate error: {}", msg),
             FixError::SessionNotFound(msg) => write!(f, "Session not found: {}", msg),
             FixError::InvalidConfiguration(msg) => write!(f, "Invalid configuration: {}", msg),
+            FixError::NotLoggedOn(msg) => write!(f, "Not logged on: {}", msg),
+            FixError::VersionConflict(msg) => write!(f, "Version conflict: {}", msg),
         }
     }
 }
@@ -3,24 +3,28 @@ use crate::{
     error::FixError,
     logging::Logger,
     message::MessagePool,
-    session::Session,
+    session::{Session, SessionRegistry},
     store::MessageStore,
     Result,
 };
 use std::sync::Arc;
-use tokio::sync::Mutex;
 
 pub struct Initiator {
-    sessions: Arc<Mutex<Vec<Session>>>,
+    registry: SessionRegistry,
     logger: Arc<Logger>,
     store: Arc<MessageStore>,
     message_pool: Arc<MessagePool>,
 }
 
 impl Initiator {
-    pub fn new(logger: Arc<Logger>, store: Arc<MessageStore>, message_pool: Arc<MessagePool>) -> Self {
+    pub fn new(
+        logger: Arc<Logger>,
+        store: Arc<MessageStore>,
+        message_pool: Arc<MessagePool>,
+        registry: SessionRegistry,
+    ) -> Self {
         Initiator {
-            sessions: Arc::new(Mutex::new(Vec::new())),
+            registry,
             logger,
             store,
             message_pool,
@@ -39,37 +43,27 @@ impl Initiator {
             Arc::clone(&self.logger),
             Arc::clone(&self.store),
             Arc::clone(&self.message_pool),
-        );
+            self.registry.event_sender(),
+        )?;
 
-        // Add session to managed sessions
-        {
-            let mut sessions = self.sessions.lock().await;
-            sessions.push(session.clone());
-        }
+        // Register the session; fails if its identity is already in use
+        self.registry.insert(session.clone())?;
 
-        // Start the session
+        // Start the session, deregistering it again if it cannot connect
         #[cfg(not(test))]
-        {
-            match session.start().await {
-                Ok(_) => Ok(()),
-                Err(e) => {
-                    // Clean up on error
-                    let mut sessions = self.sessions.lock().await;
-                    if let Some(pos) = sessions.iter().position(|s| s.session_id() == session.session_id()) {
-                        sessions.remove(pos);
-                    }
-                    Err(e)
-                }
-            }
+        if let Err(e) = session.start().await {
+            self.logger.log_event("ERROR", &format!(
+                "Failed to start session {}: {}", session.session_id(), e
+            )).ok();
+            self.registry.remove(session.session_id());
+            return Err(e);
         }
 
-        #[cfg(test)]
         Ok(())
     }
 
     pub async fn stop_all(&self) -> Result<()> {
-        let sessions = self.sessions.lock().await;
-        for session in sessions.iter() {
+        for session in self.sessions() {
             if let Err(e) = session.stop().await {
                 self.logger.log_event("ERROR", &format!("Failed to stop session: {}", e)).ok();
             }
@@ -78,9 +72,8 @@ impl Initiator {
     }
 
     pub async fn active_session_count(&self) -> usize {
-        let sessions = self.sessions.lock().await;
         let mut count = 0;
-        for session in sessions.iter() {
+        for session in self.sessions() {
             if session.is_connected().await {
                 count += 1;
             }
@@ -89,8 +82,15 @@ impl Initiator {
     }
 
     pub async fn get_sessions(&self) -> Vec<Session> {
-        let sessions = self.sessions.lock().await;
-        sessions.clone()
+        self.sessions().iter().map(|session| (**session).clone()).collect()
+    }
+
+    // Initiator sessions in the shared registry
+    fn sessions(&self) -> Vec<Arc<Session>> {
+        self.registry.sessions()
+            .into_iter()
+            .filter(|session| session.config.is_initiator())
+            .collect()
     }
 }
 
@@ -99,6 +99,7 @@ mod tests {
     use super::*;
     use std::path::PathBuf;
     use crate::config::LogConfig;
+    use crate::session::SessionID;
 
     #[tokio::test]
     async fn test_initiator_session_management() {
@@ -108,24 +109,26 @@ mod tests {
             log_events: true,
             log_messages: true,
         }));
-        let store = Arc::new(MessageStore::new());
+        let temp_dir = tempfile::tempdir().unwrap();
+        let store = Arc::new(MessageStore::with_dir(temp_dir.path().to_path_buf()));
         let message_pool = Arc::new(MessagePool::new());
-        let initiator = Initiator::new(logger, store, message_pool);
+        let registry = SessionRegistry::new();
+        let initiator = Initiator::new(logger, store, message_pool, registry.clone());
 
         let config = SessionConfig {
-            begin_string: "FIX.4.2".to_string(),
             sender_comp_id: "TEST_INITIATOR".to_string(),
             target_comp_id: "TEST_ACCEPTOR".to_string(),
             target_addr: "127.0.0.1:0".to_string(),
-            heart_bt_int: 30,
             reset_on_logon: true,
             reset_on_logout: true,
             reset_on_disconnect: true,
-            transport_config: None,
-            role: SessionRole::Initiator,
+            ..Default::default()
         };
 
-        assert!(initiator.start_session(config).await.is_ok());
+        assert!(initiator.start_session(config.clone()).await.is_ok());
+        assert!(registry.get(&SessionID::from_config(&config)).is_some());
+        // The same identity cannot be started twice
+        assert!(initiator.start_session(config).await.is_err());
         assert_eq!(initiator.active_session_count().await, 0); //This assertion is likely to fail due to issues in the Session struct.
         assert!(initiator.stop_all().await.is_ok());
     }
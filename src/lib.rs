@@ -15,6 +15,7 @@ use std::sync::Arc;
 use tokio::sync::Mutex;
 
 pub use error::FixError;
+pub use session::SessionID;
 pub use initiator::Initiator;
 pub use acceptor::Acceptor;
 pub type Result<T> = std::result::Result<T, FixError>;
@@ -33,14 +34,18 @@ pub struct FixEngine {
     #[allow(dead_code)]
     store: Arc<store::MessageStore>,
     message_pool: Arc<message::MessagePool>,
+    registry: session::SessionRegistry,
     config: Arc<config::EngineConfig>,
 }
 
 impl FixEngine {
     pub fn new(config: config::EngineConfig) -> Self {
         let logger = Arc::new(logging::Logger::new(&config.log_config));
-        let store = Arc::new(store::MessageStore::new());
+        let store = Arc::new(store::MessageStore::with_dir(config.store_directory.clone())
+            .with_fsync(config.store_fsync)
+            .with_retention(config.store_retention.clone()));
         let message_pool = Arc::new(message::MessagePool::new());
+        let registry = session::SessionRegistry::new();
         let config = Arc::new(config);
 
         FixEngine {
@@ -48,35 +53,38 @@ impl FixEngine {
                 Arc::clone(&logger),
                 Arc::clone(&store),
                 Arc::clone(&message_pool),
+                registry.clone(),
             ))),
             acceptor: Some(Arc::new(Mutex::new(Acceptor::new(
                 Arc::clone(&logger),
                 Arc::clone(&store),
                 Arc::clone(&message_pool),
+                registry.clone(),
             )))),
             logger,
             store,
             message_pool,
+            registry,
             config,
         }
     }
 
     pub async fn start(&self) -> Result<()> {
-        // Start initiator sessions if configured
-        if let Some(ref initiator) = self.initiator {
+        // Start acceptor first so local initiators can connect to it
+        if let Some(ref acceptor) = self.acceptor {
+            let mut acceptor = acceptor.lock().await;
             for session in &self.config.sessions {
-                if session.is_initiator() {
-                    initiator.start_session(session.clone()).await?;
+                if session.is_acceptor() {
+                    acceptor.start(&session.target_addr).await?;
                 }
             }
         }
 
-        // Start acceptor if configured
-        if let Some(ref acceptor) = self.acceptor {
-            let mut acceptor = acceptor.lock().await;
+        // Start initiator sessions if configured
+        if let Some(ref initiator) = self.initiator {
             for session in &self.config.sessions {
-                if session.is_acceptor() {
-                    acceptor.start(&session.target_addr).await?;
+                if session.is_initiator() {
+                    initiator.start_session(session.clone()).await?;
                 }
             }
         }
@@ -108,64 +116,37 @@ impl FixEngine {
         Arc::clone(&self.message_pool)
     }
 
-    /// Get a session by its ID (format: "SENDER_COMP_ID_TARGET_COMP_ID")
-    pub async fn get_session(&self, session_id: &str) -> Result<Arc<session::Session>> {
-        let parts: Vec<&str> = session_id.split('_').collect();
-        if parts.len() != 2 {
-            return Err(FixError::ConfigError("Invalid session ID format".into()));
-        }
-
-        let session_id = session_id.to_string();
-
-        // Check initiator sessions first
-        if let Some(ref initiator) = self.initiator {
-            // Use a method to get sessions instead of accessing the field directly
-            let sessions = initiator.get_sessions().await;
-            for session in sessions.iter() {
-                if format!("{}_{}", 
-                    session.config.sender_comp_id,
-                    session.config.target_comp_id) == session_id {
-                    return Ok(Arc::new(session.clone()));
-                }
-            }
-        }
+    /// Get a session by its ID, e.g. `"FIX.4.2:CLIENT->BROKER".parse()?`
+    pub async fn get_session(&self, session_id: &SessionID) -> Result<Arc<session::Session>> {
+        self.registry.get(session_id)
+            .ok_or_else(|| FixError::SessionNotFound(format!("Session not found: {}", session_id)))
+    }
 
-        // Then check acceptor sessions
-        if let Some(ref acceptor) = self.acceptor {
-            let acceptor = acceptor.lock().await;
-            // Use a method to get sessions instead of accessing the field directly
-            let sessions = acceptor.get_sessions().await;
-            for session in sessions.iter() {
-                if format!("{}_{}", 
-                    session.config.sender_comp_id,
-                    session.config.target_comp_id) == session_id {
-                    return Ok(Arc::new(session.clone()));
-                }
-            }
-        }
+    /// Handle for sending application messages to a session from any task
+    pub fn sender(&self, session_id: &SessionID) -> Result<session::SessionSender> {
+        self.registry.get(session_id)
+            .map(|session| session.sender())
+            .ok_or_else(|| FixError::SessionNotFound(format!("Session not found: {}", session_id)))
+    }
 
-        Err(FixError::SessionNotFound(format!("Session not found: {}", session_id)))
+    /// Queue `message` on the session's outbound queue and wait until it has been sequenced
+    /// and stored, returning its MsgSeqNum
+    pub async fn send_to_target(&self, session_id: &SessionID, message: message::Message) -> Result<u64> {
+        self.sender(session_id)?.send(message).await
     }
 
     /// Get all session IDs
-    pub async fn get_session_ids(&self) -> Vec<String> {
-        let mut ids = Vec::new();
-
-        if let Some(ref initiator) = self.initiator {
-            let sessions = initiator.get_sessions().await;
-            ids.extend(sessions.iter().map(|s| {
-                format!("{}_{}", s.config.sender_comp_id, s.config.target_comp_id)
-            }));
-        }
+    pub async fn get_session_ids(&self) -> Vec<SessionID> {
+        self.registry.ids()
+    }
 
-        if let Some(ref acceptor) = self.acceptor {
-            let acceptor = acceptor.lock().await;
-            let sessions = acceptor.get_sessions().await;
-            ids.extend(sessions.iter().map(|s| {
-                format!("{}_{}", s.config.sender_comp_id, s.config.target_comp_id)
-            }));
-        }
+    /// Sessions of this engine, shared with the initiator and acceptor
+    pub fn registry(&self) -> &session::SessionRegistry {
+        &self.registry
+    }
 
-        ids
+    /// Subscribe to lifecycle events of all sessions
+    pub fn subscribe(&self) -> tokio::sync::broadcast::Receiver<session::SessionEvent> {
+        self.registry.subscribe()
     }
 }
\ No newline at end of file
@@ -1,14 +1,30 @@
+#[derive(Debug, Clone, PartialEq)]
+enum FieldValue {
+    Text(String),
+    /// Length-prefixed data, which may contain SOH, '=' or non-UTF-8 bytes
+    Data(Vec<u8>),
+}
+
 #[derive(Debug, Clone)]
 pub struct Field {
     tag: i32,
-    value: String,
+    value: FieldValue,
 }
 
 impl Field {
     pub fn new(tag: i32, value: impl Into<String>) -> Self {
         Field {
             tag,
-            value: value.into(),
+            value: FieldValue::Text(value.into()),
+        }
+    }
+
+    /// Create a data field (e.g. RawData, XmlData, EncodedText) holding arbitrary bytes.
+    /// The matching length field is written automatically when the message is serialized.
+    pub fn data(tag: i32, value: impl Into<Vec<u8>>) -> Self {
+        Field {
+            tag,
+            value: FieldValue::Data(value.into()),
         }
     }
 
@@ -16,8 +32,24 @@ impl Field {
         self.tag
     }
 
+    /// Value as text. Data fields return their contents only when they are valid UTF-8
+    /// and an empty string otherwise; use [`Field::as_bytes`] for the raw payload.
     pub fn value(&self) -> &str {
-        &self.value
+        match &self.value {
+            FieldValue::Text(value) => value,
+            FieldValue::Data(value) => std::str::from_utf8(value).unwrap_or(""),
+        }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        match &self.value {
+            FieldValue::Text(value) => value.as_bytes(),
+            FieldValue::Data(value) => value,
+        }
+    }
+
+    pub fn is_data(&self) -> bool {
+        matches!(self.value, FieldValue::Data(_))
     }
 }
 
@@ -98,10 +130,153 @@ pub const LAST_MSG_SEQ_NUM_PROCESSED: i32 = 369;
 pub const POSS_DUP_FLAG: i32 = 43;
 pub const ORIG_SENDING_TIME: i32 = 122;
 
+// Session level reject fields
+pub const REF_SEQ_NUM: i32 = 45;
+pub const REF_TAG_ID: i32 = 371;
+pub const REF_MSG_TYPE: i32 = 372;
+pub const SESSION_REJECT_REASON: i32 = 373;
+
+// Routing and session identity header fields
+pub const SENDER_SUB_ID: i32 = 50;
+pub const TARGET_SUB_ID: i32 = 57;
+pub const POSS_RESEND: i32 = 97;
+pub const ON_BEHALF_OF_COMP_ID: i32 = 115;
+pub const DELIVER_TO_COMP_ID: i32 = 128;
+pub const SENDER_LOCATION_ID: i32 = 142;
+pub const TARGET_LOCATION_ID: i32 = 143;
+pub const MESSAGE_ENCODING: i32 = 347;
+
 // Sequence reset and resend request fields
 pub const BEGIN_SEQ_NO: i32 = 7;
 pub const END_SEQ_NO: i32 = 16;
 
+// Length-prefixed data fields
+pub const SIGNATURE: i32 = 89;
+pub const SECURE_DATA_LEN: i32 = 90;
+pub const SECURE_DATA: i32 = 91;
+pub const SIGNATURE_LENGTH: i32 = 93;
+pub const RAW_DATA_LENGTH: i32 = 95;
+pub const RAW_DATA: i32 = 96;
+pub const XML_DATA_LEN: i32 = 212;
+pub const XML_DATA: i32 = 213;
+pub const ENCODED_ISSUER_LEN: i32 = 348;
+pub const ENCODED_ISSUER: i32 = 349;
+pub const ENCODED_SECURITY_DESC_LEN: i32 = 350;
+pub const ENCODED_SECURITY_DESC: i32 = 351;
+pub const ENCODED_LIST_EXEC_INST_LEN: i32 = 352;
+pub const ENCODED_LIST_EXEC_INST: i32 = 353;
+pub const ENCODED_TEXT_LEN: i32 = 354;
+pub const ENCODED_TEXT: i32 = 355;
+pub const ENCODED_SUBJECT_LEN: i32 = 356;
+pub const ENCODED_SUBJECT: i32 = 357;
+pub const ENCODED_HEADLINE_LEN: i32 = 358;
+pub const ENCODED_HEADLINE: i32 = 359;
+pub const ENCODED_ALLOC_TEXT_LEN: i32 = 360;
+pub const ENCODED_ALLOC_TEXT: i32 = 361;
+pub const ENCODED_UNDERLYING_ISSUER_LEN: i32 = 362;
+pub const ENCODED_UNDERLYING_ISSUER: i32 = 363;
+pub const ENCODED_UNDERLYING_SECURITY_DESC_LEN: i32 = 364;
+pub const ENCODED_UNDERLYING_SECURITY_DESC: i32 = 365;
+pub const ENCODED_LIST_STATUS_TEXT_LEN: i32 = 445;
+pub const ENCODED_LIST_STATUS_TEXT: i32 = 446;
+
+/// Standard header fields in the order they are written after BeginString, BodyLength and
+/// MsgType. Length fields of header data fields are written with their data field.
+pub const HEADER_FIELDS: &[i32] = &[
+    SENDER_COMP_ID,
+    TARGET_COMP_ID,
+    ON_BEHALF_OF_COMP_ID,
+    DELIVER_TO_COMP_ID,
+    SECURE_DATA_LEN,
+    SECURE_DATA,
+    MSG_SEQ_NUM,
+    SENDER_SUB_ID,
+    SENDER_LOCATION_ID,
+    TARGET_SUB_ID,
+    TARGET_LOCATION_ID,
+    POSS_DUP_FLAG,
+    POSS_RESEND,
+    SENDING_TIME,
+    ORIG_SENDING_TIME,
+    XML_DATA_LEN,
+    XML_DATA,
+    MESSAGE_ENCODING,
+    LAST_MSG_SEQ_NUM_PROCESSED,
+];
+
+/// Standard trailer fields written before CheckSum
+pub const TRAILER_FIELDS: &[i32] = &[SIGNATURE_LENGTH, SIGNATURE];
+
+pub fn is_header_field(tag: i32) -> bool {
+    matches!(tag, BEGIN_STRING | BODY_LENGTH | MSG_TYPE) || HEADER_FIELDS.contains(&tag)
+}
+
+pub fn is_trailer_field(tag: i32) -> bool {
+    tag == CHECKSUM || TRAILER_FIELDS.contains(&tag)
+}
+
+/// Pairs of (length tag, data tag) for fields whose value is read by length
+pub const DATA_FIELDS: &[(i32, i32)] = &[
+    (SIGNATURE_LENGTH, SIGNATURE),
+    (SECURE_DATA_LEN, SECURE_DATA),
+    (RAW_DATA_LENGTH, RAW_DATA),
+    (XML_DATA_LEN, XML_DATA),
+    (ENCODED_ISSUER_LEN, ENCODED_ISSUER),
+    (ENCODED_SECURITY_DESC_LEN, ENCODED_SECURITY_DESC),
+    (ENCODED_LIST_EXEC_INST_LEN, ENCODED_LIST_EXEC_INST),
+    (ENCODED_TEXT_LEN, ENCODED_TEXT),
+    (ENCODED_SUBJECT_LEN, ENCODED_SUBJECT),
+    (ENCODED_HEADLINE_LEN, ENCODED_HEADLINE),
+    (ENCODED_ALLOC_TEXT_LEN, ENCODED_ALLOC_TEXT),
+    (ENCODED_UNDERLYING_ISSUER_LEN, ENCODED_UNDERLYING_ISSUER),
+    (ENCODED_UNDERLYING_SECURITY_DESC_LEN, ENCODED_UNDERLYING_SECURITY_DESC),
+    (ENCODED_LIST_STATUS_TEXT_LEN, ENCODED_LIST_STATUS_TEXT),
+];
+
+/// Data tag whose length is carried by `tag`, if `tag` is a length field
+pub fn data_tag_for_length(tag: i32) -> Option<i32> {
+    DATA_FIELDS.iter().find(|(len, _)| *len == tag).map(|(_, data)| *data)
+}
+
+/// Length tag carrying the size of `tag`, if `tag` is a data field
+pub fn length_tag_for_data(tag: i32) -> Option<i32> {
+    DATA_FIELDS.iter().find(|(_, data)| *data == tag).map(|(len, _)| *len)
+}
+
+/// First tag number of the user-defined range
+pub const USER_DEFINED_MIN: i32 = 5000;
+
+pub fn is_user_defined_field(tag: i32) -> bool {
+    tag >= USER_DEFINED_MIN
+}
+
+/// Whether `tag` is one of the fields defined in this module
+pub fn is_defined_field(tag: i32) -> bool {
+    matches!(tag,
+        BEGIN_STRING | BODY_LENGTH | MSG_TYPE | SENDER_COMP_ID | TARGET_COMP_ID | MSG_SEQ_NUM |
+        SENDING_TIME | CHECKSUM | HEART_BT_INT | CL_ORD_ID | ORDER_ID | EXEC_ID | EXEC_TRANS_TYPE |
+        EXEC_TYPE | ORD_STATUS | ORD_TYPE | SIDE | SYMBOL | TIME_IN_FORCE | QUANTITY | PRICE |
+        MD_REQ_ID | SUBSCRIPTION_REQ_TYPE | MARKET_DEPTH | MD_UPDATE_TYPE | AGGREGATE_BOOK |
+        NO_MD_ENTRIES | MD_ENTRY_TYPE | MD_ENTRY_PX | MD_ENTRY_SIZE | MD_ENTRY_DATE | MD_ENTRY_TIME |
+        TRADING_SESSION_ID | QUOTE_ID | QUOTE_REQ_ID | BID_PX | OFFER_PX | BID_SIZE | OFFER_SIZE |
+        VALID_UNTIL_TIME | QUOTE_CONDITION | NO_QUOTE_ENTRIES | QUOTE_ENTRY_ID |
+        QUOTE_REJECT_REASON | TEXT | TRANSACTION_TIME | SETTLE_TYPE | SETTLE_DATE | TRADE_DATE |
+        POSITION_EFFECT | STOP_PX | EX_DESTINATION | MIN_QTY | MAX_FLOOR | EXPIRE_TIME |
+        ENCRYPT_METHOD | RESET_SEQ_NUM_FLAG | TEST_REQ_ID | GAP_FILL_FLAG | NEW_SEQ_NO |
+        LAST_MSG_SEQ_NUM_PROCESSED | POSS_DUP_FLAG | ORIG_SENDING_TIME | REF_SEQ_NUM | REF_TAG_ID |
+        REF_MSG_TYPE | SESSION_REJECT_REASON | SENDER_SUB_ID | TARGET_SUB_ID | POSS_RESEND |
+        ON_BEHALF_OF_COMP_ID | DELIVER_TO_COMP_ID | SENDER_LOCATION_ID | TARGET_LOCATION_ID |
+        MESSAGE_ENCODING | BEGIN_SEQ_NO | END_SEQ_NO | SIGNATURE | SECURE_DATA_LEN | SECURE_DATA |
+        SIGNATURE_LENGTH | RAW_DATA_LENGTH | RAW_DATA | XML_DATA_LEN | XML_DATA | ENCODED_ISSUER_LEN |
+        ENCODED_ISSUER | ENCODED_SECURITY_DESC_LEN | ENCODED_SECURITY_DESC |
+        ENCODED_LIST_EXEC_INST_LEN | ENCODED_LIST_EXEC_INST | ENCODED_TEXT_LEN | ENCODED_TEXT |
+        ENCODED_SUBJECT_LEN | ENCODED_SUBJECT | ENCODED_HEADLINE_LEN | ENCODED_HEADLINE |
+        ENCODED_ALLOC_TEXT_LEN | ENCODED_ALLOC_TEXT | ENCODED_UNDERLYING_ISSUER_LEN |
+        ENCODED_UNDERLYING_ISSUER | ENCODED_UNDERLYING_SECURITY_DESC_LEN |
+        ENCODED_UNDERLYING_SECURITY_DESC | ENCODED_LIST_STATUS_TEXT_LEN | ENCODED_LIST_STATUS_TEXT
+    )
+}
+
 // Common values for fields
 pub mod values {
     use once_cell::sync::Lazy;
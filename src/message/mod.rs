@@ -4,6 +4,8 @@ pub mod validator;
 pub mod formatter;
 pub mod pool;
 pub mod group;
+pub mod view;
+pub mod serializer;
 
 use chrono;
 use std::collections::HashMap;
@@ -15,6 +17,8 @@ use crate::Result;
 use crate::error::FixError;
 use crate::message::formatter::FieldFormatter;
 pub use self::pool::MessagePool;
+pub use self::view::MessageView;
+pub use self::serializer::MessageSerializer;
 use self::group::RepeatingGroup;
 
 #[derive(Clone)]
@@ -47,10 +51,24 @@ impl Message {
         msg
     }
 
+    /// Build a message from already parsed fields, without default headers
+    pub(crate) fn from_fields(msg_type: &str, fields: HashMap<i32, Field>) -> Self {
+        Message {
+            fields,
+            msg_type: msg_type.to_string(),
+            formatters: HashMap::new(),
+            groups: HashMap::new(),
+        }
+    }
+
     pub fn from_string(msg_str: &str) -> Result<Self> {
         MessageParser::parse(msg_str)
     }
 
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        MessageParser::parse_bytes(data)?.to_message()
+    }
+
     pub fn set_formatter(&mut self, tag: i32, formatter: impl FieldFormatter + 'static) {
         self.formatters.insert(tag, Arc::new(formatter));
     }
@@ -72,13 +90,13 @@ impl Message {
 
     pub fn set_field(&mut self, field: Field) -> Result<()> {
         let tag = field.tag();
-        let value = if let Some(formatter) = self.formatters.get(&tag) {
-            formatter.format(field.value())?
+        let field = if let Some(formatter) = self.formatters.get(&tag) {
+            Field::new(tag, formatter.format(field.value())?)
         } else {
-            field.value().to_string()
+            field
         };
 
-        self.fields.insert(tag, Field::new(tag, value));
+        self.fields.insert(tag, field);
         Ok(())
     }
 
@@ -94,46 +112,19 @@ impl Message {
         &self.fields
     }
 
-    pub fn to_string(&self) -> Result<String> {
-        let mut msg = String::new();
-
-        // Start with BeginString (tag 8)
-        if let Some(begin_str) = self.get_field(self::field::BEGIN_STRING) {
-            msg.push_str(&format!("8={}\u{1}", begin_str.value()));
-        } else {
-            return Err(FixError::ParseError("Missing BeginString".into()));
-        }
-
-        // Add BodyLength placeholder
-        msg.push_str("9=0000\u{1}");
-
-        // Add MsgType
-        msg.push_str(&format!("35={}\u{1}", self.msg_type));
-
-        // Sort fields by tag number for consistent output
-        let mut sorted_fields: Vec<_> = self.fields.iter().collect();
-        sorted_fields.sort_by_key(|&(k, _)| *k);
-
-        // Add all other fields except BeginString, BodyLength, and Checksum
-        for (&tag, field) in sorted_fields.iter() {
-            if tag != self::field::BEGIN_STRING && tag != self::field::BODY_LENGTH && tag != self::field::CHECKSUM {
-                msg.push_str(&format!("{}={}\u{1}", tag, field.value()));
-            }
-        }
-
-        // Calculate body length (excluding BeginString and Checksum)
-        let body_start = msg.find("9=").unwrap_or(0);
-        let body_length = msg[body_start..].len();
-
-        // Replace body length placeholder
-        let body_length_str = format!("9={:04}\u{1}", body_length);
-        msg = msg.replace("9=0000\u{1}", &body_length_str);
-
-        // Calculate and add checksum
-        let checksum = calculate_checksum(&msg);
-        msg.push_str(&format!("10={:03}\u{1}", checksum));
+    /// Serialize to wire format. Data fields are written as-is, preceded by their length field.
+    ///
+    /// Allocates a buffer per call; repeated sends should serialize into a buffer they keep with
+    /// [`MessageSerializer::serialize_into`].
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let mut buf = Vec::with_capacity(256);
+        MessageSerializer::new().serialize_into(self, &mut buf)?;
+        Ok(buf)
+    }
 
-        Ok(msg)
+    pub fn to_string(&self) -> Result<String> {
+        String::from_utf8(self.to_bytes()?)
+            .map_err(|_| FixError::ParseError("Message contains binary data, use to_bytes".into()))
     }
 
     pub fn add_group(&mut self, tag: i32, delimiter_tag: i32, required_tags: Vec<i32>) -> Result<()> {
@@ -169,10 +160,6 @@ impl Message {
     }
 }
 
-fn calculate_checksum(msg: &str) -> u32 {
-    msg.bytes().map(|b| b as u32).sum::<u32>() % 256
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -245,6 +232,44 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_body_length_and_checksum() {
+        let msg = Message::new(values::HEARTBEAT);
+        let bytes = msg.to_bytes().unwrap();
+
+        let body_start = bytes.windows(3).position(|w| w == b"35=").unwrap();
+        let trailer_start = bytes.len() - 7;
+        let declared = std::str::from_utf8(&bytes[12..body_start - 1]).unwrap();
+        assert_eq!(declared.parse::<usize>().unwrap(), trailer_start - body_start);
+
+        let checksum = bytes[..trailer_start].iter().map(|&b| b as u32).sum::<u32>() % 256;
+        assert!(bytes.ends_with(format!("10={:03}\u{1}", checksum).as_bytes()));
+        assert_eq!(bytes.windows(4).filter(|w| w == b"35=0").count(), 1);
+    }
+
+    #[test]
+    fn test_raw_data_roundtrip() {
+        let payload = b"bin\x01ary=\x0110=123\x01\xff".to_vec();
+        let mut original = Message::new(values::NEW_ORDER_SINGLE);
+        original.set_field(Field::new(super::field::CL_ORD_ID, "12345")).unwrap();
+        original.set_field(Field::data(super::field::RAW_DATA, payload.clone())).unwrap();
+        original.set_field(Field::data(super::field::XML_DATA, "<a b=\"1\"/>")).unwrap();
+
+        let bytes = original.to_bytes().unwrap();
+        assert!(original.to_string().is_err());
+
+        let parsed = Message::from_bytes(&bytes).unwrap();
+        let raw = parsed.get_field(super::field::RAW_DATA).unwrap();
+        assert!(raw.is_data());
+        assert_eq!(raw.as_bytes(), &payload[..]);
+        assert_eq!(
+            parsed.get_field(super::field::RAW_DATA_LENGTH).unwrap().value(),
+            payload.len().to_string()
+        );
+        assert_eq!(parsed.get_field(super::field::XML_DATA).unwrap().value(), "<a b=\"1\"/>");
+        assert_eq!(parsed.get_field(super::field::CL_ORD_ID).unwrap().value(), "12345");
+    }
+
     #[test]
     fn test_message_with_formatters() {
         let mut msg = Message::new(values::NEW_ORDER_SINGLE);
@@ -1,141 +1,192 @@
-use super::{Message, Field};
+use std::str;
+use super::{Message, field};
+use super::view::MessageView;
 use crate::error::FixError;
 use crate::Result;
-use super::field::values;
+
+const SOH: u8 = 1;
+
+/// Largest frame accepted from the wire. A BodyLength beyond it is not trusted, and a reader
+/// that has buffered this much without finding a frame gives up on the connection.
+pub const MAX_FRAME_SIZE: usize = 1 << 20;
 
 pub struct MessageParser;
 
 impl MessageParser {
     pub fn parse(data: &str) -> Result<Message> {
-        let fields: Vec<&str> = data.split('\u{1}').collect();
+        Self::parse_bytes(data.as_bytes())?.to_message()
+    }
 
-        // First pass: find BeginString and MsgType to create the message
-        let mut begin_string = None;
+    /// Parse a frame into a borrowed view without copying field values.
+    ///
+    /// Data fields (e.g. RawData, XmlData, EncodedText) are read using the length declared by
+    /// the field immediately preceding them, so their values may contain SOH or '='.
+    pub fn parse_bytes(data: &[u8]) -> Result<MessageView<'_>> {
+        let mut fields = Vec::with_capacity(32);
+        let mut has_begin_string = false;
         let mut msg_type = None;
+        let mut md_entries = 0;
+        let mut expected_entries = None;
+        // Data tag expected next and its declared length
+        let mut pending_data: Option<(i32, usize)> = None;
+        let mut pos = 0;
 
-        for field_str in fields.iter() {
-            if field_str.is_empty() {
+        while pos < data.len() {
+            if data[pos] == SOH {
+                pos += 1;
                 continue;
             }
 
-            let parts: Vec<&str> = field_str.split('=').collect();
-            if parts.len() != 2 {
-                return Err(FixError::ParseError(
-                    format!("Invalid field format: {}", field_str)
-                ));
-            }
-
-            if let Ok(tag) = parts[0].parse::<i32>() {
-                match tag {
-                    8 => begin_string = Some(parts[1].to_string()),  // BeginString
-                    35 => msg_type = Some(parts[1].to_string()),     // MsgType
-                    _ => continue,
+            let remaining = &data[pos..];
+            let eq = match remaining.iter().position(|&b| b == b'=' || b == SOH) {
+                Some(eq) if remaining[eq] == b'=' => eq,
+                _ => {
+                    let end = remaining.iter().position(|&b| b == SOH).unwrap_or(remaining.len());
+                    return Err(FixError::ParseError(format!(
+                        "Invalid field format: {}", String::from_utf8_lossy(&remaining[..end])
+                    )));
                 }
-            }
-        }
+            };
+            let tag_bytes = &remaining[..eq];
+            let tag = Self::parse_tag(tag_bytes).ok_or_else(|| {
+                FixError::ParseError(format!(
+                    "Invalid tag number: {}", String::from_utf8_lossy(tag_bytes)
+                ))
+            })?;
 
-        // Create message with the correct type
-        let mut message = match (begin_string, msg_type.as_deref()) {
-            (Some(_), Some(msg_type)) if values::COMMON_MESSAGE_TYPES.contains(msg_type) => {
-                Message::new(msg_type)
-            },
-            (Some(_), Some(msg_type)) => Message::new(msg_type),
-            _ => return Err(FixError::ParseError("Missing BeginString or MsgType".into())),
-        };
+            let value_start = pos + eq + 1;
+            let value_end = match pending_data.take() {
+                Some((data_tag, len)) if data_tag == tag => {
+                    let end = value_start.checked_add(len).filter(|&end| end <= data.len());
+                    let Some(end) = end.filter(|&end| end == data.len() || data[end] == SOH) else {
+                        return Err(FixError::ParseError(format!(
+                            "Data field {} does not match its declared length {}", tag, len
+                        )));
+                    };
+                    end
+                },
+                _ => data[value_start..].iter()
+                    .position(|&b| b == SOH)
+                    .map_or(data.len(), |n| value_start + n),
+            };
+            let value = &data[value_start..value_end];
 
-        // Second pass: set all fields
-        let mut md_entries = 0;
-        let mut expected_entries = None;
-
-        for field_str in fields {
-            if field_str.is_empty() {
-                continue;
+            match tag {
+                8 => has_begin_string = true,  // BeginString
+                35 => msg_type = str::from_utf8(value).ok(),  // MsgType
+                268 => { // NoMDEntries
+                    expected_entries = str::from_utf8(value).ok().and_then(|v| v.parse::<i32>().ok());
+                },
+                269 => { // MDEntryType
+                    md_entries += 1;
+                },
+                _ => {}
             }
 
-            let parts: Vec<&str> = field_str.split('=').collect();
-            if parts.len() != 2 {
-                continue;
+            if let Some(data_tag) = field::data_tag_for_length(tag) {
+                let len = str::from_utf8(value).ok()
+                    .and_then(|v| v.parse::<usize>().ok())
+                    .ok_or_else(|| FixError::ParseError(format!(
+                        "Invalid length value for tag {}: {}", tag, String::from_utf8_lossy(value)
+                    )))?;
+                pending_data = Some((data_tag, len));
             }
 
-            if let Ok(tag) = parts[0].parse::<i32>() {
-                // Special handling for market data entries
-                match tag {
-                    268 => { // NoMDEntries
-                        expected_entries = parts[1].parse::<i32>().ok();
-                    },
-                    269 => { // MDEntryType
-                        md_entries += 1;
-                    },
-                    _ => {}
-                }
-
-                message.set_field(Field::new(tag, parts[1].to_string()))
-                    .map_err(|e| FixError::ParseError(
-                        format!("Failed to set field {}: {}", tag, e)
-                    ))?;
-            } else {
-                return Err(FixError::ParseError(
-                    format!("Invalid tag number: {}", parts[0])
-                ));
-            }
+            fields.push((tag, value));
+            pos = value_end + 1;
         }
 
+        let msg_type = match (has_begin_string, msg_type) {
+            (true, Some(msg_type)) => msg_type,
+            _ => return Err(FixError::ParseError("Missing BeginString or MsgType".into())),
+        };
+
         // Validate market data entries if applicable
         if let Some(expected) = expected_entries {
             if md_entries != expected {
                 return Err(FixError::ParseError(
-                    format!("Expected {} market data entries, found {}", 
+                    format!("Expected {} market data entries, found {}",
                         expected, md_entries)
                 ));
             }
         }
 
-        Ok(message)
+        Ok(MessageView::new(data, fields, msg_type))
     }
 
-    pub fn extract_complete_message(buffer: &[u8]) -> Option<(String, usize)> {
-        let mut start_idx = None;
-        let mut end_idx = None;
+    /// Parse a sequence number field such as MsgSeqNum, NewSeqNo or RefSeqNum.
+    ///
+    /// Only plain ASCII digits are accepted: signs, whitespace and values beyond `u64` are
+    /// rejected rather than read leniently.
+    pub fn parse_seq_num(value: &str) -> Result<u64> {
+        if value.is_empty() || !value.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(FixError::ParseError(format!("Invalid sequence number: {:?}", value)));
+        }
+        value.parse::<u64>()
+            .map_err(|_| FixError::ParseError(format!("Sequence number out of range: {}", value)))
+    }
 
-        // Optimized message boundary detection using memchr
-        for (i, window) in buffer.windows(2).enumerate() {
-            if window == b"8=" {
-                start_idx = Some(i);
-                break;
-            }
+    fn parse_tag(bytes: &[u8]) -> Option<i32> {
+        if bytes.is_empty() || bytes.len() > 9 {
+            return None;
         }
+        bytes.iter().try_fold(0i32, |acc, &b| {
+            b.is_ascii_digit().then(|| acc * 10 + (b - b'0') as i32)
+        })
+    }
 
-        if let Some(start) = start_idx {
-            let remaining = &buffer[start..];
-
-            // Enhanced checksum validation with optimized scanning
-            if let Some(checksum_pos) = remaining.windows(3)
-                .position(|w| w == b"10=") {
-                let checksum_start = checksum_pos + start + 3;
-
-                // Look for the SOH character after the checksum
-                for j in checksum_start..buffer.len() {
-                    if buffer[j] == 1 {  // SOH character
-                        // Validate checksum format (3 digits)
-                        if j - checksum_start == 3 && 
-                           buffer[checksum_start..j].iter().all(|&b| b.is_ascii_digit()) {
-                            end_idx = Some(j + 1);
-                            break;
-                        }
-                    }
-                }
+    /// Locate the first complete frame in `buffer`, returning its `(start, end)` offsets.
+    ///
+    /// The frame end is taken from BodyLength when it is consistent with the trailer, so data
+    /// fields containing "10=" cannot terminate the frame early. Otherwise, or when BodyLength
+    /// would put the frame past [`MAX_FRAME_SIZE`], the buffer is scanned for the CheckSum field.
+    pub fn find_message_boundary(buffer: &[u8]) -> Option<(usize, usize)> {
+        let start = buffer.windows(2).position(|w| w == b"8=")?;
+        let remaining = &buffer[start..];
+
+        let trailer_end = Self::body_end_from_length(remaining)
+            .and_then(|body_end| body_end.checked_add(7))  // "10=" + 3 digits + SOH
+            .filter(|&trailer_end| trailer_end <= MAX_FRAME_SIZE);
+        if let Some(trailer_end) = trailer_end {
+            let body_end = trailer_end - 7;
+            if remaining.len() < trailer_end {
+                return None; // Wait for the rest of the frame
+            }
+            let trailer = &remaining[body_end..trailer_end];
+            if trailer.starts_with(b"10=") && trailer[3..6].iter().all(|b| b.is_ascii_digit())
+                && trailer[6] == SOH {
+                return Some((start, start + trailer_end));
             }
         }
 
-        if let (Some(start), Some(end)) = (start_idx, end_idx) {
-            if end <= buffer.len() {
-                return String::from_utf8(buffer[start..end].to_vec())
-                    .ok()
-                    .map(|msg_str| (msg_str, end));
-            }
+        // Scan for the checksum field and the SOH terminating it
+        let checksum_pos = remaining.windows(4).position(|w| w == b"\x0110=")?;
+        let checksum_start = start + checksum_pos + 4;
+        let end = checksum_start + buffer[checksum_start..].iter().position(|&b| b == SOH)?;
+
+        // Validate checksum format (3 digits)
+        if end - checksum_start == 3 && buffer[checksum_start..end].iter().all(|b| b.is_ascii_digit()) {
+            Some((start, end + 1))
+        } else {
+            None
         }
+    }
 
-        None
+    /// Offset of the CheckSum field in `frame` according to its BodyLength, if present
+    fn body_end_from_length(frame: &[u8]) -> Option<usize> {
+        let begin_end = frame.iter().position(|&b| b == SOH)?;
+        let rest = &frame[begin_end + 1..];
+        if !rest.starts_with(b"9=") {
+            return None;
+        }
+        let len_end = rest.iter().position(|&b| b == SOH)?;
+        let body_length = str::from_utf8(&rest[2..len_end]).ok()?.parse::<usize>().ok()?;
+        (begin_end + len_end + 2).checked_add(body_length)
+    }
+
+    /// Extract the first complete frame in `buffer` along with the number of bytes it consumes
+    pub fn extract_complete_message(buffer: &[u8]) -> Option<(&[u8], usize)> {
+        Self::find_message_boundary(buffer).map(|(start, end)| (&buffer[start..end], end))
     }
 }
 
@@ -171,10 +222,86 @@ mod tests {
         let msg = b"8=FIX.4.2\x0149=SENDER\x0156=TARGET\x0135=0\x0134=1\x0152=20250124-12:00:00\x0110=123\x01";
         let result = MessageParser::extract_complete_message(msg);
         assert!(result.is_some());
-        let (msg_str, len) = result.unwrap();
+        let (frame, len) = result.unwrap();
         assert_eq!(len, msg.len());
-        assert!(msg_str.starts_with("8=FIX.4.2"));
-        assert!(msg_str.ends_with("\u{1}"));
+        assert!(frame.starts_with(b"8=FIX.4.2"));
+        assert!(frame.ends_with(b"\x01"));
+    }
+
+    #[test]
+    fn test_parse_bytes_view() {
+        let data = b"8=FIX.4.2\x019=40\x0135=D\x0111=12345\x0155=AAPL\x0158=a=b\x0110=123\x01";
+        let view = MessageParser::parse_bytes(data).unwrap();
+        assert_eq!(view.msg_type(), "D");
+        assert_eq!(view.begin_string(), Some("FIX.4.2"));
+        assert_eq!(view.get(field::CL_ORD_ID), Some(&b"12345"[..]));
+        assert_eq!(view.get_str(field::TEXT), Some("a=b"));
+        assert_eq!(view.fields()[0], (field::BEGIN_STRING, &b"FIX.4.2"[..]));
+        assert_eq!(view.len(), 7);
+
+        let message = view.to_message().unwrap();
+        assert_eq!(message.get_field(field::SYMBOL).unwrap().value(), "AAPL");
+        assert!(message.get_field(field::SENDER_COMP_ID).is_none());
+    }
+
+    #[test]
+    fn test_parse_bytes_non_utf8_value() {
+        let data = b"8=FIX.4.2\x0135=D\x0158=\xff\xfe\x0110=123\x01";
+        let view = MessageParser::parse_bytes(data).unwrap();
+        assert_eq!(view.get(field::TEXT), Some(&b"\xff\xfe"[..]));
+        assert!(view.get_str(field::TEXT).is_none());
+        assert!(view.to_message().is_err());
+    }
+
+    #[test]
+    fn test_find_message_boundary() {
+        let data = b"garbage8=FIX.4.2\x0135=0\x0110=123\x018=FIX.4.2\x0135=0";
+        let (start, end) = MessageParser::find_message_boundary(data).unwrap();
+        assert_eq!(&data[start..end], b"8=FIX.4.2\x0135=0\x0110=123\x01");
+        assert!(MessageParser::find_message_boundary(&data[end..]).is_none());
+    }
+
+    #[test]
+    fn test_parse_raw_data_by_length() {
+        let data = b"8=FIX.4.2\x0135=D\x0195=7\x0196=a\x01b=c\x01d\x0155=AAPL\x0110=123\x01";
+        let view = MessageParser::parse_bytes(data).unwrap();
+        assert_eq!(view.get(field::RAW_DATA), Some(&b"a\x01b=c\x01d"[..]));
+        assert_eq!(view.get_str(field::SYMBOL), Some("AAPL"));
+
+        let short = b"8=FIX.4.2\x0135=D\x0195=9\x0196=abc\x0110=123\x01";
+        assert!(MessageParser::parse_bytes(short).is_err());
+    }
+
+    #[test]
+    fn test_find_boundary_uses_body_length() {
+        let mut msg = crate::message::Message::new(crate::message::field::values::NEW_ORDER_SINGLE);
+        msg.set_field(crate::message::Field::data(field::RAW_DATA, b"x\x0110=123\x01y".to_vec())).unwrap();
+        let mut buffer = msg.to_bytes().unwrap();
+        let frame_len = buffer.len();
+        buffer.extend_from_slice(b"8=FIX.4.2\x019=5");
+
+        assert_eq!(MessageParser::find_message_boundary(&buffer), Some((0, frame_len)));
+        assert!(MessageParser::find_message_boundary(&buffer[..frame_len - 1]).is_none());
+    }
+
+    #[test]
+    fn test_wire_lengths_do_not_overflow() {
+        // A BodyLength that overflows is ignored and the frame found by its CheckSum
+        let data = b"8=FIX.4.2\x019=18446744073709551615\x0135=0\x0110=123\x01";
+        assert_eq!(MessageParser::find_message_boundary(data), Some((0, data.len())));
+
+        let data = b"8=FIX.4.2\x0135=D\x0195=18446744073709551615\x0196=abc\x0110=123\x01";
+        assert!(MessageParser::parse_bytes(data).is_err());
+    }
+
+    #[test]
+    fn test_oversized_body_length_falls_back_to_checksum() {
+        let data = format!("8=FIX.4.2\x019={}\x0135=0\x0110=123\x01", MAX_FRAME_SIZE);
+        assert_eq!(MessageParser::find_message_boundary(data.as_bytes()), Some((0, data.len())));
+
+        // Below the maximum a wrong BodyLength is still waited for
+        let data = b"8=FIX.4.2\x019=500\x0135=0\x0110=123\x01";
+        assert!(MessageParser::find_message_boundary(data).is_none());
     }
 
     #[test]
@@ -230,4 +357,14 @@ mod tests {
         assert_eq!(message.msg_type(), "S");
         assert_eq!(message.get_field(field::QUOTE_ID).unwrap().value(), "QUOTE123");
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_parse_seq_num() {
+        assert_eq!(MessageParser::parse_seq_num("1").unwrap(), 1);
+        assert_eq!(MessageParser::parse_seq_num("4294967296").unwrap(), 1 << 32);
+        assert_eq!(MessageParser::parse_seq_num("18446744073709551615").unwrap(), u64::MAX);
+        for invalid in ["", "+5", "-1", " 5", "5 ", "1e3", "18446744073709551616"] {
+            assert!(MessageParser::parse_seq_num(invalid).is_err(), "{:?} was accepted", invalid);
+        }
+    }
+}
@@ -64,6 +64,14 @@ pub struct MessagePool {
     pools: Arc<Mutex<HashMap<String, Vec<Message>>>>,
     config: PoolConfig,
     stats: Arc<Mutex<HashMap<String, PoolStats>>>,
+    // Sizes set through `resize_pool`, taking precedence over the configured sizes
+    resized: Arc<Mutex<HashMap<String, usize>>>,
+}
+
+impl Default for MessagePool {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl MessagePool {
@@ -79,6 +87,7 @@ impl MessagePool {
             pools,
             config,
             stats,
+            resized: Arc::new(Mutex::new(HashMap::new())),
         }
     }
 
@@ -118,9 +127,9 @@ impl MessagePool {
             .or_insert_with(PoolStats::new);
 
         // Calculate target and maximum sizes
-        let configured_max = self.config.type_specific_sizes
-            .get(&message.msg_type().to_string())
-            .copied()
+        let resized = self.resized.lock().await.get(message.msg_type()).copied();
+        let configured_max = resized
+            .or_else(|| self.config.type_specific_sizes.get(message.msg_type()).copied())
             .unwrap_or(self.config.default_size);
 
         let target_size = (configured_max / 2).min(self.config.max_pool_size);
@@ -150,6 +159,7 @@ impl MessagePool {
             .or_insert_with(PoolStats::new);
 
         let target_size = new_size.min(self.config.max_pool_size);
+        self.resized.lock().await.insert(msg_type.to_string(), target_size);
 
         // Grow pool if needed
         while pool.len() < target_size {
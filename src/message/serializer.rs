@@ -0,0 +1,193 @@
+use super::Message;
+use super::field;
+use crate::error::FixError;
+use crate::Result;
+
+const SOH: u8 = 1;
+
+/// Writes messages in wire format into a caller supplied buffer.
+///
+/// BodyLength is computed from the field lengths before anything is written, and CheckSum
+/// is accumulated while the bytes are appended, so each frame is produced in a single write
+/// pass without intermediate strings. The serializer keeps its scratch space between calls,
+/// so a long-lived instance (such as the one owned by `Transport`) does not allocate per
+/// message once warmed up.
+#[derive(Debug, Default)]
+pub struct MessageSerializer {
+    tags: Vec<i32>,
+}
+
+impl MessageSerializer {
+    pub fn new() -> Self {
+        MessageSerializer { tags: Vec::with_capacity(32) }
+    }
+
+    /// Append `message` to `buf` as a complete frame, returning the number of bytes written
+    pub fn serialize_into(&mut self, message: &Message, buf: &mut Vec<u8>) -> Result<usize> {
+        let begin_string = message.get_field(field::BEGIN_STRING)
+            .ok_or_else(|| FixError::ParseError("Missing BeginString".into()))?;
+
+        self.collect_tags(message);
+
+        // Body: everything between BodyLength and CheckSum, starting with MsgType
+        let mut body_length = field_len(field::MSG_TYPE, message.msg_type().len());
+        for &tag in &self.tags {
+            let value = message.fields[&tag].as_bytes();
+            if let Some(len_tag) = field::length_tag_for_data(tag) {
+                body_length += field_len(len_tag, decimal_len(value.len()));
+            }
+            body_length += field_len(tag, value.len());
+        }
+
+        let start = buf.len();
+        buf.reserve(body_length + begin_string.as_bytes().len() + 24);
+        let mut writer = FrameWriter { buf, checksum: 0 };
+
+        writer.field(field::BEGIN_STRING, begin_string.as_bytes());
+        writer.int_field(field::BODY_LENGTH, body_length);
+        writer.field(field::MSG_TYPE, message.msg_type().as_bytes());
+        for &tag in &self.tags {
+            let value = message.fields[&tag].as_bytes();
+            if let Some(len_tag) = field::length_tag_for_data(tag) {
+                writer.int_field(len_tag, value.len());
+            }
+            writer.field(tag, value);
+        }
+
+        let checksum = (writer.checksum % 256) as u8;
+        let buf = writer.buf;
+        buf.extend_from_slice(b"10=");
+        buf.extend_from_slice(&[
+            b'0' + checksum / 100,
+            b'0' + checksum / 10 % 10,
+            b'0' + checksum % 10,
+            SOH,
+        ]);
+
+        Ok(buf.len() - start)
+    }
+
+    /// Order the fields to write: standard header, body sorted by tag, then trailer.
+    /// Length fields of data fields are left out as they are derived from the data.
+    fn collect_tags(&mut self, message: &Message) {
+        let fields = &message.fields;
+        let derived_length = |tag: i32| {
+            field::data_tag_for_length(tag).is_some_and(|data_tag| fields.contains_key(&data_tag))
+        };
+
+        self.tags.clear();
+        self.tags.extend(field::HEADER_FIELDS.iter()
+            .copied()
+            .filter(|tag| fields.contains_key(tag) && !derived_length(*tag)));
+
+        let body_start = self.tags.len();
+        self.tags.extend(fields.keys()
+            .copied()
+            .filter(|&tag| !field::is_header_field(tag) && !field::is_trailer_field(tag))
+            .filter(|&tag| !derived_length(tag)));
+        self.tags[body_start..].sort_unstable();
+
+        self.tags.extend(field::TRAILER_FIELDS.iter()
+            .copied()
+            .filter(|tag| fields.contains_key(tag) && !derived_length(*tag)));
+    }
+}
+
+struct FrameWriter<'a> {
+    buf: &'a mut Vec<u8>,
+    checksum: u32,
+}
+
+impl FrameWriter<'_> {
+    fn put(&mut self, bytes: &[u8]) {
+        self.checksum = bytes.iter().fold(self.checksum, |sum, &b| sum.wrapping_add(b as u32));
+        self.buf.extend_from_slice(bytes);
+    }
+
+    fn field(&mut self, tag: i32, value: &[u8]) {
+        self.put(format_decimal(tag as usize, &mut [0; 20]));
+        self.put(b"=");
+        self.put(value);
+        self.put(&[SOH]);
+    }
+
+    fn int_field(&mut self, tag: i32, value: usize) {
+        self.field(tag, format_decimal(value, &mut [0; 20]));
+    }
+}
+
+/// Length of `tag=value<SOH>` for a value of `value_len` bytes
+fn field_len(tag: i32, value_len: usize) -> usize {
+    decimal_len(tag as usize) + value_len + 2
+}
+
+fn decimal_len(mut value: usize) -> usize {
+    let mut len = 1;
+    while value >= 10 {
+        value /= 10;
+        len += 1;
+    }
+    len
+}
+
+fn format_decimal(mut value: usize, scratch: &mut [u8; 20]) -> &[u8] {
+    let mut pos = scratch.len();
+    loop {
+        pos -= 1;
+        scratch[pos] = b'0' + (value % 10) as u8;
+        value /= 10;
+        if value == 0 {
+            break;
+        }
+    }
+    &scratch[pos..]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::Field;
+    use crate::message::field::values;
+
+    #[test]
+    fn test_header_fields_written_first() {
+        let mut msg = Message::new(values::NEW_ORDER_SINGLE);
+        msg.set_field(Field::new(field::CL_ORD_ID, "12345")).unwrap();
+        msg.set_field(Field::new(field::SYMBOL, "AAPL")).unwrap();
+        msg.set_field(Field::new(field::SENDER_SUB_ID, "DESK")).unwrap();
+
+        let bytes = msg.to_bytes().unwrap();
+        let text = String::from_utf8(bytes).unwrap();
+        let tags: Vec<&str> = text.split('\u{1}')
+            .filter(|f| !f.is_empty())
+            .map(|f| f.split('=').next().unwrap())
+            .collect();
+        assert_eq!(tags, vec!["8", "9", "35", "49", "56", "34", "50", "52", "11", "55", "10"]);
+    }
+
+    #[test]
+    fn test_serializer_reuses_buffer() {
+        let mut serializer = MessageSerializer::new();
+        let mut buf = Vec::new();
+        let first = Message::new(values::HEARTBEAT);
+        let second = Message::new(values::TEST_REQUEST);
+
+        let n1 = serializer.serialize_into(&first, &mut buf).unwrap();
+        let n2 = serializer.serialize_into(&second, &mut buf).unwrap();
+        assert_eq!(buf.len(), n1 + n2);
+        assert_eq!(&buf[..n1], &first.to_bytes().unwrap()[..]);
+        assert_eq!(&buf[n1..], &second.to_bytes().unwrap()[..]);
+
+        let parsed = Message::from_bytes(&buf[n1..]).unwrap();
+        assert_eq!(parsed.msg_type(), values::TEST_REQUEST);
+    }
+
+    #[test]
+    fn test_format_decimal() {
+        assert_eq!(format_decimal(0, &mut [0; 20]), b"0");
+        assert_eq!(format_decimal(1234, &mut [0; 20]), b"1234");
+        assert_eq!(decimal_len(0), 1);
+        assert_eq!(decimal_len(100), 3);
+        assert_eq!(field_len(field::SYMBOL, 4), "55=AAPL\u{1}".len());
+    }
+}
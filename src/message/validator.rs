@@ -1,12 +1,90 @@
 use std::collections::{HashMap, HashSet};
-use crate::message::{Message, field};
+use std::fmt;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use crate::config::{SessionConfig, ValidationConfig};
+use crate::message::{Message, MessageView, field};
+use crate::message::parser::MessageParser;
 use crate::Result;
 use crate::error::FixError;
 
+/// SessionRejectReason (373) values sent in session level rejects
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RejectReason {
+    InvalidTagNumber = 0,
+    RequiredTagMissing = 1,
+    TagNotDefinedForMessageType = 2,
+    UndefinedTag = 3,
+    TagSpecifiedWithoutValue = 4,
+    ValueIncorrect = 5,
+    IncorrectDataFormat = 6,
+    CompIdProblem = 9,
+    SendingTimeAccuracyProblem = 10,
+    InvalidMsgType = 11,
+    TagAppearsMoreThanOnce = 13,
+    TagSpecifiedOutOfRequiredOrder = 14,
+    Other = 99,
+}
+
+impl RejectReason {
+    pub fn code(self) -> i32 {
+        self as i32
+    }
+}
+
+/// A failed inbound check with what is needed to answer it with a Reject
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rejection {
+    pub reason: RejectReason,
+    pub ref_tag: Option<i32>,
+    pub text: String,
+}
+
+impl Rejection {
+    pub fn new(reason: RejectReason, ref_tag: Option<i32>, text: impl Into<String>) -> Self {
+        Rejection { reason, ref_tag, text: text.into() }
+    }
+
+    /// Whether the session must be logged out after sending the reject
+    pub fn ends_session(&self) -> bool {
+        matches!(self.reason, RejectReason::CompIdProblem | RejectReason::SendingTimeAccuracyProblem)
+    }
+
+    fn from_error(reason: RejectReason, err: FixError) -> Self {
+        let text = match err {
+            FixError::ParseError(msg) => msg,
+            other => other.to_string(),
+        };
+        Rejection::new(reason, None, text)
+    }
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} (reason {})", self.text, self.reason.code())
+    }
+}
+
+impl From<Rejection> for FixError {
+    fn from(rejection: Rejection) -> FixError {
+        FixError::ParseError(rejection.text)
+    }
+}
+
+type CheckResult = std::result::Result<(), Rejection>;
+
 pub struct MessageValidator;
 
 impl MessageValidator {
+    /// Validate a message with the default policy
     pub fn validate(message: &Message) -> Result<()> {
+        Self::validate_with(message, &ValidationConfig::default())?;
+        Ok(())
+    }
+
+    /// Validate the content of a message, relaxing checks as allowed by `policy`.
+    ///
+    /// Field order cannot be checked on a `Message`; see [`MessageValidator::validate_field_order`].
+    pub fn validate_with(message: &Message, policy: &ValidationConfig) -> CheckResult {
         // Check required header fields
         let required_header = vec![
             field::BEGIN_STRING,
@@ -19,9 +97,8 @@ impl MessageValidator {
 
         for &tag in &required_header {
             if message.get_field(tag).is_none() {
-                return Err(FixError::ParseError(
-                    format!("Missing required header field: {}", tag)
-                ));
+                return Err(Rejection::new(RejectReason::RequiredTagMissing, Some(tag),
+                    format!("Missing required header field: {}", tag)));
             }
         }
 
@@ -29,89 +106,219 @@ impl MessageValidator {
         let required_fields = Self::get_required_fields(message.msg_type());
         for &tag in &required_fields {
             if message.get_field(tag).is_none() {
-                return Err(FixError::ParseError(
-                    format!("Missing required field for message type {}: {}", 
-                        message.msg_type(), tag)
-                ));
+                return Err(Rejection::new(RejectReason::RequiredTagMissing, Some(tag),
+                    format!("Missing required field for message type {}: {}",
+                        message.msg_type(), tag)));
             }
         }
 
+        Self::validate_field_definitions(message, policy)?;
+
         // Validate field values
-        Self::validate_field_values(message)?;
+        Self::validate_field_values(message)
+            .map_err(|e| Rejection::from_error(RejectReason::ValueIncorrect, e))?;
 
         // Validate conditional fields
-        Self::validate_conditional_fields(message)?;
+        Self::validate_conditional_fields(message)
+            .map_err(|e| Rejection::from_error(RejectReason::Other, e))?;
 
         // Validate sequence numbers
-        Self::validate_sequence_numbers(message)?;
+        Self::validate_sequence_numbers(message)
+            .map_err(|e| Rejection::from_error(RejectReason::IncorrectDataFormat, e))?;
 
         // Validate sending time
-        Self::validate_sending_time(message)?;
+        Self::validate_sending_time(message)
+            .map_err(|e| Rejection::from_error(RejectReason::IncorrectDataFormat, e))?;
+
+        Ok(())
+    }
+
+    /// Run every check enabled for the session on an inbound message
+    pub fn validate_inbound(message: &Message, config: &SessionConfig) -> CheckResult {
+        let policy = &config.validation;
+        if policy.check_comp_id {
+            Self::check_comp_ids(message, config)?;
+        }
+        if policy.check_latency {
+            Self::check_latency(message, policy.max_latency, Utc::now())?;
+        }
+        Self::validate_with(message, policy)
+    }
+
+    /// Check that BeginString, BodyLength and MsgType lead the frame, that header fields
+    /// are not mixed into the body and that nothing but trailer fields follow the body.
+    pub fn validate_field_order(view: &MessageView) -> CheckResult {
+        let fields = view.fields();
+        let out_of_order = |tag: i32, text: String| {
+            Err(Rejection::new(RejectReason::TagSpecifiedOutOfRequiredOrder, Some(tag), text))
+        };
+
+        for (position, &expected) in [field::BEGIN_STRING, field::BODY_LENGTH, field::MSG_TYPE].iter().enumerate() {
+            if fields.get(position).map(|(tag, _)| *tag) != Some(expected) {
+                return out_of_order(expected, format!("Tag {} must be field {} of the message", expected, position + 1));
+            }
+        }
+
+        let mut in_body = false;
+        let mut in_trailer = false;
+        for (position, &(tag, _)) in fields.iter().enumerate().skip(3) {
+            if field::is_trailer_field(tag) {
+                if tag == field::CHECKSUM && position + 1 != fields.len() {
+                    return out_of_order(tag, "CheckSum must be the last field".into());
+                }
+                in_trailer = true;
+            } else if in_trailer {
+                return out_of_order(tag, format!("Tag {} appears after the trailer", tag));
+            } else if field::is_header_field(tag) {
+                if in_body {
+                    return out_of_order(tag, format!("Header tag {} appears in the message body", tag));
+                }
+            } else {
+                in_body = true;
+            }
+        }
+        Ok(())
+    }
+
+    /// Check that the message was sent by our counterparty to us, including any SubID and
+    /// LocationID qualifiers configured for the session
+    pub fn check_comp_ids(message: &Message, config: &SessionConfig) -> CheckResult {
+        let expected = [
+            (field::SENDER_COMP_ID, Some(&config.target_comp_id)),
+            (field::TARGET_COMP_ID, Some(&config.sender_comp_id)),
+            (field::SENDER_SUB_ID, config.target_sub_id.as_ref()),
+            (field::TARGET_SUB_ID, config.sender_sub_id.as_ref()),
+            (field::SENDER_LOCATION_ID, config.target_location_id.as_ref()),
+            (field::TARGET_LOCATION_ID, config.sender_location_id.as_ref()),
+        ];
+        for (tag, expected) in expected {
+            let Some(expected) = expected else { continue };
+            match message.get_field(tag).map(|f| f.value()) {
+                Some(value) if value == expected => {},
+                Some(value) => {
+                    return Err(Rejection::new(RejectReason::CompIdProblem, Some(tag),
+                        format!("CompID problem: tag {} is {}, expected {}", tag, value, expected)));
+                },
+                // Missing CompIDs are reported by the required field check
+                None if matches!(tag, field::SENDER_COMP_ID | field::TARGET_COMP_ID) => {},
+                None => {
+                    return Err(Rejection::new(RejectReason::CompIdProblem, Some(tag),
+                        format!("CompID problem: tag {} is missing, expected {}", tag, expected)));
+                },
+            }
+        }
+        Ok(())
+    }
+
+    /// Check that the message uses the session's FIX version. A mismatch is not rejected
+    /// but ends the session.
+    pub fn check_begin_string(message: &Message, config: &SessionConfig) -> Result<()> {
+        let begin_string = message.get_field(field::BEGIN_STRING).map(|f| f.value()).unwrap_or("");
+        if begin_string != config.begin_string {
+            return Err(FixError::SessionError(format!(
+                "Incorrect BeginString: {}, expected {}", begin_string, config.begin_string
+            )));
+        }
+        Ok(())
+    }
+
+    /// Check that SendingTime is within `max_latency` seconds of `now`
+    pub fn check_latency(message: &Message, max_latency: u64, now: DateTime<Utc>) -> CheckResult {
+        let Some(sending_time) = message.get_field(field::SENDING_TIME) else {
+            return Ok(());
+        };
+        let sent = Self::sending_time(message)
+            .ok_or_else(|| Rejection::new(RejectReason::IncorrectDataFormat, Some(field::SENDING_TIME),
+                format!("Invalid SendingTime format: {}", sending_time.value())))?;
+
+        let latency = (now - sent).num_seconds().unsigned_abs();
+        if latency > max_latency {
+            return Err(Rejection::new(RejectReason::SendingTimeAccuracyProblem, Some(field::SENDING_TIME),
+                format!("SendingTime accuracy problem: {}s from now exceeds {}s", latency, max_latency)));
+        }
+        Ok(())
+    }
+
+    /// SendingTime of the message as UTC, with or without milliseconds
+    pub fn sending_time(message: &Message) -> Option<DateTime<Utc>> {
+        let value = message.get_field(field::SENDING_TIME)?.value();
+        NaiveDateTime::parse_from_str(value, "%Y%m%d-%H:%M:%S%.f")
+            .ok()
+            .map(|time| time.and_utc())
+    }
 
+    fn validate_field_definitions(message: &Message, policy: &ValidationConfig) -> CheckResult {
+        let mut tags: Vec<i32> = message.fields().keys().copied().collect();
+        tags.sort_unstable();
+
+        for tag in tags {
+            if policy.validate_fields_have_values && message.fields()[&tag].as_bytes().is_empty() {
+                return Err(Rejection::new(RejectReason::TagSpecifiedWithoutValue, Some(tag),
+                    format!("Tag specified without a value: {}", tag)));
+            }
+
+            let check_defined = if field::is_user_defined_field(tag) {
+                policy.validate_user_defined_fields
+            } else {
+                !policy.allow_unknown_msg_fields
+            };
+            if check_defined && !field::is_defined_field(tag) {
+                return Err(Rejection::new(RejectReason::UndefinedTag, Some(tag),
+                    format!("Undefined tag: {}", tag)));
+            }
+        }
         Ok(())
     }
 
     fn validate_field_values(message: &Message) -> Result<()> {
         for (&tag, field) in message.fields() {
+            // Empty values were rejected already unless the policy allows them
+            if field.as_bytes().is_empty() {
+                continue;
+            }
             match tag {
-                field::MSG_SEQ_NUM => {
-                    if field.value().parse::<i32>().is_err() || field.value().parse::<i32>().unwrap() <= 0 {
-                        return Err(FixError::ParseError(
-                            format!("Invalid MsgSeqNum value: {}", field.value())
-                        ));
-                    }
+                field::MSG_SEQ_NUM if !MessageParser::parse_seq_num(field.value()).is_ok_and(|seq| seq > 0) => {
+                    return Err(FixError::ParseError(
+                        format!("Invalid MsgSeqNum value: {}", field.value())
+                    ));
                 },
                 field::PRICE | field::QUANTITY | field::STOP_PX | field::MIN_QTY | field::MAX_FLOOR |
                 field::MD_ENTRY_PX | field::MD_ENTRY_SIZE | field::BID_PX | field::OFFER_PX |
-                field::BID_SIZE | field::OFFER_SIZE => {
-                    if let Err(_) = field.value().parse::<f64>() {
-                        return Err(FixError::ParseError(
-                            format!("Invalid numeric value for tag {}: {}", tag, field.value())
-                        ));
-                    }
+                field::BID_SIZE | field::OFFER_SIZE if field.value().parse::<f64>().is_err() => {
+                    return Err(FixError::ParseError(
+                        format!("Invalid numeric value for tag {}: {}", tag, field.value())
+                    ));
                 },
                 field::SENDING_TIME | field::TRANSACTION_TIME | field::EXPIRE_TIME |
-                field::MD_ENTRY_TIME | field::VALID_UNTIL_TIME => {
-                    if !Self::is_valid_timestamp(field.value()) {
-                        return Err(FixError::ParseError(
-                            format!("Invalid timestamp format: {}", field.value())
-                        ));
-                    }
+                field::MD_ENTRY_TIME | field::VALID_UNTIL_TIME if !Self::is_valid_timestamp(field.value()) => {
+                    return Err(FixError::ParseError(
+                        format!("Invalid timestamp format: {}", field.value())
+                    ));
                 },
-                field::SIDE => {
-                    if !Self::is_valid_side(field.value()) {
-                        return Err(FixError::ParseError(
-                            format!("Invalid side value: {}", field.value())
-                        ));
-                    }
+                field::SIDE if !Self::is_valid_side(field.value()) => {
+                    return Err(FixError::ParseError(
+                        format!("Invalid side value: {}", field.value())
+                    ));
                 },
-                field::ORD_TYPE => {
-                    if !Self::is_valid_order_type(field.value()) {
-                        return Err(FixError::ParseError(
-                            format!("Invalid order type: {}", field.value())
-                        ));
-                    }
+                field::ORD_TYPE if !Self::is_valid_order_type(field.value()) => {
+                    return Err(FixError::ParseError(
+                        format!("Invalid order type: {}", field.value())
+                    ));
                 },
-                field::TIME_IN_FORCE => {
-                    if !Self::is_valid_time_in_force(field.value()) {
-                        return Err(FixError::ParseError(
-                            format!("Invalid time in force: {}", field.value())
-                        ));
-                    }
+                field::TIME_IN_FORCE if !Self::is_valid_time_in_force(field.value()) => {
+                    return Err(FixError::ParseError(
+                        format!("Invalid time in force: {}", field.value())
+                    ));
                 },
-                field::MD_ENTRY_TYPE => {
-                    if !Self::is_valid_md_entry_type(field.value()) {
-                        return Err(FixError::ParseError(
-                            format!("Invalid market data entry type: {}", field.value())
-                        ));
-                    }
+                field::MD_ENTRY_TYPE if !Self::is_valid_md_entry_type(field.value()) => {
+                    return Err(FixError::ParseError(
+                        format!("Invalid market data entry type: {}", field.value())
+                    ));
                 },
-                field::QUOTE_CONDITION => {
-                    if !Self::is_valid_quote_condition(field.value()) {
-                        return Err(FixError::ParseError(
-                            format!("Invalid quote condition: {}", field.value())
-                        ));
-                    }
+                field::QUOTE_CONDITION if !Self::is_valid_quote_condition(field.value()) => {
+                    return Err(FixError::ParseError(
+                        format!("Invalid quote condition: {}", field.value())
+                    ));
                 },
                 _ => {}
             }
@@ -185,7 +392,7 @@ impl MessageValidator {
 
         // Validate NoMDEntries
         if let Some(no_entries) = message.get_field(field::NO_MD_ENTRIES) {
-            if let Err(_) = no_entries.value().parse::<usize>() {
+            if no_entries.value().parse::<usize>().is_err() {
                 return Err(FixError::ParseError("Invalid NoMDEntries".into()));
             }
         }
@@ -394,7 +601,7 @@ impl MessageValidator {
         ].iter().cloned().collect();
 
         valid_combinations.get(exec_type)
-            .map_or(false, |valid_statuses| valid_statuses.contains(ord_status))
+            .is_some_and(|valid_statuses| valid_statuses.contains(ord_status))
     }
 
     fn validate_sending_time(message: &Message) -> Result<()> {
@@ -410,9 +617,9 @@ impl MessageValidator {
 
     fn validate_sequence_numbers(message: &Message) -> Result<()> {
         if let Some(seq_num) = message.get_field(field::MSG_SEQ_NUM) {
-            let seq = seq_num.value().parse::<i32>()
+            let seq = MessageParser::parse_seq_num(seq_num.value())
                 .map_err(|_| FixError::ParseError("Invalid MsgSeqNum".into()))?;
-            if seq <= 0 {
+            if seq == 0 {
                 return Err(FixError::ParseError("MsgSeqNum must be positive".into()));
             }
         }
@@ -438,22 +645,17 @@ impl MessageValidator {
         // Validate price for LIMIT orders
         if let Some(ord_type) = message.get_field(field::ORD_TYPE) {
             match ord_type.value() {
-                field::values::LIMIT => {
-                    if message.get_field(field::PRICE).is_none() {
-                        return Err(FixError::ParseError("Price is required for LIMIT orders".into()));
-                    }
+                field::values::LIMIT if message.get_field(field::PRICE).is_none() => {
+                    return Err(FixError::ParseError("Price is required for LIMIT orders".into()));
                 },
-                field::values::STOP => {
-                    if message.get_field(field::STOP_PX).is_none() {
-                        return Err(FixError::ParseError("StopPx is required for STOP orders".into()));
-                    }
+                field::values::STOP if message.get_field(field::STOP_PX).is_none() => {
+                    return Err(FixError::ParseError("StopPx is required for STOP orders".into()));
                 },
-                field::values::STOP_LIMIT => {
-                    if message.get_field(field::PRICE).is_none() || message.get_field(field::STOP_PX).is_none() {
-                        return Err(FixError::ParseError(
-                            "Both Price and StopPx are required for STOP LIMIT orders".into()
-                        ));
-                    }
+                field::values::STOP_LIMIT
+                    if message.get_field(field::PRICE).is_none() || message.get_field(field::STOP_PX).is_none() => {
+                    return Err(FixError::ParseError(
+                        "Both Price and StopPx are required for STOP LIMIT orders".into()
+                    ));
                 },
                 _ => {}
             }
@@ -493,6 +695,7 @@ mod tests {
     use super::*;
     use crate::message::Field;
     use crate::message::field::values;
+    use crate::message::parser::MessageParser;
 
     #[test]
     fn test_validate_new_order_types() {
@@ -540,6 +743,25 @@ mod tests {
         assert!(MessageValidator::validate(&msg).is_ok());
     }
 
+    #[test]
+    fn test_validate_msg_seq_num() {
+        let mut msg = Message::new(values::LOGON);
+        let _ = msg.set_field(Field::new(field::BEGIN_STRING, "FIX.4.2"));
+        let _ = msg.set_field(Field::new(field::MSG_TYPE, field::values::LOGON));
+        let _ = msg.set_field(Field::new(field::SENDER_COMP_ID, "SENDER"));
+        let _ = msg.set_field(Field::new(field::TARGET_COMP_ID, "TARGET"));
+        let _ = msg.set_field(Field::new(field::SENDING_TIME, "20250124-12:00:00"));
+        let _ = msg.set_field(Field::new(field::ENCRYPT_METHOD, "0"));
+        let _ = msg.set_field(Field::new(field::HEART_BT_INT, "30"));
+
+        let _ = msg.set_field(Field::new(field::MSG_SEQ_NUM, "4294967296"));
+        assert!(MessageValidator::validate(&msg).is_ok());
+        for invalid in ["0", "+1", "-1", "18446744073709551616"] {
+            let _ = msg.set_field(Field::new(field::MSG_SEQ_NUM, invalid));
+            assert!(MessageValidator::validate(&msg).is_err(), "MsgSeqNum {} was accepted", invalid);
+        }
+    }
+
     #[test]
     fn test_validate_missing_required_field() {
         let mut msg = Message::new(field::values::LOGON);
@@ -741,4 +963,127 @@ mod tests {
         assert!(MessageValidator::validate(&msg).is_err());
     }
 
+    fn heartbeat() -> Message {
+        let mut msg = Message::new(values::HEARTBEAT);
+        msg.set_field(Field::new(field::SENDER_COMP_ID, "TARGET")).unwrap();
+        msg.set_field(Field::new(field::TARGET_COMP_ID, "SENDER")).unwrap();
+        msg
+    }
+
+    fn session_config() -> SessionConfig {
+        SessionConfig {
+            sender_comp_id: "SENDER".to_string(),
+            target_comp_id: "TARGET".to_string(),
+            target_addr: "127.0.0.1:0".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_default_policy_accepts_execution_report() {
+        let mut msg = Message::new(values::EXECUTION_REPORT);
+        msg.set_field(Field::new(field::SENDER_COMP_ID, "TARGET")).unwrap();
+        msg.set_field(Field::new(field::TARGET_COMP_ID, "SENDER")).unwrap();
+        msg.set_field(Field::new(field::MSG_SEQ_NUM, "2")).unwrap();
+        msg.set_field(Field::new(field::SENDING_TIME, Utc::now().format("%Y%m%d-%H:%M:%S%.3f").to_string())).unwrap();
+        for (tag, value) in [
+            (1, "ACCT"), (6, "150.25"), (14, "100"), (17, "EXEC1"), (20, "0"), (21, "1"),
+            (31, "150.25"), (32, "100"), (37, "ORD1"), (39, "2"), (41, "ORIG1"), (11, "CL1"),
+            (54, "1"), (55, "AAPL"), (150, "2"), (151, "0"), (5001, "desk note"),
+        ] {
+            msg.set_field(Field::new(tag, value)).unwrap();
+        }
+
+        assert!(MessageValidator::validate_inbound(&msg, &session_config()).is_ok());
+    }
+
+    #[test]
+    fn test_validation_policy() {
+        let mut policy = ValidationConfig {
+            validate_user_defined_fields: true,
+            allow_unknown_msg_fields: false,
+            ..ValidationConfig::default()
+        };
+
+        let mut msg = heartbeat();
+        msg.set_field(Field::new(field::TEXT, "")).unwrap();
+        let rejection = MessageValidator::validate_with(&msg, &policy).unwrap_err();
+        assert_eq!(rejection.reason, RejectReason::TagSpecifiedWithoutValue);
+        assert_eq!(rejection.ref_tag, Some(field::TEXT));
+        policy.validate_fields_have_values = false;
+        assert!(MessageValidator::validate_with(&msg, &policy).is_ok());
+
+        let mut msg = heartbeat();
+        msg.set_field(Field::new(1, "ACCOUNT")).unwrap();
+        let rejection = MessageValidator::validate_with(&msg, &policy).unwrap_err();
+        assert_eq!(rejection.reason, RejectReason::UndefinedTag);
+        policy.allow_unknown_msg_fields = true;
+        assert!(MessageValidator::validate_with(&msg, &policy).is_ok());
+
+        // User-defined fields are governed separately from unknown standard fields
+        msg.set_field(Field::new(5001, "custom")).unwrap();
+        assert!(MessageValidator::validate_with(&msg, &policy).is_err());
+        policy.validate_user_defined_fields = false;
+        assert!(MessageValidator::validate_with(&msg, &policy).is_ok());
+    }
+
+    #[test]
+    fn test_validate_field_order() {
+        let frame = b"8=FIX.4.2\x019=5\x0135=0\x0149=A\x0156=B\x0110=000\x01";
+        let view = MessageParser::parse_bytes(frame).unwrap();
+        assert!(MessageValidator::validate_field_order(&view).is_ok());
+
+        let frame = b"8=FIX.4.2\x019=5\x0135=0\x0149=A\x0158=hi\x0156=B\x0110=000\x01";
+        let view = MessageParser::parse_bytes(frame).unwrap();
+        let rejection = MessageValidator::validate_field_order(&view).unwrap_err();
+        assert_eq!(rejection.reason, RejectReason::TagSpecifiedOutOfRequiredOrder);
+        assert_eq!(rejection.ref_tag, Some(field::TARGET_COMP_ID));
+
+        let frame = b"8=FIX.4.2\x0135=0\x019=5\x0149=A\x0156=B\x0110=000\x01";
+        let view = MessageParser::parse_bytes(frame).unwrap();
+        assert_eq!(MessageValidator::validate_field_order(&view).unwrap_err().ref_tag, Some(field::BODY_LENGTH));
+
+        let frame = b"8=FIX.4.2\x019=5\x0135=0\x0149=A\x0110=000\x0156=B\x01";
+        let view = MessageParser::parse_bytes(frame).unwrap();
+        assert!(MessageValidator::validate_field_order(&view).is_err());
+    }
+
+    #[test]
+    fn test_check_comp_ids_and_latency() {
+        let config = session_config();
+
+        let msg = heartbeat();
+        assert!(MessageValidator::validate_inbound(&msg, &config).is_ok());
+
+        let mut wrong_sender = heartbeat();
+        wrong_sender.set_field(Field::new(field::SENDER_COMP_ID, "OTHER")).unwrap();
+        let rejection = MessageValidator::validate_inbound(&wrong_sender, &config).unwrap_err();
+        assert_eq!(rejection.reason, RejectReason::CompIdProblem);
+        let mut relaxed = config.clone();
+        relaxed.validation.check_comp_id = false;
+        assert!(MessageValidator::validate_inbound(&wrong_sender, &relaxed).is_ok());
+
+        // Configured qualifiers must be present and match
+        let mut qualified = config.clone();
+        qualified.target_sub_id = Some("DESK".to_string());
+        let rejection = MessageValidator::check_comp_ids(&msg, &qualified).unwrap_err();
+        assert_eq!(rejection.ref_tag, Some(field::SENDER_SUB_ID));
+        let mut with_sub_id = heartbeat();
+        with_sub_id.set_field(Field::new(field::SENDER_SUB_ID, "DESK")).unwrap();
+        assert!(MessageValidator::check_comp_ids(&with_sub_id, &qualified).is_ok());
+
+        assert!(MessageValidator::check_begin_string(&msg, &config).is_ok());
+        let mut fix44 = heartbeat();
+        fix44.set_field(Field::new(field::BEGIN_STRING, "FIX.4.4")).unwrap();
+        assert!(MessageValidator::check_begin_string(&fix44, &config).is_err());
+
+        let now = Utc::now();
+        let mut stale = heartbeat();
+        let sent = now - chrono::Duration::seconds(300);
+        stale.set_field(Field::new(field::SENDING_TIME, sent.format("%Y%m%d-%H:%M:%S%.3f").to_string())).unwrap();
+        let rejection = MessageValidator::check_latency(&stale, 120, now).unwrap_err();
+        assert_eq!(rejection.reason, RejectReason::SendingTimeAccuracyProblem);
+        assert!(rejection.ends_session());
+        assert!(MessageValidator::check_latency(&stale, 600, now).is_ok());
+    }
 }
\ No newline at end of file
@@ -0,0 +1,80 @@
+use std::collections::HashMap;
+use std::str;
+use super::{Message, Field};
+use super::field;
+use crate::error::FixError;
+use crate::Result;
+
+/// Borrowed view over a raw FIX frame.
+///
+/// Holds `(tag, value)` pairs pointing into the receive buffer in wire order, so the
+/// hot path can inspect a message without copying or allocating per field. Use
+/// [`MessageView::to_message`] when an owned [`Message`] is needed.
+#[derive(Debug, Clone)]
+pub struct MessageView<'a> {
+    data: &'a [u8],
+    fields: Vec<(i32, &'a [u8])>,
+    msg_type: &'a str,
+}
+
+impl<'a> MessageView<'a> {
+    pub(crate) fn new(data: &'a [u8], fields: Vec<(i32, &'a [u8])>, msg_type: &'a str) -> Self {
+        MessageView { data, fields, msg_type }
+    }
+
+    /// The complete frame this view was parsed from
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.data
+    }
+
+    pub fn msg_type(&self) -> &'a str {
+        self.msg_type
+    }
+
+    /// All fields in the order they appeared on the wire
+    pub fn fields(&self) -> &[(i32, &'a [u8])] {
+        &self.fields
+    }
+
+    pub fn len(&self) -> usize {
+        self.fields.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    /// Raw value of the first occurrence of `tag`
+    pub fn get(&self, tag: i32) -> Option<&'a [u8]> {
+        self.fields.iter()
+            .find(|(t, _)| *t == tag)
+            .map(|(_, value)| *value)
+    }
+
+    /// Value of the first occurrence of `tag`, if it is valid UTF-8
+    pub fn get_str(&self, tag: i32) -> Option<&'a str> {
+        self.get(tag).and_then(|value| str::from_utf8(value).ok())
+    }
+
+    pub fn begin_string(&self) -> Option<&'a str> {
+        self.get_str(field::BEGIN_STRING)
+    }
+
+    /// Build an owned message from this view.
+    ///
+    /// Only the fields present on the wire are copied; no default headers are added.
+    pub fn to_message(&self) -> Result<Message> {
+        let mut fields = HashMap::with_capacity(self.fields.len());
+        for &(tag, value) in &self.fields {
+            let field = if field::length_tag_for_data(tag).is_some() {
+                Field::data(tag, value)
+            } else {
+                let value = str::from_utf8(value)
+                    .map_err(|_| FixError::ParseError(format!("Field {} is not valid UTF-8", tag)))?;
+                Field::new(tag, value)
+            };
+            fields.insert(tag, field);
+        }
+        Ok(Message::from_fields(self.msg_type, fields))
+    }
+}
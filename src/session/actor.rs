@@ -0,0 +1,824 @@
+use std::collections::VecDeque;
+use std::sync::Arc;
+use futures::SinkExt;
+use tokio::net::TcpStream;
+use tokio::sync::{broadcast, mpsc, oneshot, watch};
+use tokio::task::JoinHandle;
+use tokio::time::{self, Duration, Instant};
+
+use crate::config::{SeqNumRollover, SessionConfig};
+use crate::error::FixError;
+use crate::logging::Logger;
+use crate::message::{Message, Field, field, MessagePool};
+use crate::message::parser::MessageParser;
+use crate::message::validator::{MessageValidator, Rejection};
+use crate::store::{Direction, MessageStoreBackend};
+use crate::transport::{FixReader, FixWriter, Transport};
+use crate::Result;
+
+use super::heartbeat::{HeartbeatAction, HeartbeatTimer};
+use super::latency::LatencyHistogram;
+use super::sender::{self, Outbound};
+use super::state::{SessionState, Status, TransitionAction};
+use super::{Session, SessionEvent, SessionID};
+
+// Frames buffered between the actor and the reader/writer tasks of a connection
+const CONNECTION_CAPACITY: usize = 1024;
+
+// A received message, any field order rejection and, if incoming messages are stored, the frame
+type Inbound = Result<Option<(Message, Option<Rejection>, Option<Vec<u8>>)>>;
+
+/// Requests from [`Session`] handles, answered once the actor has acted on them
+pub(crate) enum Command {
+    Send(Outbound),
+    Connect(oneshot::Sender<Result<()>>),
+    Recover(oneshot::Sender<Result<()>>),
+    /// Log out with an optional Text and answer once the counterparty replied or timed out
+    Logout(Option<String>, oneshot::Sender<Result<()>>),
+    Disconnect(oneshot::Sender<Result<()>>),
+    SetNextSenderSeq(u64, oneshot::Sender<Result<()>>),
+    SetNextTargetSeq(u64, oneshot::Sender<Result<()>>),
+}
+
+// Reader and writer tasks of the current connection
+struct Connection {
+    writer: mpsc::Sender<Message>,
+    inbound: mpsc::Receiver<Inbound>,
+    reader: JoinHandle<()>,
+    // Started once logged on
+    heartbeat: Option<HeartbeatTimer>,
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        // The writer stops on its own once its queue is drained and closed
+        self.reader.abort();
+    }
+}
+
+/// Sole owner of a session's state and connection.
+///
+/// Runs as one task that reacts to commands, inbound messages and heartbeat ticks in turn,
+/// while a reader task and a writer task move frames on and off the wire. Sends therefore never
+/// wait on reads, and state is published to the handles through a watch channel.
+pub(crate) struct SessionActor {
+    config: SessionConfig,
+    id: SessionID,
+    state: SessionState,
+    published: watch::Sender<SessionState>,
+    commands: mpsc::Receiver<Command>,
+    logger: Arc<Logger>,
+    store: Arc<dyn MessageStoreBackend>,
+    message_pool: Arc<MessagePool>,
+    latency: Arc<LatencyHistogram>,
+    events: broadcast::Sender<SessionEvent>,
+    connection: Option<Connection>,
+    // Application messages held until the next logon
+    pending: VecDeque<Outbound>,
+    // Callers waiting for the Logout we sent to be answered
+    logout_waiters: Vec<oneshot::Sender<Result<()>>>,
+    // Logon timeout while in InitiateLogon, logout timeout while Disconnecting
+    deadline: Option<Instant>,
+    // Last MsgSeqNum asked for while in ResendRequest
+    resend_end: u64,
+    // The sequence numbers reached their limit, so the logout in progress resets them
+    reset_after_logout: bool,
+}
+
+impl SessionActor {
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn new(
+        config: SessionConfig,
+        id: SessionID,
+        state: SessionState,
+        published: watch::Sender<SessionState>,
+        commands: mpsc::Receiver<Command>,
+        logger: Arc<Logger>,
+        store: Arc<dyn MessageStoreBackend>,
+        message_pool: Arc<MessagePool>,
+        latency: Arc<LatencyHistogram>,
+        events: broadcast::Sender<SessionEvent>,
+    ) -> Self {
+        SessionActor {
+            config,
+            id,
+            state,
+            published,
+            commands,
+            logger,
+            store,
+            message_pool,
+            latency,
+            events,
+            connection: None,
+            pending: VecDeque::new(),
+            logout_waiters: Vec::new(),
+            deadline: None,
+            resend_end: 0,
+            reset_after_logout: false,
+        }
+    }
+
+    /// Process until every session handle has been dropped
+    pub(crate) async fn run(mut self) {
+        loop {
+            let heartbeat_deadline = self.connection.as_ref()
+                .and_then(|connection| connection.heartbeat.as_ref())
+                .map(HeartbeatTimer::next_deadline);
+
+            tokio::select! {
+                command = self.commands.recv() => match command {
+                    Some(command) => self.handle_command(command).await,
+                    None => break,
+                },
+                Some(inbound) = Self::next_inbound(&mut self.connection) => {
+                    self.handle_inbound(inbound).await;
+                },
+                _ = Self::until(heartbeat_deadline) => self.check_heartbeat().await,
+                _ = Self::until(self.deadline) => self.timed_out().await,
+            }
+            self.publish_state();
+        }
+
+        if let Err(e) = self.state.flush().await {
+            self.logger.log_event("ERROR", &format!("Failed to persist session state: {}", e)).ok();
+        }
+    }
+
+    async fn next_inbound(connection: &mut Option<Connection>) -> Option<Inbound> {
+        match connection {
+            Some(connection) => connection.inbound.recv().await,
+            None => std::future::pending().await,
+        }
+    }
+
+    async fn until(deadline: Option<Instant>) {
+        match deadline {
+            Some(deadline) => time::sleep_until(deadline).await,
+            None => std::future::pending().await,
+        }
+    }
+
+    async fn handle_command(&mut self, command: Command) {
+        match command {
+            Command::Send(outbound) => self.send_application(outbound).await,
+            Command::Connect(done) => {
+                let _ = done.send(self.connect().await);
+            },
+            Command::Recover(done) => {
+                let _ = done.send(self.recover().await);
+            },
+            Command::Logout(text, done) => self.initiate_logout(text, done).await,
+            Command::Disconnect(done) => {
+                if self.connection.is_some() {
+                    self.transition(Status::Disconnecting);
+                    let logout = Session::logout_message(&self.message_pool, &self.config).await;
+                    let _ = self.send_session_message(logout).await;  // Best effort send
+                }
+                self.transition(Status::Disconnected);
+                self.publish(SessionEvent::Disconnected(self.id.clone()));
+                let _ = done.send(Ok(()));
+            },
+            Command::SetNextSenderSeq(seq_num, done) => {
+                let _ = done.send(Self::check_seq_num(seq_num).and_then(|_| self.store.set_next_sender_seq(seq_num)));
+            },
+            Command::SetNextTargetSeq(seq_num, done) => {
+                let _ = done.send(Self::check_seq_num(seq_num).and_then(|_| self.store.set_next_target_seq(seq_num)));
+            },
+        }
+    }
+
+    fn check_seq_num(seq_num: u64) -> Result<()> {
+        if seq_num == 0 {
+            return Err(FixError::SessionError(format!("Invalid sequence number {}", seq_num)));
+        }
+        Ok(())
+    }
+
+    async fn connect(&mut self) -> Result<()> {
+        if !self.transition(Status::Connecting) {
+            return Err(FixError::SessionError(format!("Cannot connect while {:?}", self.state.status())));
+        }
+
+        // Connect to counterparty
+        let stream = match TcpStream::connect(&self.config.target_addr).await {
+            Ok(stream) => stream,
+            Err(e) => {
+                self.transition(Status::Error);
+                return Err(e.into());
+            }
+        };
+        self.attach(Transport::new(stream))?;
+        self.initiate_logon().await
+    }
+
+    async fn recover(&mut self) -> Result<()> {
+        // Reset sequence numbers if configured
+        if self.config.reset_on_disconnect {
+            self.store.reset()?;
+        }
+
+        if self.connection.is_some() {
+            let logout = Session::logout_message(&self.message_pool, &self.config).await;
+            let _ = self.send_session_message(logout).await;  // Best effort logout
+        }
+
+        // Attempt to establish new connection
+        self.transition(Status::Recovering);
+        match TcpStream::connect(&self.config.target_addr).await {
+            Ok(stream) => {
+                self.attach(Transport::new(stream))?;
+                self.initiate_logon().await
+            },
+            Err(e) => {
+                self.transition(Status::Error);
+                Err(e.into())
+            }
+        }
+    }
+
+    // Split the transport and start its reader and writer tasks
+    fn attach(&mut self, transport: Transport) -> Result<()> {
+        let (reader, writer) = transport.split()?;
+        let (inbound_tx, inbound) = mpsc::channel(CONNECTION_CAPACITY);
+        let (writer_tx, writer_rx) = mpsc::channel(CONNECTION_CAPACITY);
+        let check_order = self.config.validation.validate_fields_out_of_order;
+        let keep_frames = self.config.store_incoming;
+
+        let reader = tokio::spawn(Self::read_frames(reader, inbound_tx, check_order, keep_frames));
+        tokio::spawn(Self::write_frames(writer, writer_rx, Arc::clone(&self.logger)));
+        self.connection = Some(Connection { writer: writer_tx, inbound, reader, heartbeat: None });
+        Ok(())
+    }
+
+    async fn read_frames(mut reader: FixReader, inbound: mpsc::Sender<Inbound>, check_order: bool, keep_frames: bool) {
+        loop {
+            // Field order is only visible on the raw frame
+            let received = reader.receive_with(|view| {
+                let order = if check_order { MessageValidator::validate_field_order(&view).err() } else { None };
+                let frame = keep_frames.then(|| view.as_bytes().to_vec());
+                Ok((view.to_message()?, order, frame))
+            }).await;
+
+            let open = matches!(received, Ok(Some(_)));
+            if inbound.send(received).await.is_err() || !open {
+                break;
+            }
+        }
+    }
+
+    async fn write_frames(mut writer: FixWriter, mut frames: mpsc::Receiver<Message>, logger: Arc<Logger>) {
+        let mut batch = Vec::new();
+        while let Some(message) = frames.recv().await {
+            // Coalesce whatever else is already queued into the same write
+            batch.push(message);
+            while let Ok(message) = frames.try_recv() {
+                batch.push(message);
+            }
+            if let Err(e) = writer.send_batch(&batch).await {
+                logger.log_event("ERROR", &format!("Failed to send messages: {}", e)).ok();
+                return;
+            }
+            batch.clear();
+        }
+        let _ = writer.close().await;
+    }
+
+    async fn initiate_logon(&mut self) -> Result<()> {
+        if self.config.reset_on_logon {
+            self.store.reset()?;
+        }
+        self.transition(Status::InitiateLogon);
+        // Not returned to the pool, ResetSeqNumFlag depends on the session's configuration
+        let logon = Session::logon_message(&self.message_pool, &self.config).await;
+        self.send(logon).await.map(|_| ())
+    }
+
+    async fn check_heartbeat(&mut self) {
+        let Some(timer) = self.connection.as_mut().and_then(|connection| connection.heartbeat.as_mut()) else {
+            return;
+        };
+
+        match timer.poll(Instant::now()) {
+            HeartbeatAction::Idle => {},
+            HeartbeatAction::SendHeartbeat => {
+                let heartbeat = self.message_pool.get_message(field::values::HEARTBEAT).await;
+                if let Err(e) = self.send_session_message(heartbeat).await {
+                    self.logger.log_event("ERROR", &format!("Failed to send heartbeat: {}", e)).ok();
+                    self.connection_lost();
+                }
+            },
+            HeartbeatAction::SendTestRequest(test_req_id) => {
+                let mut test_request = self.message_pool.get_message(field::values::TEST_REQUEST).await;
+                let _ = test_request.set_field(Field::new(field::TEST_REQ_ID, test_req_id));
+                if let Err(e) = self.send_session_message(test_request).await {
+                    self.logger.log_event("ERROR", &format!("Failed to send test request: {}", e)).ok();
+                    self.connection_lost();
+                }
+            },
+            HeartbeatAction::Disconnect => {
+                self.logger.log_event("WARN", "TestRequest not answered, disconnecting").ok();
+                self.connection_lost();
+            },
+        }
+    }
+
+    fn connection_lost(&mut self) {
+        self.transition(Status::Disconnected);
+        self.publish(SessionEvent::Disconnected(self.id.clone()));
+    }
+
+    async fn timed_out(&mut self) {
+        match self.state.status() {
+            Status::InitiateLogon => {
+                self.logger.log_event("WARN", &format!(
+                    "No Logon reply within {}s, closing connection", self.config.logon_timeout
+                )).ok();
+                self.connection_lost();
+            },
+            Status::Disconnecting => {
+                self.logger.log_event("WARN", &format!(
+                    "No Logout reply within {}s, closing connection", self.config.logout_timeout
+                )).ok();
+                self.finish_logout(false).await;
+            },
+            _ => self.deadline = None,
+        }
+    }
+
+    async fn handle_inbound(&mut self, inbound: Inbound) {
+        let (msg, order_rejection, frame) = match inbound {
+            Ok(Some(received)) => received,
+            Ok(None) if *self.state.status() == Status::Disconnecting => {
+                self.finish_logout(false).await;
+                return;
+            },
+            Ok(None) => {
+                self.logger.log_event("INFO", "Connection closed by counterparty").ok();
+                self.connection_lost();
+                return;
+            },
+            Err(e) => {
+                self.logger.log_event("ERROR", &format!("Error receiving message: {}", e)).ok();
+                self.transition(Status::Error);
+                self.publish(SessionEvent::Disconnected(self.id.clone()));
+                return;
+            }
+        };
+        if let Some(timer) = self.connection.as_mut().and_then(|connection| connection.heartbeat.as_mut()) {
+            timer.on_received(Instant::now());
+        }
+
+        if let Some(sent) = MessageValidator::sending_time(&msg) {
+            let latency = (chrono::Utc::now() - sent).abs();
+            self.latency.record(latency.to_std().unwrap_or_default());
+        }
+
+        // A message for another FIX version is never processed or rejected
+        if let Err(e) = MessageValidator::check_begin_string(&msg, &self.config) {
+            self.logger.log_event("ERROR", &format!("{}, logging out", e)).ok();
+            let mut logout = Session::logout_message(&self.message_pool, &self.config).await;
+            let _ = logout.set_field(Field::new(field::TEXT, "Incorrect BeginString"));
+            let _ = self.send(logout).await;  // Best effort send
+            self.connection_lost();
+            return;
+        }
+
+        // A SequenceReset in reset mode moves the expected MsgSeqNum whatever its own
+        if msg.msg_type() == field::values::SEQUENCE_RESET && !Self::is_gap_fill(&msg) {
+            self.reset_target_seq(&msg);
+            return;
+        }
+
+        // Store incoming message
+        if let Some(seq_num) = msg.get_field(field::MSG_SEQ_NUM) {
+            if let Ok(seq_num) = MessageParser::parse_seq_num(seq_num.value()) {
+                // Check for sequence gaps
+                let expected = match self.store.next_target_seq() {
+                    Ok(expected) => expected,
+                    Err(e) => {
+                        self.logger.log_event("ERROR", &format!("Failed to read sequence number: {}", e)).ok();
+                        return;
+                    }
+                };
+                if seq_num > expected {
+                    // A Logon or Logout is acted on despite the gap, which is requested
+                    // afterwards. Anything else waits for the resend, a gap fill included, as
+                    // applying it would move the expected number past the resent messages.
+                    if matches!(msg.msg_type(), field::values::LOGON | field::values::LOGOUT) {
+                        self.process(&msg, order_rejection).await;
+                    }
+                    self.request_resend(expected, seq_num).await;
+                    return;
+                }
+                if seq_num < expected {
+                    // A possible duplicate was already processed, anything else means lost messages
+                    if Self::is_poss_dup(&msg) {
+                        return;
+                    }
+                    let text = format!("MsgSeqNum too low, expecting {} but received {}", expected, seq_num);
+                    self.logger.log_event("ERROR", &format!("{}, logging out", text)).ok();
+                    let mut logout = Session::logout_message(&self.message_pool, &self.config).await;
+                    let _ = logout.set_field(Field::new(field::TEXT, text));
+                    let _ = self.send(logout).await;  // Best effort send
+                    self.connection_lost();
+                    return;
+                }
+
+                if let Some(frame) = &frame {
+                    if let Err(e) = self.store.store_message(Direction::Inbound, seq_num, frame) {
+                        self.logger.log_event("ERROR", &format!("Failed to store message {}: {}", seq_num, e)).ok();
+                    }
+                }
+                if let Err(e) = self.store.set_next_target_seq(expected.saturating_add(1)) {
+                    self.logger.log_event("ERROR", &format!("Failed to store sequence number: {}", e)).ok();
+                }
+                if *self.state.status() == Status::ResendRequest && seq_num >= self.resend_end {
+                    self.transition(Status::Connected);
+                }
+            }
+        }
+
+        self.process(&msg, order_rejection).await;
+    }
+
+    /// Ask for the messages from `expected` to `received`, unless already asked for or the
+    /// connection is gone
+    async fn request_resend(&mut self, expected: u64, received: u64) {
+        if self.connection.is_none() {
+            return;
+        }
+        if *self.state.status() == Status::ResendRequest && received <= self.resend_end {
+            return;  // Already requested
+        }
+        if *self.state.status() == Status::Connected {
+            self.transition(Status::ResendRequest);
+        }
+        self.resend_end = received;
+        self.publish(SessionEvent::SequenceGap {
+            session_id: self.id.clone(),
+            expected,
+            received,
+        });
+
+        let mut resend_request = self.message_pool.get_message(field::values::RESEND_REQUEST).await;
+        let _ = resend_request.set_field(Field::new(field::BEGIN_SEQ_NO, expected.to_string()));
+        let _ = resend_request.set_field(Field::new(field::END_SEQ_NO, received.to_string()));
+        if let Err(e) = self.send_session_message(resend_request).await {
+            self.logger.log_event("ERROR", &format!("Failed to send resend request: {}", e)).ok();
+        }
+    }
+
+    /// Validate a message that passed the sequence checks and act on it
+    async fn process(&mut self, msg: &Message, order_rejection: Option<Rejection>) {
+        // Apply the session's validation policy before acting on the message
+        let rejection = order_rejection
+            .or_else(|| MessageValidator::validate_inbound(msg, &self.config).err());
+        if let Some(rejection) = rejection {
+            self.reject(msg, rejection).await;
+            return;
+        }
+
+        // Process message based on type
+        match msg.msg_type() {
+            field::values::LOGON => {
+                if *self.state.status() == Status::InitiateLogon {
+                    self.transition(Status::LogonReceived);
+                    self.transition(Status::Connected);
+                    self.publish(SessionEvent::LoggedOn(self.id.clone()));
+                    self.release_pending().await;
+                }
+            },
+            field::values::TEST_REQUEST => {
+                if let Some(test_req_id) = msg.get_field(field::TEST_REQ_ID) {
+                    let mut heartbeat = self.message_pool.get_message(field::values::HEARTBEAT).await;
+                    let _ = heartbeat.set_field(Field::new(field::TEST_REQ_ID, test_req_id.value()));
+                    if let Err(e) = self.send(heartbeat).await {
+                        self.logger.log_event("ERROR", &format!("Failed to send heartbeat: {}", e)).ok();
+                    }
+                }
+            },
+            field::values::HEARTBEAT => {
+                // Receipt alone answers a pending TestRequest
+            },
+            field::values::SEQUENCE_RESET => {
+                // Gap fill mode, the reset mode was handled before the sequence check
+                self.reset_target_seq(msg);
+            },
+            field::values::LOGOUT => {
+                if *self.state.status() != Status::Disconnecting {
+                    // Counterparty initiated, confirm before closing
+                    self.transition(Status::Disconnecting);
+                    let logout = Session::logout_message(&self.message_pool, &self.config).await;
+                    if let Err(e) = self.send_session_message(logout).await {
+                        self.logger.log_event("ERROR", &format!("Failed to send logout: {}", e)).ok();
+                    }
+                }
+                self.finish_logout(true).await;
+            },
+            field::values::REJECT => {
+                let text = msg.get_field(field::TEXT).map(|f| f.value().to_string()).unwrap_or_default();
+                self.logger.log_event("WARN", &format!("Received reject: {}", text)).ok();
+                self.publish(SessionEvent::Reject {
+                    session_id: self.id.clone(),
+                    ref_seq_num: msg.get_field(field::REF_SEQ_NUM).and_then(|f| MessageParser::parse_seq_num(f.value()).ok()),
+                    reason: msg.get_field(field::SESSION_REJECT_REASON).and_then(|f| f.value().parse().ok()),
+                    text,
+                    outbound: false,
+                });
+            },
+            _ => {
+                self.logger.log_event("INFO", &format!("Received message type: {}", msg.msg_type())).ok();
+            }
+        }
+    }
+
+    /// First phase of a logout: send Logout and wait for the reply, see [`Self::finish_logout`]
+    async fn initiate_logout(&mut self, text: Option<String>, done: oneshot::Sender<Result<()>>) {
+        if self.connection.is_none() {
+            self.transition(Status::Disconnected);
+            let _ = done.send(Ok(()));
+            return;
+        }
+        self.logout_waiters.push(done);
+        if *self.state.status() == Status::Disconnecting {
+            return;  // Already waiting for the reply
+        }
+
+        self.transition(Status::Disconnecting);
+        let mut logout = Session::logout_message(&self.message_pool, &self.config).await;
+        let result = match text {
+            Some(text) => {
+                let _ = logout.set_field(Field::new(field::TEXT, text));
+                self.send(logout).await.map(|_| ())
+            },
+            None => self.send_session_message(logout).await,
+        };
+        if let Err(e) = result {
+            for done in self.logout_waiters.drain(..) {
+                let _ = done.send(Err(FixError::SessionError(format!("Failed to send logout: {}", e))));
+            }
+            self.transition(Status::Disconnected);
+        }
+    }
+
+    /// Second phase: the Logout was answered, timed out or the peer logged out. Close the
+    /// connection once queued frames are written and apply `reset_on_logout`.
+    ///
+    /// With the Logout `confirmed` by the counterparty, which resolves any gap before logging
+    /// out, nothing sent so far can be asked for again, so the store is compacted.
+    async fn finish_logout(&mut self, confirmed: bool) {
+        if self.config.reset_on_logout || std::mem::take(&mut self.reset_after_logout) {
+            if let Err(e) = self.store.reset() {
+                self.logger.log_event("ERROR", &format!("Failed to reset store on logout: {}", e)).ok();
+            }
+        } else if confirmed {
+            if let Err(e) = self.store.next_sender_seq().and_then(|next| self.store.compact(next)) {
+                self.logger.log_event("ERROR", &format!("Failed to compact store on logout: {}", e)).ok();
+            }
+        }
+        // Taken first, closing the transport fails any waiter still there
+        let waiters = std::mem::take(&mut self.logout_waiters);
+        self.transition(Status::Disconnected);
+        if let Err(e) = self.state.flush().await {
+            self.logger.log_event("ERROR", &format!("Failed to persist session state: {}", e)).ok();
+        }
+        self.publish(SessionEvent::LoggedOut(self.id.clone()));
+        for done in waiters {
+            let _ = done.send(Ok(()));
+        }
+    }
+
+    async fn reject(&mut self, msg: &Message, rejection: Rejection) {
+        self.logger.log_event("WARN", &format!("Rejecting message: {}", rejection)).ok();
+        let reject = Session::create_reject_message(&self.config, msg, &rejection);
+        if let Err(e) = self.send(reject).await {
+            self.logger.log_event("ERROR", &format!("Failed to send reject: {}", e)).ok();
+        }
+        self.publish(SessionEvent::Reject {
+            session_id: self.id.clone(),
+            ref_seq_num: msg.get_field(field::MSG_SEQ_NUM).and_then(|f| MessageParser::parse_seq_num(f.value()).ok()),
+            reason: Some(rejection.reason.code()),
+            text: rejection.text.clone(),
+            outbound: true,
+        });
+
+        if rejection.ends_session() {
+            let mut logout = Session::logout_message(&self.message_pool, &self.config).await;
+            let _ = logout.set_field(Field::new(field::TEXT, &rejection.text));
+            if let Err(e) = self.send(logout).await {
+                self.logger.log_event("ERROR", &format!("Failed to send logout: {}", e)).ok();
+            }
+            self.transition(Status::Disconnected);
+            self.publish(SessionEvent::LoggedOut(self.id.clone()));
+        }
+    }
+
+    async fn send_application(&mut self, outbound: Outbound) {
+        if !self.state.status().is_logged_on() {
+            if self.config.queue_while_disconnected {
+                self.pending.push_back(outbound);
+            } else {
+                let _ = outbound.done.send(Err(sender::not_logged_on(&self.id)));
+            }
+            return;
+        }
+
+        let Outbound { mut message, done } = outbound;
+        if let Err(e) = self.roll_over(&mut message).await {
+            let _ = done.send(Err(e));
+            return;
+        }
+        let seq_num = match self.stamp(&mut message) {
+            Ok(seq_num) => seq_num,
+            Err(e) => {
+                let _ = done.send(Err(e));
+                return;
+            }
+        };
+        // Kept as the bytes the writer puts on the wire, serialization being deterministic
+        if let Err(e) = message.to_bytes().and_then(|bytes| self.store.store_outgoing(seq_num, &bytes)) {
+            let _ = done.send(Err(e));
+            return;
+        }
+        // Not advanced if the writer is gone, the next message takes the number instead
+        if let Err(e) = self.write(message).await {
+            self.logger.log_event("ERROR", &format!("Failed to send message {}: {}", seq_num, e)).ok();
+            let _ = done.send(Err(e));
+            return;
+        }
+        self.advance(seq_num);
+        let _ = done.send(Ok(seq_num));
+    }
+
+    async fn release_pending(&mut self) {
+        while let Some(outbound) = self.pending.pop_front() {
+            self.send_application(outbound).await;
+        }
+    }
+
+    /// Send an admin message taken from the pool, returning it afterwards. Messages given
+    /// optional fields are sent with [`Self::send`] instead so the fields cannot leak.
+    async fn send_session_message(&mut self, message: Message) -> Result<()> {
+        let result = self.send(message.clone()).await.map(|_| ());
+        self.message_pool.return_message(message).await;
+        result
+    }
+
+    async fn send(&mut self, mut message: Message) -> Result<u64> {
+        self.roll_over(&mut message).await?;
+        let seq_num = self.stamp(&mut message)?;
+        self.write(message).await?;
+        self.advance(seq_num);
+        Ok(seq_num)
+    }
+
+    // Stamp the session header, next MsgSeqNum and SendingTime
+    fn stamp(&self, message: &mut Message) -> Result<u64> {
+        let seq_num = self.store.next_sender_seq()?;
+        Session::set_session_header(message, &self.config);
+        let _ = message.set_field(Field::new(field::MSG_SEQ_NUM, seq_num.to_string()));
+        let _ = message.set_field(Field::new(field::SENDING_TIME,
+            chrono::Utc::now().format("%Y%m%d-%H:%M:%S").to_string()));
+        Ok(seq_num)
+    }
+
+    fn advance(&mut self, seq_num: u64) {
+        // Past the limit the next message rolls over before it is stamped
+        if let Err(e) = self.store.set_next_sender_seq(seq_num.saturating_add(1)) {
+            self.logger.log_event("ERROR", &format!("Failed to store sequence number: {}", e)).ok();
+        }
+        if let Some(timer) = self.connection.as_mut().and_then(|connection| connection.heartbeat.as_mut()) {
+            timer.on_sent(Instant::now());
+        }
+    }
+
+    /// Apply the session's `seq_num_limit` before `message` takes the next MsgSeqNum. A Logout
+    /// may still take the last number and a Logon starts the sequence over; anything else first
+    /// resets the sequence or is refused while the session logs out.
+    async fn roll_over(&mut self, message: &mut Message) -> Result<()> {
+        let limit = self.config.seq_num_limit;
+        let next = self.store.next_sender_seq()?;
+        if next < limit.max_seq_num {
+            return Ok(());
+        }
+        match message.msg_type() {
+            field::values::LOGOUT => return Ok(()),
+            field::values::LOGON => {
+                self.store.reset()?;
+                let _ = message.set_field(Field::new(field::RESET_SEQ_NUM_FLAG, "Y"));
+                return Ok(());
+            },
+            _ => {},
+        }
+
+        match limit.action {
+            SeqNumRollover::SequenceReset => {
+                self.logger.log_event("INFO", &format!("MsgSeqNum {} reached the limit, resetting to 1", next)).ok();
+                let mut reset = self.message_pool.get_message(field::values::SEQUENCE_RESET).await;
+                let _ = reset.set_field(Field::new(field::GAP_FILL_FLAG, "N"));
+                let _ = reset.set_field(Field::new(field::NEW_SEQ_NO, "1"));
+                self.stamp(&mut reset)?;
+                self.write(reset).await?;
+                // The old cycle's numbers can no longer be asked for
+                self.store.reset_sender()
+            },
+            SeqNumRollover::Logout => {
+                if *self.state.status() != Status::Disconnecting {
+                    self.logger.log_event("WARN", &format!("MsgSeqNum {} reached the limit, logging out", next)).ok();
+                    let mut logout = Session::logout_message(&self.message_pool, &self.config).await;
+                    let _ = logout.set_field(Field::new(field::TEXT, "MsgSeqNum limit reached"));
+                    self.stamp(&mut logout)?;
+                    self.write(logout).await?;
+                    self.reset_after_logout = true;
+                    self.transition(Status::Disconnecting);
+                }
+                Err(FixError::SessionError(format!("MsgSeqNum limit {} reached", limit.max_seq_num)))
+            },
+        }
+    }
+
+    fn is_gap_fill(msg: &Message) -> bool {
+        msg.get_field(field::GAP_FILL_FLAG).is_some_and(|f| f.value() == "Y")
+    }
+
+    fn is_poss_dup(msg: &Message) -> bool {
+        msg.get_field(field::POSS_DUP_FLAG).is_some_and(|f| f.value() == "Y")
+    }
+
+    // Expect NewSeqNo of a SequenceReset next
+    fn reset_target_seq(&mut self, msg: &Message) {
+        let new_seq_no = match msg.get_field(field::NEW_SEQ_NO).map(|f| MessageParser::parse_seq_num(f.value())) {
+            Some(Ok(new_seq_no)) if new_seq_no > 0 => new_seq_no,
+            _ => {
+                self.logger.log_event("WARN", "Ignoring SequenceReset without a valid NewSeqNo").ok();
+                return;
+            }
+        };
+        // A gap fill only ever moves the sequence forward
+        if Self::is_gap_fill(msg) && self.store.next_target_seq().is_ok_and(|next| new_seq_no < next) {
+            return;
+        }
+        if let Err(e) = self.store.set_next_target_seq(new_seq_no) {
+            self.logger.log_event("ERROR", &format!("Failed to store sequence number: {}", e)).ok();
+        }
+    }
+
+    async fn write(&self, message: Message) -> Result<()> {
+        let connection = self.connection.as_ref()
+            .ok_or_else(|| FixError::TransportError("Not connected".to_string()))?;
+        connection.writer.send(message).await
+            .map_err(|_| FixError::TransportError("Connection closed".to_string()))
+    }
+
+    /// Take a transition of the session state machine and carry out its action. Illegal
+    /// transitions are logged and leave the state unchanged.
+    fn transition(&mut self, next: Status) -> bool {
+        let action = match self.state.transition(next) {
+            Ok(action) => action,
+            Err(e) => {
+                self.logger.log_event("ERROR", &e.to_string()).ok();
+                return false;
+            }
+        };
+
+        match action {
+            TransitionAction::None => {},
+            TransitionAction::StartLogonTimer => {
+                self.deadline = Some(Instant::now() + Duration::from_secs(self.config.logon_timeout));
+            },
+            TransitionAction::StartHeartbeat => {
+                self.deadline = None;
+                if let Some(connection) = self.connection.as_mut() {
+                    let interval = Duration::from_secs(self.config.heart_bt_int as u64);
+                    connection.heartbeat = Some(HeartbeatTimer::for_interval(interval, Instant::now()));
+                }
+            },
+            TransitionAction::StartLogoutTimer => {
+                self.deadline = Some(Instant::now() + Duration::from_secs(self.config.logout_timeout));
+            },
+            TransitionAction::CloseTransport => {
+                self.deadline = None;
+                self.connection = None;
+                // A logout still waiting for its reply will not get one
+                self.reset_after_logout = false;
+                for done in self.logout_waiters.drain(..) {
+                    let _ = done.send(Err(FixError::SessionError(format!(
+                        "Connection of {} closed before the logout completed", self.id
+                    ))));
+                }
+            },
+        }
+        self.publish_state();
+        true
+    }
+
+    fn publish_state(&self) {
+        self.published.send_replace(self.state.clone());
+    }
+
+    fn publish(&self, event: SessionEvent) {
+        // Sending only fails when nobody is subscribed
+        let _ = self.events.send(event);
+    }
+}
@@ -0,0 +1,122 @@
+use tokio::time::{Duration, Instant};
+
+/// What a logged on session has to do to keep its connection alive
+#[derive(Debug, Clone, PartialEq)]
+pub enum HeartbeatAction {
+    /// Nothing is due before [`HeartbeatTimer::next_deadline`]
+    Idle,
+    /// Nothing was sent for HeartBtInt
+    SendHeartbeat,
+    /// Nothing was received for HeartBtInt plus the tolerance; send a TestRequest with this ID
+    SendTestRequest(String),
+    /// A TestRequest went unanswered
+    Disconnect,
+}
+
+/// Heartbeat and TestRequest timing of a session, driven by its actual traffic.
+///
+/// Uses the monotonic clock, so wall-clock adjustments never trigger or suppress a heartbeat.
+/// Times are passed in to keep the decisions deterministic.
+#[derive(Debug, Clone)]
+pub struct HeartbeatTimer {
+    interval: Duration,
+    tolerance: Duration,
+    last_sent: Instant,
+    last_received: Instant,
+    // TestReqID and send time of the TestRequest waiting for an answer
+    test_request: Option<(String, Instant)>,
+    test_requests_sent: u64,
+}
+
+impl HeartbeatTimer {
+    /// Timer allowing inbound messages to be up to 20% of HeartBtInt late
+    pub fn for_interval(interval: Duration, now: Instant) -> Self {
+        Self::new(interval, interval / 5, now)
+    }
+
+    pub fn new(interval: Duration, tolerance: Duration, now: Instant) -> Self {
+        HeartbeatTimer {
+            interval,
+            tolerance,
+            last_sent: now,
+            last_received: now,
+            test_request: None,
+            test_requests_sent: 0,
+        }
+    }
+
+    pub fn on_sent(&mut self, now: Instant) {
+        self.last_sent = now;
+    }
+
+    /// Any inbound message shows the counterparty is alive and answers a pending TestRequest
+    pub fn on_received(&mut self, now: Instant) {
+        self.last_received = now;
+        self.test_request = None;
+    }
+
+    pub fn poll(&mut self, now: Instant) -> HeartbeatAction {
+        if let Some((_, sent)) = &self.test_request {
+            if now >= *sent + self.interval + self.tolerance {
+                return HeartbeatAction::Disconnect;
+            }
+        } else if now >= self.last_received + self.interval + self.tolerance {
+            self.test_requests_sent += 1;
+            let id = format!("TEST_REQ_{}", self.test_requests_sent);
+            self.test_request = Some((id.clone(), now));
+            return HeartbeatAction::SendTestRequest(id);
+        }
+
+        if now >= self.last_sent + self.interval {
+            HeartbeatAction::SendHeartbeat
+        } else {
+            HeartbeatAction::Idle
+        }
+    }
+
+    /// When [`Self::poll`] next has something to do, barring new traffic
+    pub fn next_deadline(&self) -> Instant {
+        let inbound = match &self.test_request {
+            Some((_, sent)) => *sent + self.interval + self.tolerance,
+            None => self.last_received + self.interval + self.tolerance,
+        };
+        inbound.min(self.last_sent + self.interval)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_heartbeat_timer() {
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        let mut timer = HeartbeatTimer::new(Duration::from_secs(30), Duration::from_secs(2), start);
+        assert_eq!(timer.next_deadline(), at(30_000));
+
+        // Outbound traffic postpones the heartbeat
+        timer.on_sent(at(10_000));
+        timer.on_received(at(10_000));
+        assert_eq!(timer.poll(at(39_999)), HeartbeatAction::Idle);
+        assert_eq!(timer.poll(at(40_000)), HeartbeatAction::SendHeartbeat);
+        timer.on_sent(at(40_000));
+
+        // Inbound silence past HeartBtInt plus tolerance asks for a TestRequest
+        assert_eq!(timer.next_deadline(), at(42_000));
+        let id = match timer.poll(at(42_000)) {
+            HeartbeatAction::SendTestRequest(id) => id,
+            action => panic!("unexpected {:?}", action),
+        };
+        timer.on_sent(at(42_000));
+        assert_eq!(timer.poll(at(50_000)), HeartbeatAction::Idle);
+
+        // An answer clears it, no answer disconnects
+        let mut answered = timer.clone();
+        answered.on_received(at(43_000));
+        assert_eq!(answered.poll(at(72_000)), HeartbeatAction::SendHeartbeat);
+        assert_eq!(timer.poll(at(73_999)), HeartbeatAction::SendHeartbeat);
+        assert_eq!(timer.poll(at(74_000)), HeartbeatAction::Disconnect);
+        assert!(id.starts_with("TEST_REQ_"));
+    }
+}
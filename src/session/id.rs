@@ -0,0 +1,171 @@
+use std::fmt;
+use std::str::FromStr;
+use serde::{Serialize, Deserialize};
+use crate::config::SessionConfig;
+use crate::error::FixError;
+
+/// Identity of a FIX session.
+///
+/// Displayed as `BEGINSTRING:SENDER[/SUBID[/LOCATIONID]]->TARGET[/SUBID[/LOCATIONID]][:QUALIFIER]`,
+/// e.g. `FIX.4.2:CLIENT_1->BROKER/DESK`, and parsed back from the same format. Components
+/// may contain anything but ':', '/' and "->".
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct SessionID {
+    pub begin_string: String,
+    pub sender_comp_id: String,
+    pub sender_sub_id: Option<String>,
+    pub sender_location_id: Option<String>,
+    pub target_comp_id: String,
+    pub target_sub_id: Option<String>,
+    pub target_location_id: Option<String>,
+    pub qualifier: Option<String>,
+}
+
+impl SessionID {
+    pub fn new(begin_string: &str, sender_comp_id: &str, target_comp_id: &str) -> Self {
+        SessionID {
+            begin_string: begin_string.to_string(),
+            sender_comp_id: sender_comp_id.to_string(),
+            sender_sub_id: None,
+            sender_location_id: None,
+            target_comp_id: target_comp_id.to_string(),
+            target_sub_id: None,
+            target_location_id: None,
+            qualifier: None,
+        }
+    }
+
+    pub fn from_config(config: &SessionConfig) -> Self {
+        SessionID {
+            begin_string: config.begin_string.clone(),
+            sender_comp_id: config.sender_comp_id.clone(),
+            sender_sub_id: config.sender_sub_id.clone(),
+            sender_location_id: config.sender_location_id.clone(),
+            target_comp_id: config.target_comp_id.clone(),
+            target_sub_id: config.target_sub_id.clone(),
+            target_location_id: config.target_location_id.clone(),
+            qualifier: config.session_qualifier.clone(),
+        }
+    }
+
+    /// Name usable as a file or directory name, unique per session
+    pub fn file_name(&self) -> String {
+        let mut name = String::new();
+        for c in self.to_string().chars() {
+            if c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-') {
+                name.push(c);
+            } else {
+                let mut bytes = [0; 4];
+                for b in c.encode_utf8(&mut bytes).bytes() {
+                    name.push_str(&format!("%{:02X}", b));
+                }
+            }
+        }
+        name
+    }
+
+    fn write_party(f: &mut fmt::Formatter, comp_id: &str, sub_id: &Option<String>, location_id: &Option<String>) -> fmt::Result {
+        write!(f, "{}", comp_id)?;
+        match (sub_id, location_id) {
+            (Some(sub_id), Some(location_id)) => write!(f, "/{}/{}", sub_id, location_id),
+            (Some(sub_id), None) => write!(f, "/{}", sub_id),
+            (None, Some(location_id)) => write!(f, "//{}", location_id),
+            (None, None) => Ok(()),
+        }
+    }
+
+    fn parse_party(party: &str) -> crate::Result<(String, Option<String>, Option<String>)> {
+        let mut parts = party.split('/');
+        let comp_id = parts.next().unwrap_or("");
+        if comp_id.is_empty() {
+            return Err(FixError::ParseError(format!("Missing CompID in session ID part: {}", party)));
+        }
+        let optional = |part: Option<&str>| part.filter(|p| !p.is_empty()).map(str::to_string);
+        let sub_id = optional(parts.next());
+        let location_id = optional(parts.next());
+        if parts.next().is_some() {
+            return Err(FixError::ParseError(format!("Too many components in session ID part: {}", party)));
+        }
+        Ok((comp_id.to_string(), sub_id, location_id))
+    }
+}
+
+impl fmt::Display for SessionID {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:", self.begin_string)?;
+        Self::write_party(f, &self.sender_comp_id, &self.sender_sub_id, &self.sender_location_id)?;
+        write!(f, "->")?;
+        Self::write_party(f, &self.target_comp_id, &self.target_sub_id, &self.target_location_id)?;
+        if let Some(qualifier) = &self.qualifier {
+            write!(f, ":{}", qualifier)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for SessionID {
+    type Err = FixError;
+
+    fn from_str(s: &str) -> crate::Result<Self> {
+        let invalid = || FixError::ParseError(format!("Invalid session ID: {}", s));
+
+        let (begin_string, rest) = s.split_once(':').ok_or_else(invalid)?;
+        let (parties, qualifier) = match rest.split_once(':') {
+            Some((parties, qualifier)) => (parties, Some(qualifier.to_string())),
+            None => (rest, None),
+        };
+        let (sender, target) = parties.split_once("->").ok_or_else(invalid)?;
+        if begin_string.is_empty() || qualifier.as_deref() == Some("") {
+            return Err(invalid());
+        }
+
+        let (sender_comp_id, sender_sub_id, sender_location_id) = Self::parse_party(sender)?;
+        let (target_comp_id, target_sub_id, target_location_id) = Self::parse_party(target)?;
+        Ok(SessionID {
+            begin_string: begin_string.to_string(),
+            sender_comp_id,
+            sender_sub_id,
+            sender_location_id,
+            target_comp_id,
+            target_sub_id,
+            target_location_id,
+            qualifier,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_session_id_roundtrip() {
+        let id = SessionID::new("FIX.4.2", "CLIENT_1", "BROKER_1");
+        assert_eq!(id.to_string(), "FIX.4.2:CLIENT_1->BROKER_1");
+        assert_eq!("FIX.4.2:CLIENT_1->BROKER_1".parse::<SessionID>().unwrap(), id);
+
+        let mut qualified = id.clone();
+        qualified.sender_sub_id = Some("DESK".to_string());
+        qualified.target_location_id = Some("LDN".to_string());
+        qualified.qualifier = Some("B".to_string());
+        let text = qualified.to_string();
+        assert_eq!(text, "FIX.4.2:CLIENT_1/DESK->BROKER_1//LDN:B");
+        assert_eq!(text.parse::<SessionID>().unwrap(), qualified);
+        assert_ne!(qualified, id);
+    }
+
+    #[test]
+    fn test_session_id_invalid() {
+        assert!("SENDER_TARGET".parse::<SessionID>().is_err());
+        assert!("FIX.4.2:SENDER".parse::<SessionID>().is_err());
+        assert!("FIX.4.2:->TARGET".parse::<SessionID>().is_err());
+        assert!("FIX.4.2:A/B/C/D->TARGET".parse::<SessionID>().is_err());
+    }
+
+    #[test]
+    fn test_session_id_file_name() {
+        let id = SessionID::new("FIX.4.2", "CLIENT_1", "BROKER");
+        assert_eq!(id.file_name(), "FIX.4.2%3ACLIENT_1-%3EBROKER");
+        assert_ne!(id.file_name(), SessionID::new("FIX.4.4", "CLIENT_1", "BROKER").file_name());
+    }
+}
@@ -0,0 +1,122 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use serde::{Serialize, Deserialize};
+
+// Bucket i counts latencies up to 2^i milliseconds; the last bucket takes everything above
+const BUCKETS: usize = 22;
+
+/// Inbound latency (local receive time minus SendingTime) of a session.
+///
+/// Recording is lock-free so the message processor never waits on readers taking snapshots.
+#[derive(Debug)]
+pub struct LatencyHistogram {
+    buckets: [AtomicU64; BUCKETS],
+    count: AtomicU64,
+    sum_ms: AtomicU64,
+    max_ms: AtomicU64,
+}
+
+/// Point-in-time copy of a [`LatencyHistogram`], suitable for export
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LatencySnapshot {
+    pub count: u64,
+    pub mean_ms: f64,
+    pub max_ms: u64,
+    /// `(upper bound in ms, count)` per bucket; the last bound is `u64::MAX`
+    pub buckets: Vec<(u64, u64)>,
+}
+
+impl LatencyHistogram {
+    pub fn new() -> Self {
+        LatencyHistogram {
+            buckets: std::array::from_fn(|_| AtomicU64::new(0)),
+            count: AtomicU64::new(0),
+            sum_ms: AtomicU64::new(0),
+            max_ms: AtomicU64::new(0),
+        }
+    }
+
+    pub fn record(&self, latency: Duration) {
+        let ms = latency.as_millis().min(u64::MAX as u128) as u64;
+        let bucket = (0..BUCKETS - 1)
+            .find(|&i| ms <= Self::upper_bound(i))
+            .unwrap_or(BUCKETS - 1);
+
+        self.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum_ms.fetch_add(ms, Ordering::Relaxed);
+        self.max_ms.fetch_max(ms, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> LatencySnapshot {
+        let count = self.count.load(Ordering::Relaxed);
+        let sum = self.sum_ms.load(Ordering::Relaxed);
+        LatencySnapshot {
+            count,
+            mean_ms: if count == 0 { 0.0 } else { sum as f64 / count as f64 },
+            max_ms: self.max_ms.load(Ordering::Relaxed),
+            buckets: self.buckets.iter()
+                .enumerate()
+                .map(|(i, bucket)| (Self::upper_bound(i), bucket.load(Ordering::Relaxed)))
+                .collect(),
+        }
+    }
+
+    pub fn reset(&self) {
+        for bucket in &self.buckets {
+            bucket.store(0, Ordering::Relaxed);
+        }
+        self.count.store(0, Ordering::Relaxed);
+        self.sum_ms.store(0, Ordering::Relaxed);
+        self.max_ms.store(0, Ordering::Relaxed);
+    }
+
+    fn upper_bound(bucket: usize) -> u64 {
+        if bucket == BUCKETS - 1 { u64::MAX } else { 1 << bucket }
+    }
+}
+
+impl Default for LatencyHistogram {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LatencySnapshot {
+    /// Upper bound in ms of the bucket holding the given percentile (0-100)
+    pub fn percentile(&self, percentile: f64) -> u64 {
+        let target = ((self.count as f64) * percentile / 100.0).ceil().max(1.0) as u64;
+        let mut seen = 0;
+        for &(bound, count) in &self.buckets {
+            seen += count;
+            if seen >= target {
+                return bound.min(self.max_ms);
+            }
+        }
+        self.max_ms
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_latency_histogram() {
+        let histogram = LatencyHistogram::new();
+        for ms in [0, 1, 3, 3, 40, 250] {
+            histogram.record(Duration::from_millis(ms));
+        }
+
+        let snapshot = histogram.snapshot();
+        assert_eq!(snapshot.count, 6);
+        assert_eq!(snapshot.max_ms, 250);
+        assert_eq!(snapshot.buckets[0], (1, 2));
+        assert_eq!(snapshot.buckets[2], (4, 2));
+        assert_eq!(snapshot.percentile(50.0), 4);
+        assert_eq!(snapshot.percentile(100.0), 250);
+
+        histogram.reset();
+        assert_eq!(histogram.snapshot().count, 0);
+    }
+}
@@ -1,395 +1,243 @@
 use std::sync::Arc;
-use tokio::sync::Mutex;
-use tokio::net::TcpStream;
-use tokio::time::{self, Duration};
-use std::path::PathBuf;
+use tokio::sync::{broadcast, mpsc, oneshot, watch};
 
-use crate::config::{SessionConfig, SessionRole};
+use crate::config::SessionConfig;
+use crate::error::FixError;
 use crate::logging::Logger;
 use crate::message::{Message, Field, field, MessagePool};
-use crate::transport::{Transport, TransportConfig};
+use crate::message::validator::Rejection;
 use crate::Result;
-use crate::store::MessageStore;
-use chrono;
+use crate::store::{MessageStore, MessageStoreBackend};
 
 pub mod state;
-
+pub mod latency;
+pub mod heartbeat;
+pub mod persist;
+pub mod id;
+pub mod registry;
+pub mod sender;
+mod actor;
+
+pub use self::id::SessionID;
+pub use self::registry::{SessionEvent, SessionRegistry};
+pub use self::sender::SessionSender;
+use self::actor::{Command, SessionActor};
+use self::latency::{LatencyHistogram, LatencySnapshot};
+
+/// Handle to a FIX session.
+///
+/// Clones share the same session. The state and connection are owned by a [`SessionActor`]
+/// task, spawned the first time the session is driven; handles talk to it through commands and
+/// observe its state through a watch channel.
 #[derive(Clone)]
 pub struct Session {
     pub config: SessionConfig,
-    state: Arc<Mutex<state::SessionState>>,
-    transport: Arc<Mutex<Option<Transport>>>,
-    logger: Arc<Logger>,
-    store: Arc<MessageStore>,
-    message_pool: Arc<MessagePool>,
+    id: SessionID,
+    state: watch::Receiver<state::SessionState>,
+    commands: mpsc::Sender<Command>,
+    // Taken when the actor task is spawned
+    actor: Arc<std::sync::Mutex<Option<SessionActor>>>,
+    store: Arc<dyn MessageStoreBackend>,
+    latency: Arc<LatencyHistogram>,
 }
 
 impl Session {
+    /// Create a session over the backend selected by `config.store`, failing if it cannot be opened.
+    /// State persisted by an earlier run of the session is reloaded.
     pub fn new(
         config: SessionConfig,
         logger: Arc<Logger>,
         store: Arc<MessageStore>,
         message_pool: Arc<MessagePool>,
-    ) -> Self {
-        let session_id = format!("{}_{}", config.sender_comp_id, config.target_comp_id);
-        let store_dir = PathBuf::from("store/sessions");
-
-        let state = state::SessionState::with_config(
-            &session_id,
-            store_dir,
-            10,  // logon timeout
-            config.heart_bt_int as u64,
-            2,   // test request delay
+        events: broadcast::Sender<SessionEvent>,
+    ) -> Result<Self> {
+        let id = SessionID::from_config(&config);
+        let store_dir = store.session_dir(&id);
+        let store = store.open(&id, config.store)?;
+        // Picked up as Recovering if a previous run left its state behind
+        let state = state::SessionState::load_or_create(&id, store_dir)?
+            .with_timeouts(config.logon_timeout, config.heart_bt_int as u64)
+            .with_sync_policy(config.state_sync);
+        let (published, state_rx) = watch::channel(state.clone());
+        let (commands, command_rx) = mpsc::channel(sender::OUTBOUND_CAPACITY);
+        let latency = Arc::new(LatencyHistogram::new());
+
+        let actor = SessionActor::new(
+            config.clone(),
+            id.clone(),
+            state,
+            published,
+            command_rx,
+            logger,
+            Arc::clone(&store),
+            message_pool,
+            Arc::clone(&latency),
+            events,
         );
 
-        Session {
+        Ok(Session {
             config,
-            state: Arc::new(Mutex::new(state)),
-            transport: Arc::new(Mutex::new(None)),
-            logger,
+            id,
+            state: state_rx,
+            commands,
+            actor: Arc::new(std::sync::Mutex::new(Some(actor))),
             store,
-            message_pool,
-        }
+            latency,
+        })
     }
 
+    /// Connect to the counterparty and send a Logon
     pub async fn start(&self) -> Result<()> {
-        let mut state = self.state.lock().await;
-        state.set_status(state::Status::Connecting);
-        drop(state);
-
-        // Connect to counterparty
-        let stream = TcpStream::connect(&self.config.target_addr).await?;
-        let transport = Transport::new(stream);
-
-        {
-            let mut transport_guard = self.transport.lock().await;
-            *transport_guard = Some(transport);
-        }
+        self.request(Command::Connect).await
+    }
 
-        // Initiate logon sequence
-        self.initiate_logon().await?;
+    /// Log out and wait for the counterparty to confirm, see [`Session::logout`]
+    pub async fn stop(&self) -> Result<()> {
+        self.logout().await
+    }
 
-        // Start heartbeat monitoring
-        self.start_heartbeat_monitor().await;
+    pub async fn recover(&self) -> Result<()> {
+        // Reload persisted messages from the store
+        self.store.refresh()?;
+        self.request(Command::Recover).await
+    }
 
-        // Start message processing
-        self.start_message_processor().await;
+    pub async fn disconnect(&self) -> Result<()> {
+        self.request(Command::Disconnect).await
+    }
 
-        Ok(())
+    /// Send Logout and wait up to `logout_timeout` seconds for the counterparty's Logout
+    /// before closing the connection
+    pub async fn logout(&self) -> Result<()> {
+        self.request(|done| Command::Logout(None, done)).await
     }
 
-    async fn initiate_logon(&self) -> Result<()> {
-        let mut state = self.state.lock().await;
-        state.set_status(state::Status::InitiateLogon);
-        state.update_send_time();
+    /// Like [`Session::logout`], giving the reason in the Logout's Text field
+    pub async fn logout_with_text(&self, text: &str) -> Result<()> {
+        self.request(|done| Command::Logout(Some(text.to_string()), done)).await
+    }
 
-        let logon = self.create_logon_message().await;
-        drop(state);
+    // Hand a command to the actor, spawning it on first use, and wait for its answer
+    async fn request(&self, command: impl FnOnce(oneshot::Sender<Result<()>>) -> Command) -> Result<()> {
+        self.spawn_actor();
+        let (done, answer) = oneshot::channel();
+        self.commands.send(command(done)).await
+            .map_err(|_| FixError::SessionError(format!("Session {} has stopped", self.id)))?;
+        answer.await
+            .map_err(|_| FixError::SessionError(format!("Session {} has stopped", self.id)))?
+    }
 
-        if let Some(transport) = self.transport.lock().await.as_mut() {
-            transport.send(&logon).await?;
+    fn spawn_actor(&self) {
+        if let Some(actor) = self.actor.lock().unwrap().take() {
+            tokio::spawn(actor.run());
         }
-
-        Ok(())
-    }
-
-    async fn start_heartbeat_monitor(&self) {
-        let transport_clone = Arc::clone(&self.transport);
-        let state_clone = Arc::clone(&self.state);
-        let config_clone = self.config.clone();
-        let logger_clone = Arc::clone(&self.logger);
-        let message_pool_clone = Arc::clone(&self.message_pool);
-
-        tokio::spawn(async move {
-            let heartbeat_interval = Duration::from_secs(config_clone.heart_bt_int as u64);
-            let mut interval = time::interval(heartbeat_interval);
-
-            loop {
-                interval.tick().await;
-                let mut state = state_clone.lock().await;
-
-                if state.should_disconnect() {
-                    state.set_status(state::Status::Disconnecting);
-                    drop(state);
-                    break;
-                }
-
-                // Check if we need to send a test request
-                if state.should_send_test_request() {
-                    state.increment_test_request_counter();
-                    drop(state);
-
-                    // Get test request message from pool
-                    let mut test_request = message_pool_clone.get_message(field::values::TEST_REQUEST).await;
-                    if let Err(e) = test_request.set_field(Field::new(field::TEST_REQ_ID,
-                        format!("TEST_REQ_{}", chrono::Utc::now().timestamp()))) {
-                            logger_clone.log_event("ERROR", &format!("Failed to set TEST_REQ_ID: {}", e)).ok();
-                            break;
-                    }
-
-                    // Send test request
-                    if let Some(transport) = transport_clone.lock().await.as_mut() {
-                        if let Err(e) = transport.send(&test_request).await {
-                            logger_clone.log_event("ERROR", &format!("Failed to send test request: {}", e)).ok();
-                            break;
-                        }
-                    }
-
-                    // Return message to pool
-                    message_pool_clone.return_message(test_request).await;
-                }
-
-                // Send regular heartbeat if connected
-                let state = state_clone.lock().await;
-                if *state.status() == state::Status::Connected {
-                    drop(state);
-
-                    // Get heartbeat message from pool
-                    let heartbeat = message_pool_clone.get_message(field::values::HEARTBEAT).await;
-                    if let Some(transport) = transport_clone.lock().await.as_mut() {
-                        if let Err(e) = transport.send(&heartbeat).await {
-                            logger_clone.log_event("ERROR", &format!("Failed to send heartbeat: {}", e)).ok();
-                            break;
-                        }
-                    }
-                    // Return message to pool
-                    message_pool_clone.return_message(heartbeat).await;
-                }
-            }
-        });
     }
 
-    async fn start_message_processor(&self) {
-        let transport_clone = Arc::clone(&self.transport);
-        let state_clone = Arc::clone(&self.state);
-        let logger_clone = Arc::clone(&self.logger);
-        let store_clone = Arc::clone(&self.store);
-        let config_clone = self.config.clone();
-        let message_pool_clone = Arc::clone(&self.message_pool);
-
-        tokio::spawn(async move {
-            loop {
-                let state = state_clone.lock().await;
-                if *state.status() == state::Status::Disconnected {
-                    break;
-                }
-                drop(state);
-
-                if let Some(transport) = transport_clone.lock().await.as_mut() {
-                    match transport.receive().await {
-                        Ok(Some(msg)) => {
-                            let session_id = format!("{}_{}", config_clone.sender_comp_id, config_clone.target_comp_id);
-
-                            // Store incoming message
-                            if let Some(seq_num) = msg.get_field(field::MSG_SEQ_NUM) {
-                                if let Ok(seq_num) = seq_num.value().parse::<i32>() {
-                                    let mut state = state_clone.lock().await;
-
-                                    // Check for sequence gaps
-                                    if seq_num > state.next_incoming_seq() {
-                                        // Get resend request from pool
-                                        let mut resend_request = message_pool_clone.get_message(field::values::RESEND_REQUEST).await;
-                                        match resend_request.set_field(Field::new(field::BEGIN_SEQ_NO, 
-                                            state.next_incoming_seq().to_string())) {
-                                                Ok(_) => {
-                                                    match resend_request.set_field(Field::new(field::END_SEQ_NO, seq_num.to_string())) {
-                                                        Ok(_) => {
-                                                            if let Err(e) = transport.send(&resend_request).await {
-                                                                logger_clone.log_event("ERROR", &format!("Failed to send resend request: {}", e)).ok();
-                                                            }
-                                                        },
-                                                        Err(e) => {
-                                                            logger_clone.log_event("ERROR", &format!("Failed to set END_SEQ_NO: {}", e)).ok();
-                                                        }
-                                                    }
-                                                },
-                                                Err(e) => {
-                                                    logger_clone.log_event("ERROR", &format!("Failed to set BEGIN_SEQ_NO: {}", e)).ok();
-                                                }
-                                        }
-                                        message_pool_clone.return_message(resend_request).await;
-                                        continue;
-                                    }
-
-                                    // Store message
-                                    if let Err(e) = store_clone.store_message(&session_id, seq_num, msg.clone()).await {
-                                        logger_clone.log_event("ERROR", &format!("Failed to store message: {}", e)).ok();
-                                    }
-
-                                    state.increment_incoming_seq();
-                                }
-                            }
-
-                            // Process message based on type
-                            match msg.msg_type() {
-                                field::values::LOGON => {
-                                    let mut state = state_clone.lock().await;
-                                    if *state.status() == state::Status::InitiateLogon {
-                                        state.set_status(state::Status::Connected);
-                                        state.reset_test_request_counter();
-                                    }
-                                },
-                                field::values::TEST_REQUEST => {
-                                    let mut heartbeat = message_pool_clone.get_message(field::values::HEARTBEAT).await;
-                                    if let Some(test_req_id) = msg.get_field(field::TEST_REQ_ID) {
-                                        if let Err(e) = heartbeat.set_field(Field::new(field::TEST_REQ_ID, test_req_id.value())) {
-                                            logger_clone.log_event("ERROR", &format!("Failed to set TEST_REQ_ID in heartbeat: {}", e)).ok();
-                                        } else if let Err(e) = transport.send(&heartbeat).await {
-                                            logger_clone.log_event("ERROR", &format!("Failed to send heartbeat: {}", e)).ok();
-                                        }
-                                    }
-                                    message_pool_clone.return_message(heartbeat).await;
-                                },
-                                field::values::HEARTBEAT => {
-                                    let mut state = state_clone.lock().await;
-                                    state.reset_test_request_counter();
-                                },
-                                field::values::LOGOUT => {
-                                    let mut state = state_clone.lock().await;
-                                    state.set_status(state::Status::Disconnected);
-                                    break;
-                                },
-                                _ => {
-                                    logger_clone.log_event("INFO", &format!("Received message type: {}", msg.msg_type())).ok();
-                                }
-                            }
-                        },
-                        Ok(None) => {
-                            time::sleep(Duration::from_millis(100)).await;
-                        },
-                        Err(e) => {
-                            logger_clone.log_event("ERROR", &format!("Error receiving message: {}", e)).ok();
-                            let mut state = state_clone.lock().await;
-                            state.set_status(state::Status::Error);
-                            break;
-                        }
-                    }
-                }
+    /// Stamp the session identity (version, CompIDs and any SubID/LocationID) on an outbound message
+    fn set_session_header(msg: &mut Message, config: &SessionConfig) {
+        let _ = msg.set_field(Field::new(field::BEGIN_STRING, &config.begin_string));
+        let _ = msg.set_field(Field::new(field::SENDER_COMP_ID, &config.sender_comp_id));
+        let _ = msg.set_field(Field::new(field::TARGET_COMP_ID, &config.target_comp_id));
+        let qualifiers = [
+            (field::SENDER_SUB_ID, &config.sender_sub_id),
+            (field::TARGET_SUB_ID, &config.target_sub_id),
+            (field::SENDER_LOCATION_ID, &config.sender_location_id),
+            (field::TARGET_LOCATION_ID, &config.target_location_id),
+        ];
+        for (tag, value) in qualifiers {
+            if let Some(value) = value {
+                let _ = msg.set_field(Field::new(tag, value));
             }
-        });
-    }
-
-    pub async fn stop(&self) -> Result<()> {
-        let mut state = self.state.lock().await;
-        state.set_status(state::Status::Disconnecting);
-
-        // Send logout message if connected
-        if let Some(mut transport) = self.transport.lock().await.take() {
-            let logout = self.create_logout_message().await;
-            transport.send(&logout).await?;
         }
-
-        state.set_status(state::Status::Disconnected);
-        Ok(())
     }
 
-    async fn create_logon_message(&self) -> Message {
-        let mut msg = self.message_pool.get_message(field::values::LOGON).await;
-        let _ = msg.set_field(Field::new(field::BEGIN_STRING, &self.config.begin_string));
-        let _ = msg.set_field(Field::new(field::SENDER_COMP_ID, &self.config.sender_comp_id));
-        let _ = msg.set_field(Field::new(field::TARGET_COMP_ID, &self.config.target_comp_id));
-        let _ = msg.set_field(Field::new(field::HEART_BT_INT, self.config.heart_bt_int.to_string()));
+    async fn logon_message(message_pool: &MessagePool, config: &SessionConfig) -> Message {
+        let mut msg = message_pool.get_message(field::values::LOGON).await;
+        Self::set_session_header(&mut msg, config);
+        let _ = msg.set_field(Field::new(field::HEART_BT_INT, config.heart_bt_int.to_string()));
         let _ = msg.set_field(Field::new(field::ENCRYPT_METHOD, "0")); // No encryption
-        let _ = msg.set_field(Field::new(field::RESET_SEQ_NUM_FLAG, "Y")); // Reset sequence numbers
+        if config.reset_on_logon {
+            let _ = msg.set_field(Field::new(field::RESET_SEQ_NUM_FLAG, "Y")); // Both sides start over at 1
+        }
         msg
     }
 
-    async fn create_logout_message(&self) -> Message {
-        let mut msg = self.message_pool.get_message(field::values::LOGOUT).await;
-        let _ = msg.set_field(Field::new(field::BEGIN_STRING, &self.config.begin_string));
-        let _ = msg.set_field(Field::new(field::SENDER_COMP_ID, &self.config.sender_comp_id));
-        let _ = msg.set_field(Field::new(field::TARGET_COMP_ID, &self.config.target_comp_id));
+    async fn logout_message(message_pool: &MessagePool, config: &SessionConfig) -> Message {
+        let mut msg = message_pool.get_message(field::values::LOGOUT).await;
+        Self::set_session_header(&mut msg, config);
         msg
     }
 
-    pub async fn recover(&self) -> Result<()> {
-        let mut state = self.state.lock().await;
-
-        // Load persisted messages from store
-        let session_id = format!("{}_{}", self.config.sender_comp_id, self.config.target_comp_id);
-        self.store.load_messages(&session_id).await?;
-
-        // Reset sequence numbers if configured
-        if self.config.reset_on_disconnect {
-            state.reset_sequence_numbers();
-            self.store.reset_sequence_numbers(&session_id).await?;
+    /// Build a session level Reject (35=3) answering `message`. Not taken from the pool as
+    /// the optional reference fields would leak between rejects.
+    fn create_reject_message(config: &SessionConfig, message: &Message, rejection: &Rejection) -> Message {
+        let mut msg = Message::new(field::values::REJECT);
+        Self::set_session_header(&mut msg, config);
+        if let Some(seq_num) = message.get_field(field::MSG_SEQ_NUM) {
+            let _ = msg.set_field(Field::new(field::REF_SEQ_NUM, seq_num.value()));
         }
-
-        // Attempt to reconnect
-        if let Some(transport) = self.transport.lock().await.as_mut() {
-            let logout = self.create_logout_message().await;
-            let _ = transport.send(&logout).await;  // Best effort logout
+        let _ = msg.set_field(Field::new(field::REF_MSG_TYPE, message.msg_type()));
+        if let Some(tag) = rejection.ref_tag {
+            let _ = msg.set_field(Field::new(field::REF_TAG_ID, tag.to_string()));
         }
+        let _ = msg.set_field(Field::new(field::SESSION_REJECT_REASON, rejection.reason.code().to_string()));
+        let _ = msg.set_field(Field::new(field::TEXT, &rejection.text));
+        msg
+    }
 
-        // Clear existing transport
-        *self.transport.lock().await = None;
-
-        // Update state for recovery
-        state.set_status(state::Status::Recovering);
-
-        // Attempt to establish new connection
-        match TcpStream::connect(&self.config.target_addr).await {
-            Ok(stream) => {
-                let transport = Transport::new(stream);
-                *self.transport.lock().await = Some(transport);
-
-                // Initiate new logon sequence
-                drop(state);
-                self.initiate_logon().await?;
-
-                // Start heartbeat monitoring and message processing
-                self.start_heartbeat_monitor().await;
-                self.start_message_processor().await;
 
-                Ok(())
-            },
-            Err(e) => {
-                state.set_status(state::Status::Error);
-                Err(e.into())
-            }
-        }
+    pub async fn is_connected(&self) -> bool {
+        self.state.borrow().status().is_logged_on()
     }
 
-    pub async fn disconnect(&self) -> Result<()> {
-        let mut state = self.state.lock().await;
-        state.set_status(state::Status::Disconnecting);
+    pub async fn get_state(&self) -> Result<state::SessionState> {
+        Ok(self.state.borrow().clone())
+    }
 
-        if let Some(transport) = self.transport.lock().await.as_mut() {
-            let logout = self.create_logout_message().await;
-            let _ = transport.send(&logout).await;  // Best effort send
-        }
+    /// Inbound latency measured from SendingTime since the session was created
+    pub fn latency(&self) -> LatencySnapshot {
+        self.latency.snapshot()
+    }
 
-        *self.transport.lock().await = None;
-        state.set_status(state::Status::Disconnected);
-        Ok(())
+    pub fn session_id(&self) -> &SessionID {
+        &self.id
     }
 
-    pub async fn logout(&self) -> Result<()> {
-        let mut state = self.state.lock().await;
-        state.set_status(state::Status::Disconnecting);
+    /// The session's message store, as chosen by [`SessionConfig::store`]
+    pub fn store(&self) -> &Arc<dyn MessageStoreBackend> {
+        &self.store
+    }
 
-        // Send logout message
-        if let Some(transport) = self.transport.lock().await.as_mut() {
-            let logout = self.create_logout_message().await;
-            transport.send(&logout).await?;
-        }
+    /// MsgSeqNum of the next message we send, kept by the store alone
+    pub fn next_sender_seq(&self) -> Result<u64> {
+        self.store.next_sender_seq()
+    }
 
-        state.set_status(state::Status::Disconnected);
-        Ok(())
+    /// MsgSeqNum expected on the next message from the counterparty, kept by the store alone
+    pub fn next_target_seq(&self) -> Result<u64> {
+        self.store.next_target_seq()
     }
 
-    pub async fn is_connected(&self) -> bool {
-        let state = self.state.lock().await;
-        *state.status() == state::Status::Connected
+    /// Set the MsgSeqNum of the next message we send, for manual intervention. Applied between
+    /// the messages the session is handling, so none of them is numbered twice.
+    pub async fn set_next_sender_seq(&self, seq_num: u64) -> Result<()> {
+        self.request(|done| Command::SetNextSenderSeq(seq_num, done)).await
     }
 
-    pub async fn get_state(&self) -> Result<state::SessionState> {
-        Ok(self.state.lock().await.clone())
+    /// Set the MsgSeqNum expected on the next message from the counterparty, for manual
+    /// intervention
+    pub async fn set_next_target_seq(&self, seq_num: u64) -> Result<()> {
+        self.request(|done| Command::SetNextTargetSeq(seq_num, done)).await
     }
 
-    /// Get the session ID in the format "{sender_comp_id}_{target_comp_id}"
-    pub fn session_id(&self) -> String {
-        format!("{}_{}", self.config.sender_comp_id, self.config.target_comp_id)
+    /// Handle for queueing application messages on this session from other tasks
+    pub fn sender(&self) -> SessionSender {
+        SessionSender::new(
+            self.id.clone(),
+            self.commands.clone(),
+            self.state.clone(),
+            self.config.queue_while_disconnected,
+        )
     }
 }
 
@@ -397,22 +245,25 @@ impl Session {
 mod tests {
     use super::*;
     use std::path::PathBuf;
-    use crate::config::{LogConfig, SessionRole};
-    use crate::transport::TransportConfig;
+    use futures::SinkExt;
+    use tokio::net::TcpListener;
+    use tokio::time::{self, Duration, Instant};
+    use crate::config::{LogConfig, RetentionPolicy, SeqNumLimit, SeqNumRollover};
+    use crate::message::validator::MessageValidator;
+    use crate::store::Direction;
+    use crate::transport::{FixReader, FixWriter, Transport, TransportConfig};
 
     #[tokio::test]
     async fn test_session_lifecycle() {
         let config = SessionConfig {
-            begin_string: "FIX.4.2".to_string(),
             sender_comp_id: "SENDER".to_string(),
             target_comp_id: "TARGET".to_string(),
             target_addr: "127.0.0.1:0".to_string(),
-            heart_bt_int: 30,
             reset_on_logon: true,
             reset_on_logout: true,
             reset_on_disconnect: true,
             transport_config: Some(TransportConfig::default()),
-            role: SessionRole::Initiator,
+            ..Default::default()
         };
 
         let log_config = LogConfig {
@@ -423,20 +274,680 @@ mod tests {
         };
 
         let logger = Arc::new(Logger::new(&log_config));
-        let store = Arc::new(MessageStore::new());
+        let (_temp_dir, store) = temp_store();
         let message_pool = Arc::new(MessagePool::new());
-        let session = Session::new(config, logger, store, message_pool);
+        let session = Session::new(config, logger, store, Arc::clone(&message_pool), broadcast::channel(16).0).unwrap();
 
         // Test message creation
-        let logon = session.create_logon_message().await;
+        let logon = Session::logon_message(&message_pool, &session.config).await;
         assert_eq!(logon.msg_type(), field::values::LOGON);
         assert!(logon.get_field(field::HEART_BT_INT).is_some());
+        assert_eq!(logon.get_field(field::RESET_SEQ_NUM_FLAG).unwrap().value(), "Y");
 
-        let logout = session.create_logout_message().await;
+        let logout = Session::logout_message(&message_pool, &session.config).await;
         assert_eq!(logout.msg_type(), field::values::LOGOUT);
 
         // Test session state without actual connection
         let state = session.get_state().await.unwrap();
         assert_eq!(*state.status(), state::Status::Created);
+
+        // Inbound messages from the wrong counterparty are answered with a CompID reject
+        let mut inbound = Message::new(field::values::HEARTBEAT);
+        let _ = inbound.set_field(Field::new(field::SENDER_COMP_ID, "OTHER"));
+        let _ = inbound.set_field(Field::new(field::TARGET_COMP_ID, "SENDER"));
+        let _ = inbound.set_field(Field::new(field::MSG_SEQ_NUM, "7"));
+        let rejection = MessageValidator::validate_inbound(&inbound, &session.config).unwrap_err();
+        let reject = Session::create_reject_message(&session.config, &inbound, &rejection);
+        assert_eq!(reject.msg_type(), field::values::REJECT);
+        assert_eq!(reject.get_field(field::REF_SEQ_NUM).unwrap().value(), "7");
+        assert_eq!(reject.get_field(field::REF_TAG_ID).unwrap().value(), "49");
+        assert_eq!(reject.get_field(field::SESSION_REJECT_REASON).unwrap().value(), "9");
+    }
+
+    // Initiator config for a counterparty listening on `target_addr`
+    fn test_config(sender: &str, target: &str, target_addr: String) -> SessionConfig {
+        SessionConfig {
+            sender_comp_id: sender.to_string(),
+            target_comp_id: target.to_string(),
+            target_addr,
+            reset_on_logon: true,
+            reset_on_logout: true,
+            reset_on_disconnect: true,
+            ..Default::default()
+        }
+    }
+
+    // A store in its own temporary directory, removed with the returned `TempDir`
+    fn temp_store() -> (tempfile::TempDir, Arc<MessageStore>) {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let store = Arc::new(MessageStore::with_dir(temp_dir.path().to_path_buf()));
+        (temp_dir, store)
+    }
+
+    fn test_session(config: SessionConfig, store: Arc<MessageStore>) -> Session {
+        let logger = Arc::new(Logger::new(&LogConfig {
+            log_directory: PathBuf::from("/tmp"),
+            log_level: "INFO".to_string(),
+            log_events: true,
+            log_messages: true,
+        }));
+        Session::new(config, logger, store, Arc::new(MessagePool::new()), broadcast::channel(16).0).unwrap()
+    }
+
+    // Counterparty side: accept the session's connection and answer its Logon
+    async fn accept_logon(listener: &TcpListener, config: &SessionConfig) -> (FixReader, FixWriter) {
+        let (stream, _) = listener.accept().await.unwrap();
+        let (mut reader, mut writer) = Transport::new(stream).split().unwrap();
+        let logon = reader.receive().await.unwrap().unwrap();
+        assert_eq!(logon.msg_type(), field::values::LOGON);
+
+        let mut reply = Message::new(field::values::LOGON);
+        let _ = reply.set_field(Field::new(field::SENDER_COMP_ID, &config.target_comp_id));
+        let _ = reply.set_field(Field::new(field::TARGET_COMP_ID, &config.sender_comp_id));
+        let _ = reply.set_field(Field::new(field::HEART_BT_INT, "30"));
+        let _ = reply.set_field(Field::new(field::ENCRYPT_METHOD, "0"));
+        writer.send(reply).await.unwrap();
+        (reader, writer)
+    }
+
+    async fn wait_for_status(session: &Session, status: state::Status) {
+        let mut state = session.state.clone();
+        time::timeout(Duration::from_secs(5), state.wait_for(|state| *state.status() == status))
+            .await.unwrap().unwrap();
+    }
+
+    fn counterparty_message(msg_type: &str, config: &SessionConfig, seq_num: u64) -> Message {
+        let mut msg = Message::new(msg_type);
+        let _ = msg.set_field(Field::new(field::SENDER_COMP_ID, &config.target_comp_id));
+        let _ = msg.set_field(Field::new(field::TARGET_COMP_ID, &config.sender_comp_id));
+        let _ = msg.set_field(Field::new(field::MSG_SEQ_NUM, seq_num.to_string()));
+        msg
+    }
+
+    #[test]
+    fn test_store_open_failure() {
+        let config = test_config("SENDER_OPEN", "TARGET_OPEN", "127.0.0.1:0".to_string());
+        let (_temp_dir, store) = temp_store();
+        let _session = test_session(config.clone(), Arc::clone(&store));
+
+        // The store is locked by the first session, so the second cannot be created
+        let logger = Arc::new(Logger::new(&LogConfig {
+            log_directory: PathBuf::from("/tmp"),
+            log_level: "INFO".to_string(),
+            log_events: true,
+            log_messages: true,
+        }));
+        let result = Session::new(config, logger, store, Arc::new(MessagePool::new()), broadcast::channel(16).0);
+        assert!(matches!(result, Err(FixError::StoreError(_))));
+    }
+
+    #[tokio::test]
+    async fn test_session_reloads_state() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let config = test_config("SENDER_RELOAD", "TARGET_RELOAD", listener.local_addr().unwrap().to_string());
+        let (_temp_dir, store) = temp_store();
+
+        // A previous run stopped while connected
+        let id = SessionID::from_config(&config);
+        let mut previous = state::SessionState::new(&id, store.session_dir(&id));
+        for next in [state::Status::Connecting, state::Status::InitiateLogon, state::Status::LogonReceived, state::Status::Connected] {
+            previous.transition(next).unwrap();
+        }
+        previous.flush().await.unwrap();
+        drop(previous);
+
+        // The new session starts out recovering and can connect from there
+        let session = test_session(config.clone(), store);
+        assert_eq!(*session.get_state().await.unwrap().status(), state::Status::Recovering);
+        session.start().await.unwrap();
+        let (_reader, _writer) = accept_logon(&listener, &config).await;
+        wait_for_status(&session, state::Status::Connected).await;
+    }
+
+    #[tokio::test]
+    async fn test_session_sender() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut config = test_config("SENDER_OUT", "TARGET_OUT", listener.local_addr().unwrap().to_string());
+        let (_temp_dir, store) = temp_store();
+
+        // Not logged on
+        let session = test_session(config.clone(), Arc::clone(&store));
+        let err = session.sender().send(Message::new("D")).await.unwrap_err();
+        assert!(matches!(err, FixError::NotLoggedOn(_)));
+        drop(session);  // Releases the session's store
+
+        // Held until logon when configured to queue
+        config.queue_while_disconnected = true;
+        let session = test_session(config.clone(), Arc::clone(&store));
+        let sender = session.sender();
+        let queued = tokio::spawn({
+            let sender = sender.clone();
+            async move { sender.send(Message::new("D")).await }
+        });
+        time::sleep(Duration::from_millis(50)).await;
+        assert!(!queued.is_finished());
+
+        session.start().await.unwrap();
+        let (mut reader, mut writer) = accept_logon(&listener, &config).await;
+        assert_eq!(queued.await.unwrap().unwrap(), 2);
+
+        // Sent while the session is waiting on the next read, sequenced and stored in order
+        assert_eq!(sender.send(Message::new("F")).await.unwrap(), 3);
+        for (msg_type, seq_num) in [("D", "2"), ("F", "3")] {
+            let msg = reader.receive().await.unwrap().unwrap();
+            assert_eq!(msg.msg_type(), msg_type);
+            assert_eq!(msg.get_field(field::MSG_SEQ_NUM).unwrap().value(), seq_num);
+            assert_eq!(msg.get_field(field::SENDER_COMP_ID).unwrap().value(), "SENDER_OUT");
+        }
+        let stored = session.store().fetch_range(3, 3).unwrap();
+        assert_eq!(Message::from_bytes(&stored[0].1).unwrap().msg_type(), "F");
+        assert_eq!(session.store().next_sender_seq().unwrap(), 4);
+        assert!(session.is_connected().await);
+
+        let stop = tokio::spawn({
+            let session = session.clone();
+            async move { session.stop().await }
+        });
+        assert_eq!(reader.receive().await.unwrap().unwrap().msg_type(), field::values::LOGOUT);
+        writer.send(counterparty_message(field::values::LOGOUT, &config, 2)).await.unwrap();
+        stop.await.unwrap().unwrap();
+        assert!(!session.is_connected().await);
+    }
+
+    #[tokio::test]
+    async fn test_session_logout_handshake() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut config = test_config("SENDER_LOGOUT", "TARGET_LOGOUT", listener.local_addr().unwrap().to_string());
+        config.logout_timeout = 1;
+        let (_temp_dir, store) = temp_store();
+        let session = test_session(config.clone(), store);
+
+        // We log out: the connection stays open until the counterparty answers
+        session.start().await.unwrap();
+        let (mut reader, mut writer) = accept_logon(&listener, &config).await;
+        let logout = tokio::spawn({
+            let session = session.clone();
+            async move { session.logout_with_text("End of day").await }
+        });
+        let sent = reader.receive().await.unwrap().unwrap();
+        assert_eq!(sent.msg_type(), field::values::LOGOUT);
+        assert_eq!(sent.get_field(field::TEXT).unwrap().value(), "End of day");
+        time::sleep(Duration::from_millis(100)).await;
+        assert!(!logout.is_finished());
+        assert_eq!(*session.get_state().await.unwrap().status(), state::Status::Disconnecting);
+
+        writer.send(counterparty_message(field::values::LOGOUT, &config, 2)).await.unwrap();
+        logout.await.unwrap().unwrap();
+        assert!(reader.receive().await.unwrap().is_none());
+        let state = session.get_state().await.unwrap();
+        assert_eq!(*state.status(), state::Status::Disconnected);
+        assert_eq!(session.next_sender_seq().unwrap(), 1);  // reset_on_logout
+
+        // No reply: the connection is closed after logout_timeout
+        session.start().await.unwrap();
+        let (mut reader, _writer) = accept_logon(&listener, &config).await;
+        let started = Instant::now();
+        session.logout().await.unwrap();
+        assert!(started.elapsed() >= Duration::from_secs(1));
+        assert_eq!(reader.receive().await.unwrap().unwrap().msg_type(), field::values::LOGOUT);
+        assert!(reader.receive().await.unwrap().is_none());
+
+        // The counterparty logs out: we confirm and close
+        session.start().await.unwrap();
+        let (mut reader, mut writer) = accept_logon(&listener, &config).await;
+        writer.send(counterparty_message(field::values::LOGOUT, &config, 2)).await.unwrap();
+        let reply = reader.receive().await.unwrap().unwrap();
+        assert_eq!(reply.msg_type(), field::values::LOGOUT);
+        assert!(reader.receive().await.unwrap().is_none());
+        assert!(!session.is_connected().await);
+
+        // Disconnected while waiting for the reply: the logout fails rather than hanging
+        session.start().await.unwrap();
+        let (mut reader, _writer) = accept_logon(&listener, &config).await;
+        wait_for_status(&session, state::Status::Connected).await;
+        let logout = tokio::spawn({
+            let session = session.clone();
+            async move { session.logout().await }
+        });
+        assert_eq!(reader.receive().await.unwrap().unwrap().msg_type(), field::values::LOGOUT);
+        session.disconnect().await.unwrap();
+        let result = time::timeout(Duration::from_millis(500), logout).await.unwrap().unwrap();
+        assert!(matches!(result, Err(FixError::SessionError(_))));
+    }
+
+    #[tokio::test]
+    async fn test_session_compacts_on_logout() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut config = test_config("SENDER_COMPACT", "TARGET_COMPACT", listener.local_addr().unwrap().to_string());
+        config.reset_on_logout = false;
+        config.logout_timeout = 1;
+        let temp_dir = tempfile::tempdir().unwrap();
+        let retention = RetentionPolicy { max_messages: Some(0), ..RetentionPolicy::default() };
+        let store = Arc::new(MessageStore::with_dir(temp_dir.path().to_path_buf()).with_retention(retention));
+        let session = test_session(config.clone(), store);
+
+        // An unanswered Logout may leave gaps the counterparty asks to fill later
+        session.start().await.unwrap();
+        let (mut reader, _writer) = accept_logon(&listener, &config).await;
+        wait_for_status(&session, state::Status::Connected).await;
+        assert_eq!(session.sender().send(Message::new("D")).await.unwrap(), 2);
+        session.logout().await.unwrap();
+        assert_eq!(reader.receive().await.unwrap().unwrap().msg_type(), "D");
+        assert_eq!(session.store().fetch_range(1, 10).unwrap().len(), 1);
+
+        // Once answered, whatever the retention policy lets go is dropped
+        session.start().await.unwrap();
+        let (mut reader, mut writer) = accept_logon(&listener, &config).await;
+        wait_for_status(&session, state::Status::Connected).await;
+        assert_eq!(session.sender().send(Message::new("D")).await.unwrap(), 2);
+        let stop = tokio::spawn({
+            let session = session.clone();
+            async move { session.stop().await }
+        });
+        assert_eq!(reader.receive().await.unwrap().unwrap().msg_type(), "D");
+        assert_eq!(reader.receive().await.unwrap().unwrap().msg_type(), field::values::LOGOUT);
+        writer.send(counterparty_message(field::values::LOGOUT, &config, 2)).await.unwrap();
+        stop.await.unwrap().unwrap();
+        assert!(session.store().fetch_range(1, 10).unwrap().is_empty());
+        assert_eq!(session.next_sender_seq().unwrap(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_session_heartbeat_timing() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut config = test_config("SENDER_HB", "TARGET_HB", listener.local_addr().unwrap().to_string());
+        config.heart_bt_int = 1;
+        let (_temp_dir, store) = temp_store();
+        let session = test_session(config.clone(), store);
+        session.start().await.unwrap();
+        let (mut reader, _writer) = accept_logon(&listener, &config).await;
+        let logged_on = Instant::now();
+
+        // A silent counterparty gets a Heartbeat after HeartBtInt, then a TestRequest once the
+        // tolerance has passed, and is disconnected when that goes unanswered
+        let heartbeat = reader.receive().await.unwrap().unwrap();
+        assert_eq!(heartbeat.msg_type(), field::values::HEARTBEAT);
+        assert!(logged_on.elapsed() >= Duration::from_millis(900));
+
+        let test_request = reader.receive().await.unwrap().unwrap();
+        assert_eq!(test_request.msg_type(), field::values::TEST_REQUEST);
+        assert!(test_request.get_field(field::TEST_REQ_ID).is_some());
+        assert!(logged_on.elapsed() >= Duration::from_millis(1100));
+
+        // Heartbeats keep going while waiting for the answer
+        while let Some(msg) = reader.receive().await.unwrap() {
+            assert_eq!(msg.msg_type(), field::values::HEARTBEAT);
+        }
+        assert!(logged_on.elapsed() >= Duration::from_millis(2300));
+        assert_eq!(*session.get_state().await.unwrap().status(), state::Status::Disconnected);
+    }
+
+    #[tokio::test]
+    async fn test_session_logon_timeout() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut config = test_config("SENDER_LOGON", "TARGET_LOGON", listener.local_addr().unwrap().to_string());
+        config.logon_timeout = 1;
+        let (_temp_dir, store) = temp_store();
+        let session = test_session(config.clone(), store);
+
+        // The Logon is never answered
+        session.start().await.unwrap();
+        let started = Instant::now();
+        let (stream, _) = listener.accept().await.unwrap();
+        let (mut reader, _writer) = Transport::new(stream).split().unwrap();
+        assert_eq!(reader.receive().await.unwrap().unwrap().msg_type(), field::values::LOGON);
+        assert_eq!(*session.get_state().await.unwrap().status(), state::Status::InitiateLogon);
+
+        assert!(reader.receive().await.unwrap().is_none());
+        assert!(started.elapsed() >= Duration::from_millis(900));
+        assert_eq!(*session.get_state().await.unwrap().status(), state::Status::Disconnected);
+    }
+
+    #[tokio::test]
+    async fn test_session_resend_request_state() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let config = test_config("SENDER_GAP", "TARGET_GAP", listener.local_addr().unwrap().to_string());
+        let (_temp_dir, store) = temp_store();
+        let session = test_session(config.clone(), store);
+        session.start().await.unwrap();
+        let (mut reader, mut writer) = accept_logon(&listener, &config).await;
+
+        // A gap asks for a resend once, however many messages arrive past it
+        writer.send(counterparty_message(field::values::HEARTBEAT, &config, 4)).await.unwrap();
+        writer.send(counterparty_message(field::values::HEARTBEAT, &config, 4)).await.unwrap();
+        let resend = reader.receive().await.unwrap().unwrap();
+        assert_eq!(resend.msg_type(), field::values::RESEND_REQUEST);
+        assert_eq!(resend.get_field(field::BEGIN_SEQ_NO).unwrap().value(), "2");
+        assert_eq!(resend.get_field(field::END_SEQ_NO).unwrap().value(), "4");
+        wait_for_status(&session, state::Status::ResendRequest).await;
+        assert!(session.is_connected().await);
+
+        // Back to normal once the requested range has been received
+        for seq_num in 2..=4 {
+            writer.send(counterparty_message(field::values::HEARTBEAT, &config, seq_num)).await.unwrap();
+        }
+        wait_for_status(&session, state::Status::Connected).await;
+        assert_eq!(session.next_target_seq().unwrap(), 5);
+        assert_eq!(session.sender().send(Message::new("D")).await.unwrap(), 3);
+        assert_eq!(reader.receive().await.unwrap().unwrap().msg_type(), "D");
+    }
+
+    #[tokio::test]
+    async fn test_session_gap_fill_past_gap() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut config = test_config("SENDER_GAP_FILL", "TARGET_GAP_FILL", listener.local_addr().unwrap().to_string());
+        config.store_incoming = true;
+        let (_temp_dir, store) = temp_store();
+        let session = test_session(config.clone(), store);
+        session.start().await.unwrap();
+        let (mut reader, mut writer) = accept_logon(&listener, &config).await;
+        wait_for_status(&session, state::Status::Connected).await;
+
+        // A gap fill past the expected number is not applied, only the gap before it requested
+        let gap_fill = |seq_num: u64, poss_dup: bool| {
+            let mut gap_fill = counterparty_message(field::values::SEQUENCE_RESET, &config, seq_num);
+            let _ = gap_fill.set_field(Field::new(field::GAP_FILL_FLAG, "Y"));
+            let _ = gap_fill.set_field(Field::new(field::NEW_SEQ_NO, "6"));
+            if poss_dup {
+                let _ = gap_fill.set_field(Field::new(field::POSS_DUP_FLAG, "Y"));
+            }
+            gap_fill
+        };
+        writer.send(gap_fill(4, false)).await.unwrap();
+        let resend = reader.receive().await.unwrap().unwrap();
+        assert_eq!(resend.msg_type(), field::values::RESEND_REQUEST);
+        assert_eq!(resend.get_field(field::BEGIN_SEQ_NO).unwrap().value(), "2");
+        assert_eq!(resend.get_field(field::END_SEQ_NO).unwrap().value(), "4");
+        assert_eq!(session.next_target_seq().unwrap(), 2);
+
+        // so the resent messages are taken, then the gap fill again
+        for seq_num in 2..=3 {
+            let mut resent = counterparty_message(field::values::HEARTBEAT, &config, seq_num);
+            let _ = resent.set_field(Field::new(field::POSS_DUP_FLAG, "Y"));
+            writer.send(resent).await.unwrap();
+        }
+        writer.send(gap_fill(4, true)).await.unwrap();
+        wait_for_status(&session, state::Status::Connected).await;
+        assert_eq!(session.next_target_seq().unwrap(), 6);
+        let received = session.store().fetch_messages(Direction::Inbound, 2, 5).unwrap();
+        assert_eq!(received.iter().map(|(seq_num, _)| *seq_num).collect::<Vec<_>>(), vec![2, 3, 4]);
+    }
+
+    #[tokio::test]
+    async fn test_session_gap_on_logon_and_logout() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let config = test_config("SENDER_GAP_LOGON", "TARGET_GAP_LOGON", listener.local_addr().unwrap().to_string());
+        let (_temp_dir, store) = temp_store();
+        let session = test_session(config.clone(), store);
+        session.start().await.unwrap();
+        let (stream, _) = listener.accept().await.unwrap();
+        let (mut reader, mut writer) = Transport::new(stream).split().unwrap();
+        assert_eq!(reader.receive().await.unwrap().unwrap().msg_type(), field::values::LOGON);
+
+        // A Logon past the expected number still logs on, and the gap is asked for after it
+        let mut reply = counterparty_message(field::values::LOGON, &config, 3);
+        let _ = reply.set_field(Field::new(field::HEART_BT_INT, "30"));
+        let _ = reply.set_field(Field::new(field::ENCRYPT_METHOD, "0"));
+        writer.send(reply).await.unwrap();
+        let resend = reader.receive().await.unwrap().unwrap();
+        assert_eq!(resend.msg_type(), field::values::RESEND_REQUEST);
+        assert_eq!(resend.get_field(field::BEGIN_SEQ_NO).unwrap().value(), "1");
+        assert_eq!(resend.get_field(field::END_SEQ_NO).unwrap().value(), "3");
+        wait_for_status(&session, state::Status::ResendRequest).await;
+        assert!(session.is_connected().await);
+        assert_eq!(session.next_target_seq().unwrap(), 1);
+
+        // A Logout past it is answered, with nothing left to ask for once disconnected
+        writer.send(counterparty_message(field::values::LOGOUT, &config, 9)).await.unwrap();
+        assert_eq!(reader.receive().await.unwrap().unwrap().msg_type(), field::values::LOGOUT);
+        assert!(reader.receive().await.unwrap().is_none());
+        wait_for_status(&session, state::Status::Disconnected).await;
+    }
+
+    #[tokio::test]
+    async fn test_session_msg_seq_num_too_low() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let config = test_config("SENDER_LOW", "TARGET_LOW", listener.local_addr().unwrap().to_string());
+        let (_temp_dir, store) = temp_store();
+        let session = test_session(config.clone(), store);
+        session.start().await.unwrap();
+        let (mut reader, mut writer) = accept_logon(&listener, &config).await;
+        wait_for_status(&session, state::Status::Connected).await;
+        writer.send(counterparty_message(field::values::HEARTBEAT, &config, 2)).await.unwrap();
+
+        // A possible duplicate below the expected number is dropped without advancing
+        let mut duplicate = counterparty_message(field::values::TEST_REQUEST, &config, 2);
+        let _ = duplicate.set_field(Field::new(field::POSS_DUP_FLAG, "Y"));
+        let _ = duplicate.set_field(Field::new(field::TEST_REQ_ID, "DUP"));
+        writer.send(duplicate).await.unwrap();
+        let mut test_request = counterparty_message(field::values::TEST_REQUEST, &config, 3);
+        let _ = test_request.set_field(Field::new(field::TEST_REQ_ID, "NEXT"));
+        writer.send(test_request).await.unwrap();
+        let heartbeat = reader.receive().await.unwrap().unwrap();
+        assert_eq!(heartbeat.get_field(field::TEST_REQ_ID).unwrap().value(), "NEXT");
+        assert_eq!(session.next_target_seq().unwrap(), 4);
+
+        // Without PossDupFlag messages were lost, so the session logs out
+        writer.send(counterparty_message(field::values::HEARTBEAT, &config, 2)).await.unwrap();
+        let logout = reader.receive().await.unwrap().unwrap();
+        assert_eq!(logout.msg_type(), field::values::LOGOUT);
+        assert!(logout.get_field(field::TEXT).unwrap().value().starts_with("MsgSeqNum too low"));
+        wait_for_status(&session, state::Status::Disconnected).await;
+        assert_eq!(session.next_target_seq().unwrap(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_session_stores_incoming_apart() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut config = test_config("SENDER_IN", "TARGET_IN", listener.local_addr().unwrap().to_string());
+        config.store = crate::config::StoreKind::Memory;
+        config.store_incoming = true;
+        let (_temp_dir, store) = temp_store();
+        let session = test_session(config.clone(), store);
+        session.start().await.unwrap();
+        let (mut reader, mut writer) = accept_logon(&listener, &config).await;
+
+        // Both sides number from 1 without overwriting each other
+        let mut execution = counterparty_message("8", &config, 2);
+        let _ = execution.set_field(Field::new(field::CL_ORD_ID, "IN2"));
+        writer.send(execution).await.unwrap();
+        let mut test_request = counterparty_message(field::values::TEST_REQUEST, &config, 3);
+        let _ = test_request.set_field(Field::new(field::TEST_REQ_ID, "IN"));
+        writer.send(test_request).await.unwrap();
+        assert_eq!(reader.receive().await.unwrap().unwrap().msg_type(), field::values::HEARTBEAT);
+        assert_eq!(session.sender().send(Message::new("D")).await.unwrap(), 3);
+
+        let inbound = session.store().fetch_messages(Direction::Inbound, 1, 10).unwrap();
+        let types: Vec<_> = inbound.iter().map(|(_, raw)| Message::from_bytes(raw).unwrap().msg_type().to_string()).collect();
+        assert_eq!(types, ["A", "8", "1"]);
+        assert_eq!(inbound.iter().map(|(seq_num, _)| *seq_num).collect::<Vec<_>>(), [1, 2, 3]);
+        let outbound = session.store().fetch_range(1, 10).unwrap();
+        assert_eq!(outbound.len(), 1);
+        assert_eq!(outbound[0].0, 3);
+        assert_eq!(Message::from_bytes(&outbound[0].1).unwrap().msg_type(), "D");
+    }
+
+    #[tokio::test]
+    async fn test_session_sequence_numbers_from_store() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut config = test_config("SENDER_SEQ", "TARGET_SEQ", listener.local_addr().unwrap().to_string());
+        config.reset_on_logon = false;
+        config.reset_on_logout = false;
+        let store = Arc::new(MessageStore::with_dir(temp_dir.path().to_path_buf()));
+
+        // Operations moves the sender number on before the session starts
+        let session = test_session(config.clone(), Arc::clone(&store));
+        assert!(session.set_next_sender_seq(0).await.is_err());
+        session.set_next_sender_seq(10).await.unwrap();
+        session.start().await.unwrap();
+        let (stream, _) = listener.accept().await.unwrap();
+        let (mut reader, mut writer) = Transport::new(stream).split().unwrap();
+        let logon = reader.receive().await.unwrap().unwrap();
+        assert_eq!(logon.get_field(field::MSG_SEQ_NUM).unwrap().value(), "10");
+        assert!(logon.get_field(field::RESET_SEQ_NUM_FLAG).is_none());
+        let mut reply = counterparty_message(field::values::LOGON, &config, 1);
+        let _ = reply.set_field(Field::new(field::HEART_BT_INT, "30"));
+        let _ = reply.set_field(Field::new(field::ENCRYPT_METHOD, "0"));
+        writer.send(reply).await.unwrap();
+        wait_for_status(&session, state::Status::Connected).await;
+
+        // and skips the target number past messages it knows were lost
+        session.set_next_target_seq(5).await.unwrap();
+        let mut test_request = counterparty_message(field::values::TEST_REQUEST, &config, 5);
+        let _ = test_request.set_field(Field::new(field::TEST_REQ_ID, "SEQ"));
+        writer.send(test_request).await.unwrap();
+        let heartbeat = reader.receive().await.unwrap().unwrap();
+        assert_eq!(heartbeat.msg_type(), field::values::HEARTBEAT);
+        assert_eq!(heartbeat.get_field(field::MSG_SEQ_NUM).unwrap().value(), "11");
+        assert_eq!(session.next_target_seq().unwrap(), 6);
+        assert_eq!(session.sender().send(Message::new("D")).await.unwrap(), 12);
+        assert_eq!(reader.receive().await.unwrap().unwrap().msg_type(), "D");
+
+        let stop = tokio::spawn({
+            let session = session.clone();
+            async move { session.stop().await }
+        });
+        assert_eq!(reader.receive().await.unwrap().unwrap().msg_type(), field::values::LOGOUT);
+        writer.send(counterparty_message(field::values::LOGOUT, &config, 6)).await.unwrap();
+        stop.await.unwrap().unwrap();
+        drop(session);
+
+        // A new session picks up where the store left off, once the old actor released it
+        let id = SessionID::from_config(&config);
+        time::timeout(Duration::from_secs(5), async {
+            while store.open(&id, crate::config::StoreKind::File).is_err() {
+                time::sleep(Duration::from_millis(10)).await;
+            }
+        }).await.unwrap();
+        let restarted = test_session(config, store);
+        assert_eq!(restarted.next_sender_seq().unwrap(), 14);
+        assert_eq!(restarted.next_target_seq().unwrap(), 7);
+    }
+
+    #[tokio::test]
+    async fn test_transaction_moves_session_sequence() {
+        let config = test_config("SENDER_TX", "TARGET_TX", "127.0.0.1:0".to_string());
+        let (_temp_dir, store) = temp_store();
+        let session = test_session(config, Arc::clone(&store));
+        let id = session.session_id().clone();
+
+        // A batch committed through the engine's store is in the session's store, which sends
+        // from after it
+        store.begin_transaction(&id).await.unwrap();
+        for seq_num in 1..=3 {
+            store.store_message(&id, seq_num, Message::new("D")).await.unwrap();
+        }
+        assert_eq!(session.store().next_sender_seq().unwrap(), 1);
+        store.commit_transaction(&id).await.unwrap();
+        assert_eq!(session.store().next_sender_seq().unwrap(), 4);
+        assert_eq!(session.store().fetch_range(1, 3).unwrap().len(), 3);
+
+        // as does a message set on its own
+        store.compare_and_set(&id, 4, 0, Message::new("D")).await.unwrap();
+        assert_eq!(session.store().next_sender_seq().unwrap(), 5);
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_session_seq_num_rollover() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut config = test_config("SENDER_ROLL", "TARGET_ROLL", listener.local_addr().unwrap().to_string());
+        config.store = crate::config::StoreKind::Memory;
+        config.seq_num_limit = SeqNumLimit { max_seq_num: 4, action: SeqNumRollover::SequenceReset };
+        let (_temp_dir, store) = temp_store();
+        let session = test_session(config.clone(), store);
+        session.start().await.unwrap();
+        let (mut reader, mut writer) = accept_logon(&listener, &config).await;
+        wait_for_status(&session, state::Status::Connected).await;
+
+        // The last number goes to a SequenceReset, numbering starts over after it
+        assert_eq!(session.sender().send(Message::new("D")).await.unwrap(), 2);
+        assert_eq!(session.sender().send(Message::new("D")).await.unwrap(), 3);
+        assert_eq!(session.sender().send(Message::new("D")).await.unwrap(), 1);
+        for _ in 0..2 {
+            assert_eq!(reader.receive().await.unwrap().unwrap().msg_type(), "D");
+        }
+        let reset = reader.receive().await.unwrap().unwrap();
+        assert_eq!(reset.msg_type(), field::values::SEQUENCE_RESET);
+        assert_eq!(reset.get_field(field::MSG_SEQ_NUM).unwrap().value(), "4");
+        assert_eq!(reset.get_field(field::NEW_SEQ_NO).unwrap().value(), "1");
+        assert_eq!(reset.get_field(field::GAP_FILL_FLAG).unwrap().value(), "N");
+        let order = reader.receive().await.unwrap().unwrap();
+        assert_eq!(order.get_field(field::MSG_SEQ_NUM).unwrap().value(), "1");
+
+        // and the counterparty's reset is followed whatever its MsgSeqNum
+        let mut reset = counterparty_message(field::values::SEQUENCE_RESET, &config, 9);
+        let _ = reset.set_field(Field::new(field::NEW_SEQ_NO, "1"));
+        writer.send(reset).await.unwrap();
+        let mut test_request = counterparty_message(field::values::TEST_REQUEST, &config, 1);
+        let _ = test_request.set_field(Field::new(field::TEST_REQ_ID, "ROLL"));
+        writer.send(test_request).await.unwrap();
+        assert_eq!(reader.receive().await.unwrap().unwrap().msg_type(), field::values::HEARTBEAT);
+        assert_eq!(session.next_target_seq().unwrap(), 2);
+        drop(session);
+
+        // Configured to log out, the limit ends the session and resets it once answered
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        config.target_addr = listener.local_addr().unwrap().to_string();
+        config.reset_on_logout = false;
+        config.seq_num_limit = SeqNumLimit { max_seq_num: 3, action: SeqNumRollover::Logout };
+        let (_temp_dir, store) = temp_store();
+        let session = test_session(config.clone(), store);
+        session.start().await.unwrap();
+        let (mut reader, mut writer) = accept_logon(&listener, &config).await;
+        wait_for_status(&session, state::Status::Connected).await;
+        assert_eq!(session.sender().send(Message::new("D")).await.unwrap(), 2);
+        let err = session.sender().send(Message::new("D")).await.unwrap_err();
+        assert!(matches!(err, FixError::SessionError(_)));
+        assert_eq!(reader.receive().await.unwrap().unwrap().msg_type(), "D");
+        let logout = reader.receive().await.unwrap().unwrap();
+        assert_eq!(logout.msg_type(), field::values::LOGOUT);
+        assert_eq!(logout.get_field(field::MSG_SEQ_NUM).unwrap().value(), "3");
+        assert_eq!(logout.get_field(field::TEXT).unwrap().value(), "MsgSeqNum limit reached");
+        writer.send(counterparty_message(field::values::LOGOUT, &config, 2)).await.unwrap();
+        wait_for_status(&session, state::Status::Disconnected).await;
+        assert_eq!(session.next_sender_seq().unwrap(), 1);
+        assert_eq!(session.next_target_seq().unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_session_seq_num_rollover_on_file_store() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut config = test_config("SENDER_ROLL_FILE", "TARGET_ROLL_FILE", listener.local_addr().unwrap().to_string());
+        config.seq_num_limit = SeqNumLimit { max_seq_num: 4, action: SeqNumRollover::SequenceReset };
+        let temp_dir = tempfile::tempdir().unwrap();
+        let retention = RetentionPolicy { archive_on_reset: true, ..RetentionPolicy::default() };
+        let store = Arc::new(MessageStore::with_dir(temp_dir.path().to_path_buf()).with_retention(retention));
+        let session = test_session(config.clone(), Arc::clone(&store));
+        session.start().await.unwrap();
+        let (mut reader, _writer) = accept_logon(&listener, &config).await;
+        wait_for_status(&session, state::Status::Connected).await;
+
+        for cl_ord_id in ["OLD2", "OLD3", "NEW1"] {
+            let mut order = Message::new("D");
+            let _ = order.set_field(Field::new(field::CL_ORD_ID, cl_ord_id));
+            session.sender().send(order).await.unwrap();
+        }
+        for _ in 0..4 {
+            reader.receive().await.unwrap().unwrap();
+        }
+
+        // Only the new cycle can be resent, so a resend from 1 cannot return an old order
+        let sent = session.store().fetch_range(1, 4).unwrap();
+        assert_eq!(sent.len(), 1);
+        let resent = Message::from_bytes(&sent[0].1).unwrap();
+        assert_eq!(resent.get_field(field::CL_ORD_ID).unwrap().value(), "NEW1");
+        assert_eq!(session.next_sender_seq().unwrap(), 2);
+        assert_eq!(session.next_target_seq().unwrap(), 2);
+
+        // The old cycle went to an archive, as a reset's messages do
+        let archive_dir = store.session_dir(session.session_id()).join("archive");
+        let archives: Vec<_> = std::fs::read_dir(&archive_dir).unwrap().map(|entry| entry.unwrap().path()).collect();
+        assert_eq!(archives.len(), 1);
+        let archived = crate::store::retention::read_archive(&archives[0]).unwrap();
+        let cl_ord_ids: Vec<_> = archived.iter()
+            .map(|raw| Message::from_bytes(raw).unwrap().get_field(field::CL_ORD_ID).unwrap().value().to_string())
+            .collect();
+        assert_eq!(cl_ord_ids, ["OLD2", "OLD3"]);
+    }
+}
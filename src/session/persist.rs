@@ -0,0 +1,172 @@
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+use tokio::sync::oneshot;
+
+use crate::config::StateSyncPolicy;
+use crate::error::FixError;
+use crate::Result;
+
+enum Request {
+    /// Latest serialized state, replacing any not yet written
+    Update(Vec<u8>),
+    /// Write whatever is pending and report the outcome
+    Flush(oneshot::Sender<Result<()>>),
+}
+
+/// Write-behind persistence of a session's state, also used for a file store's sequence numbers.
+///
+/// Updates are handed to a background thread which writes only the latest of them, according
+/// to the [`StateSyncPolicy`], so neither the actor nor the runtime ever waits on the disk.
+/// Pending updates are written when the last handle is dropped.
+#[derive(Debug)]
+pub struct StateWriter {
+    requests: mpsc::Sender<Request>,
+}
+
+impl StateWriter {
+    pub fn new(path: PathBuf, policy: StateSyncPolicy) -> Self {
+        let (requests, receiver) = mpsc::channel();
+        thread::Builder::new()
+            .name("goldfix-state".to_string())
+            .spawn(move || Self::run(&path, policy, receiver))
+            .expect("failed to spawn state writer thread");
+        StateWriter { requests }
+    }
+
+    pub fn update(&self, state: Vec<u8>) {
+        // The thread only stops once every sender is gone
+        let _ = self.requests.send(Request::Update(state));
+    }
+
+    /// Wait until every update handed over so far is on disk
+    pub async fn flush(&self) -> Result<()> {
+        let (done, written) = oneshot::channel();
+        self.requests.send(Request::Flush(done))
+            .map_err(|_| FixError::SessionError("State writer has stopped".to_string()))?;
+        written.await
+            .map_err(|_| FixError::SessionError("State writer has stopped".to_string()))?
+    }
+
+    fn run(path: &Path, policy: StateSyncPolicy, requests: mpsc::Receiver<Request>) {
+        let mut pending: Option<Vec<u8>> = None;
+        // Error of the last background write, reported by the next flush
+        let mut failed: Option<FixError> = None;
+        let mut due: Option<Instant> = None;
+        // A flush found while coalescing updates, handled next
+        let mut deferred: Option<Request> = None;
+
+        loop {
+            let request = match (deferred.take(), due) {
+                (Some(request), _) => Ok(request),
+                (None, Some(due)) => requests.recv_timeout(due.saturating_duration_since(Instant::now())),
+                (None, None) => requests.recv().map_err(|_| RecvTimeoutError::Disconnected),
+            };
+
+            match request {
+                Ok(Request::Update(state)) => {
+                    pending = Some(state);
+                    match policy {
+                        StateSyncPolicy::EveryMessage => {
+                            // Only the newest of the updates queued meanwhile is worth writing
+                            while let Ok(request) = requests.try_recv() {
+                                match request {
+                                    Request::Update(state) => pending = Some(state),
+                                    flush => {
+                                        deferred = Some(flush);
+                                        break;
+                                    },
+                                }
+                            }
+                            failed = Self::write_pending(path, &mut pending).err();
+                        },
+                        StateSyncPolicy::Periodic { interval_ms } => {
+                            due.get_or_insert_with(|| Instant::now() + Duration::from_millis(interval_ms));
+                        },
+                        StateSyncPolicy::OnShutdown => {},
+                    }
+                },
+                Ok(Request::Flush(done)) => {
+                    due = None;
+                    let result = match failed.take() {
+                        Some(e) if pending.is_none() => Err(e),
+                        _ => Self::write_pending(path, &mut pending),
+                    };
+                    let _ = done.send(result);
+                },
+                Err(RecvTimeoutError::Timeout) => {
+                    due = None;
+                    failed = Self::write_pending(path, &mut pending).err();
+                },
+                Err(RecvTimeoutError::Disconnected) => {
+                    let _ = Self::write_pending(path, &mut pending);
+                    return;
+                },
+            }
+        }
+    }
+
+    fn write_pending(path: &Path, pending: &mut Option<Vec<u8>>) -> Result<()> {
+        match pending.take() {
+            Some(state) => write_atomically(path, &state),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Write to a temporary file, sync it and rename it over the previous contents, so a crash
+/// leaves either the old or the new file in place
+pub(crate) fn write_atomically(path: &Path, contents: &[u8]) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let temp_path = path.with_extension("tmp");
+    let mut temp_file = File::create(&temp_path)?;
+    temp_file.write_all(contents)?;
+    temp_file.sync_all()?;
+    fs::rename(&temp_path, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_state_writer_policies() {
+        let temp_dir = tempdir().unwrap();
+
+        // Updates queued behind a write are coalesced into the newest
+        let path = temp_dir.path().join("every.json");
+        let writer = StateWriter::new(path.clone(), StateSyncPolicy::EveryMessage);
+        writer.update(b"1".to_vec());
+        writer.update(b"2".to_vec());
+        writer.flush().await.unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"2");
+
+        // Periodic writes happen once the interval has passed
+        let path = temp_dir.path().join("periodic.json");
+        let writer = StateWriter::new(path.clone(), StateSyncPolicy::Periodic { interval_ms: 50 });
+        writer.update(b"1".to_vec());
+        assert!(!path.exists());
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(fs::read(&path).unwrap(), b"1");
+
+        // On shutdown only on flush or drop
+        let path = temp_dir.path().join("shutdown.json");
+        let writer = StateWriter::new(path.clone(), StateSyncPolicy::OnShutdown);
+        writer.update(b"1".to_vec());
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!path.exists());
+        writer.flush().await.unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"1");
+        writer.update(b"2".to_vec());
+        drop(writer);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(fs::read(&path).unwrap(), b"2");
+    }
+}
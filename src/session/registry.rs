@@ -0,0 +1,182 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use tokio::sync::broadcast;
+use crate::error::FixError;
+use crate::Result;
+use super::{Session, SessionID};
+
+// Events buffered per subscriber before slow receivers start lagging
+const EVENT_CAPACITY: usize = 1024;
+
+/// Session lifecycle notifications published by the engine
+#[derive(Debug, Clone, PartialEq)]
+pub enum SessionEvent {
+    Created(SessionID),
+    LoggedOn(SessionID),
+    LoggedOut(SessionID),
+    Disconnected(SessionID),
+    /// An inbound MsgSeqNum was higher than expected and a resend was requested
+    SequenceGap {
+        session_id: SessionID,
+        expected: u64,
+        received: u64,
+    },
+    /// A session level Reject was sent (`outbound`) or received
+    Reject {
+        session_id: SessionID,
+        ref_seq_num: Option<u64>,
+        reason: Option<i32>,
+        text: String,
+        outbound: bool,
+    },
+}
+
+impl SessionEvent {
+    pub fn session_id(&self) -> &SessionID {
+        match self {
+            SessionEvent::Created(id)
+            | SessionEvent::LoggedOn(id)
+            | SessionEvent::LoggedOut(id)
+            | SessionEvent::Disconnected(id) => id,
+            SessionEvent::SequenceGap { session_id, .. }
+            | SessionEvent::Reject { session_id, .. } => session_id,
+        }
+    }
+}
+
+/// Sessions of an engine keyed by [`SessionID`], shared by the initiator and acceptor
+#[derive(Clone)]
+pub struct SessionRegistry {
+    sessions: Arc<RwLock<HashMap<SessionID, Arc<Session>>>>,
+    events: broadcast::Sender<SessionEvent>,
+}
+
+impl Default for SessionRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SessionRegistry {
+    pub fn new() -> Self {
+        let (events, _) = broadcast::channel(EVENT_CAPACITY);
+        SessionRegistry {
+            sessions: Arc::new(RwLock::new(HashMap::new())),
+            events,
+        }
+    }
+
+    /// Sender for sessions to publish their events on
+    pub fn event_sender(&self) -> broadcast::Sender<SessionEvent> {
+        self.events.clone()
+    }
+
+    /// Receive every event published after this call
+    pub fn subscribe(&self) -> broadcast::Receiver<SessionEvent> {
+        self.events.subscribe()
+    }
+
+    /// Register a new session, failing if its ID is already taken
+    pub fn insert(&self, session: Session) -> Result<Arc<Session>> {
+        let session_id = session.session_id().clone();
+        let session = Arc::new(session);
+        {
+            let mut sessions = self.sessions.write().unwrap();
+            if sessions.contains_key(&session_id) {
+                return Err(FixError::InvalidConfiguration(format!("Session {} already exists", session_id)));
+            }
+            sessions.insert(session_id.clone(), Arc::clone(&session));
+        }
+        let _ = self.events.send(SessionEvent::Created(session_id));
+        Ok(session)
+    }
+
+    /// Register a session, returning the one it replaced
+    pub fn replace(&self, session: Session) -> Option<Arc<Session>> {
+        let session_id = session.session_id().clone();
+        let previous = self.sessions.write().unwrap().insert(session_id.clone(), Arc::new(session));
+        let _ = self.events.send(SessionEvent::Created(session_id));
+        previous
+    }
+
+    pub fn get(&self, session_id: &SessionID) -> Option<Arc<Session>> {
+        self.sessions.read().unwrap().get(session_id).cloned()
+    }
+
+    pub fn remove(&self, session_id: &SessionID) -> Option<Arc<Session>> {
+        self.sessions.write().unwrap().remove(session_id)
+    }
+
+    pub fn contains(&self, session_id: &SessionID) -> bool {
+        self.sessions.read().unwrap().contains_key(session_id)
+    }
+
+    pub fn ids(&self) -> Vec<SessionID> {
+        self.sessions.read().unwrap().keys().cloned().collect()
+    }
+
+    pub fn sessions(&self) -> Vec<Arc<Session>> {
+        self.sessions.read().unwrap().values().cloned().collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.sessions.read().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sessions.read().unwrap().is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use crate::config::{LogConfig, SessionConfig, StoreKind};
+    use crate::logging::Logger;
+    use crate::message::MessagePool;
+    use crate::store::MessageStore;
+
+    // Memory backed, so a second session of the same identity can be built for the duplicate check
+    fn session(registry: &SessionRegistry, store: &Arc<MessageStore>, target: &str) -> Session {
+        let config = SessionConfig {
+            sender_comp_id: "REGISTRY_CLIENT".to_string(),
+            target_comp_id: target.to_string(),
+            target_addr: "127.0.0.1:0".to_string(),
+            reset_on_logon: true,
+            reset_on_logout: true,
+            reset_on_disconnect: true,
+            store: StoreKind::Memory,
+            ..Default::default()
+        };
+        let logger = Arc::new(Logger::new(&LogConfig {
+            log_directory: PathBuf::from("/tmp"),
+            log_level: "INFO".to_string(),
+            log_events: true,
+            log_messages: true,
+        }));
+        Session::new(config, logger, Arc::clone(store), Arc::new(MessagePool::new()),
+            registry.event_sender()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_registry_lookup_and_events() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let store = Arc::new(MessageStore::with_dir(temp_dir.path().to_path_buf()));
+        let registry = SessionRegistry::new();
+        let mut events = registry.subscribe();
+
+        let first = registry.insert(session(&registry, &store, "BROKER_A")).unwrap();
+        registry.insert(session(&registry, &store, "BROKER_B")).unwrap();
+        assert_eq!(registry.len(), 2);
+        assert!(registry.insert(session(&registry, &store, "BROKER_A")).is_err());
+
+        let id = first.session_id().clone();
+        assert!(Arc::ptr_eq(&registry.get(&id).unwrap(), &first));
+        assert_eq!(events.recv().await.unwrap(), SessionEvent::Created(id.clone()));
+
+        assert!(registry.remove(&id).is_some());
+        assert!(!registry.contains(&id));
+        assert_eq!(registry.ids().len(), 1);
+    }
+}
@@ -0,0 +1,64 @@
+use tokio::sync::{mpsc, oneshot, watch};
+use crate::error::FixError;
+use crate::message::Message;
+use crate::Result;
+use super::actor::Command;
+use super::state::SessionState;
+use super::SessionID;
+
+// Commands buffered per session before senders wait for the session task
+pub(crate) const OUTBOUND_CAPACITY: usize = 1024;
+
+/// An application message waiting on a session's outbound queue
+pub(crate) struct Outbound {
+    pub message: Message,
+    /// Answered with the assigned MsgSeqNum once the message is stored
+    pub done: oneshot::Sender<Result<u64>>,
+}
+
+/// Cloneable handle for sending application messages to one session.
+///
+/// Obtained from [`super::Session::sender`] or [`crate::FixEngine::sender`], it can be moved to
+/// any task without holding on to the session itself.
+#[derive(Clone)]
+pub struct SessionSender {
+    session_id: SessionID,
+    commands: mpsc::Sender<Command>,
+    state: watch::Receiver<SessionState>,
+    queue_while_disconnected: bool,
+}
+
+impl SessionSender {
+    pub(crate) fn new(
+        session_id: SessionID,
+        commands: mpsc::Sender<Command>,
+        state: watch::Receiver<SessionState>,
+        queue_while_disconnected: bool,
+    ) -> Self {
+        SessionSender { session_id, commands, state, queue_while_disconnected }
+    }
+
+    pub fn session_id(&self) -> &SessionID {
+        &self.session_id
+    }
+
+    /// Queue `message` and wait until it has been sequenced and stored, returning its MsgSeqNum.
+    ///
+    /// Fails with [`FixError::NotLoggedOn`] unless the session is logged on or configured to
+    /// hold messages until the next logon.
+    pub async fn send(&self, message: Message) -> Result<u64> {
+        if !self.queue_while_disconnected && !self.state.borrow().status().is_logged_on() {
+            return Err(not_logged_on(&self.session_id));
+        }
+
+        let (done, stored) = oneshot::channel();
+        self.commands.send(Command::Send(Outbound { message, done })).await
+            .map_err(|_| FixError::SessionError(format!("Outbound queue of {} is closed", self.session_id)))?;
+        stored.await
+            .map_err(|_| FixError::SessionError(format!("Session {} dropped an outbound message", self.session_id)))?
+    }
+}
+
+pub(crate) fn not_logged_on(session_id: &SessionID) -> FixError {
+    FixError::NotLoggedOn(session_id.to_string())
+}
@@ -1,11 +1,15 @@
-use std::time::{SystemTime, UNIX_EPOCH};
-use std::fs::{self, File};
-use std::io::{Read, Write};
+use std::fs::File;
+use std::io::Read;
 use std::path::PathBuf;
+use std::sync::{Arc, OnceLock};
 use serde::{Serialize, Deserialize};
+use crate::config::StateSyncPolicy;
+use crate::error::FixError;
 use crate::Result;
+use super::persist::StateWriter;
+use super::SessionID;
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Status {
     Created,
     Connecting,
@@ -19,192 +23,190 @@ pub enum Status {
     Recovering,  // New state for recovery
 }
 
+/// Side effect of taking a transition, carried out by the session
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransitionAction {
+    None,
+    /// Arm the logon timeout
+    StartLogonTimer,
+    /// Cancel the logon timeout and start heartbeat monitoring
+    StartHeartbeat,
+    /// Arm the logout timeout
+    StartLogoutTimer,
+    /// Close the transport and cancel any timeout
+    CloseTransport,
+}
+
+/// Allowed transitions as (from any of, to, action). Anything else is rejected, except staying
+/// in the same state which does nothing.
+pub const TRANSITIONS: &[(&[Status], Status, TransitionAction)] = &[
+    (
+        &[Status::Created, Status::Disconnected, Status::Error, Status::Recovering],
+        Status::Connecting,
+        TransitionAction::None,
+    ),
+    (&[Status::Connecting, Status::Recovering], Status::InitiateLogon, TransitionAction::StartLogonTimer),
+    (&[Status::Connecting, Status::InitiateLogon], Status::LogonReceived, TransitionAction::None),
+    (&[Status::LogonReceived], Status::Connected, TransitionAction::StartHeartbeat),
+    (&[Status::Connected], Status::ResendRequest, TransitionAction::None),
+    (&[Status::ResendRequest], Status::Connected, TransitionAction::None),
+    (
+        &[Status::InitiateLogon, Status::LogonReceived, Status::Connected, Status::ResendRequest],
+        Status::Disconnecting,
+        TransitionAction::StartLogoutTimer,
+    ),
+    (
+        &[Status::Created, Status::Connecting, Status::InitiateLogon, Status::LogonReceived, Status::Connected,
+          Status::ResendRequest, Status::Disconnecting, Status::Error, Status::Recovering],
+        Status::Disconnected,
+        TransitionAction::CloseTransport,
+    ),
+    (
+        &[Status::Connecting, Status::InitiateLogon, Status::LogonReceived, Status::Connected,
+          Status::ResendRequest, Status::Disconnecting, Status::Recovering],
+        Status::Error,
+        TransitionAction::CloseTransport,
+    ),
+    (
+        &[Status::Created, Status::InitiateLogon, Status::LogonReceived, Status::Connected,
+          Status::ResendRequest, Status::Disconnecting, Status::Disconnected, Status::Error],
+        Status::Recovering,
+        TransitionAction::CloseTransport,
+    ),
+];
+
+impl Status {
+    /// Action for moving to `next`, or `None` if [`TRANSITIONS`] does not allow it
+    pub fn transition_to(&self, next: Status) -> Option<TransitionAction> {
+        if *self == next {
+            return Some(TransitionAction::None);
+        }
+        TRANSITIONS.iter()
+            .find(|(from, to, _)| *to == next && from.contains(self))
+            .map(|(_, _, action)| *action)
+    }
+
+    /// Logged on, application messages can be exchanged
+    pub fn is_logged_on(&self) -> bool {
+        matches!(self, Status::Connected | Status::ResendRequest)
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct SessionState {
     status: Status,
-    next_outgoing_seq: i32,
-    next_incoming_seq: i32,
-    expected_target_seq_num: i32,
-    last_send_time: u64,
-    last_receive_time: u64,
-    test_request_counter: i32,
     logon_timeout: u64,
     heartbeat_interval: u64,
-    test_request_delay: u64,
-    session_id: String,  // Added for persistence
+    session_id: SessionID,
     store_dir: PathBuf,  // Added for persistence
+    #[serde(skip)]
+    sync_policy: StateSyncPolicy,
+    // Started by the first change, shared by clones
+    #[serde(skip)]
+    writer: OnceLock<Arc<StateWriter>>,
 }
 
 impl SessionState {
-    pub fn new(session_id: &str, store_dir: PathBuf) -> Self {
-        let state = SessionState {
+    pub fn new(session_id: &SessionID, store_dir: PathBuf) -> Self {
+        SessionState {
             status: Status::Created,
-            next_outgoing_seq: 1,
-            next_incoming_seq: 1,
-            expected_target_seq_num: 1,
-            last_send_time: 0,
-            last_receive_time: 0,
-            test_request_counter: 0,
             logon_timeout: 10,
             heartbeat_interval: 30,
-            test_request_delay: 2,
-            session_id: session_id.to_string(),
+            session_id: session_id.clone(),
             store_dir,
-        };
-        state.persist().unwrap_or_default();
-        state
+            sync_policy: StateSyncPolicy::default(),
+            writer: OnceLock::new(),
+        }
     }
 
     pub fn with_config(
-        session_id: &str,
+        session_id: &SessionID,
         store_dir: PathBuf,
         logon_timeout: u64,
         heartbeat_interval: u64,
-        test_request_delay: u64
     ) -> Self {
-        let state = SessionState {
+        SessionState {
             status: Status::Created,
-            next_outgoing_seq: 1,
-            next_incoming_seq: 1,
-            expected_target_seq_num: 1,
-            last_send_time: 0,
-            last_receive_time: 0,
-            test_request_counter: 0,
             logon_timeout,
             heartbeat_interval,
-            test_request_delay,
-            session_id: session_id.to_string(),
+            session_id: session_id.clone(),
             store_dir,
-        };
-        state.persist().unwrap_or_default();
-        state
+            sync_policy: StateSyncPolicy::default(),
+            writer: OnceLock::new(),
+        }
     }
 
-    // Recovery methods
-    pub fn load_or_create(session_id: &str, store_dir: PathBuf) -> Result<Self> {
-        let state_path = store_dir.join(format!("{}_state.json", session_id));
+    /// Load the state a previous run persisted in `store_dir`, as `Recovering`, or start afresh
+    /// if there is none
+    pub fn load_or_create(session_id: &SessionID, store_dir: PathBuf) -> Result<Self> {
+        let state_path = store_dir.join(format!("{}_state.json", session_id.file_name()));
         if state_path.exists() {
             let mut file = File::open(&state_path)?;
             let mut contents = String::new();
             file.read_to_string(&mut contents)?;
             let mut state: SessionState = serde_json::from_str(&contents)?;
             state.status = Status::Recovering;
+            state.store_dir = store_dir;
             Ok(state)
         } else {
             Ok(Self::new(session_id, store_dir))
         }
     }
 
-    fn persist(&self) -> Result<()> {
-        fs::create_dir_all(&self.store_dir)?;
-        let state_path = self.store_dir.join(format!("{}_state.json", self.session_id));
-        let temp_path = state_path.with_extension("tmp");
-
-        // Write to temporary file first
-        let json = serde_json::to_string_pretty(self)?;
-        let mut temp_file = File::create(&temp_path)?;
-        temp_file.write_all(json.as_bytes())?;
-        temp_file.sync_all()?;
-
-        // Atomic rename
-        fs::rename(&temp_path, &state_path)?;
-        Ok(())
-    }
-
-    pub fn set_status(&mut self, status: Status) {
-        self.status = status;
-        self.persist().unwrap_or_default();
-    }
-
-    pub fn status(&self) -> &Status {
-        &self.status
-    }
-
-    pub fn next_outgoing_seq(&self) -> i32 {
-        self.next_outgoing_seq
-    }
-
-    pub fn increment_outgoing_seq(&mut self) {
-        self.next_outgoing_seq += 1;
-        self.persist().unwrap_or_default();
-    }
-
-    pub fn next_incoming_seq(&self) -> i32 {
-        self.next_incoming_seq
+    /// Take the timeouts from the session's current configuration rather than the loaded state
+    pub fn with_timeouts(mut self, logon_timeout: u64, heartbeat_interval: u64) -> Self {
+        self.logon_timeout = logon_timeout;
+        self.heartbeat_interval = heartbeat_interval;
+        self
     }
 
-    pub fn increment_incoming_seq(&mut self) {
-        self.next_incoming_seq += 1;
-        self.persist().unwrap_or_default();
-    }
-
-    pub fn expected_target_seq_num(&self) -> i32 {
-        self.expected_target_seq_num
-    }
-
-    pub fn set_expected_target_seq_num(&mut self, seq_num: i32) {
-        self.expected_target_seq_num = seq_num;
-        self.persist().unwrap_or_default();
-    }
-
-    pub fn increment_test_request_counter(&mut self) {
-        self.test_request_counter += 1;
-        self.persist().unwrap_or_default();
+    /// Set when the state is written to disk; takes effect if called before the first change
+    pub fn with_sync_policy(mut self, policy: StateSyncPolicy) -> Self {
+        self.sync_policy = policy;
+        self
     }
 
-    pub fn reset_test_request_counter(&mut self) {
-        self.test_request_counter = 0;
-        self.persist().unwrap_or_default();
+    // Hand a snapshot to the background writer, see [`StateWriter`]
+    fn persist(&self) -> Result<()> {
+        let json = serde_json::to_vec_pretty(self)?;
+        self.writer().update(json);
+        Ok(())
     }
 
-    pub fn test_request_counter(&self) -> i32 {
-        self.test_request_counter
+    fn writer(&self) -> &StateWriter {
+        self.writer.get_or_init(|| {
+            let state_path = self.store_dir.join(format!("{}_state.json", self.session_id.file_name()));
+            Arc::new(StateWriter::new(state_path, self.sync_policy))
+        })
     }
 
-    pub fn update_send_time(&mut self) {
-        self.last_send_time = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
-        self.persist().unwrap_or_default();
+    /// Wait until every change made so far is on disk
+    pub async fn flush(&self) -> Result<()> {
+        self.writer().flush().await
     }
 
-    pub fn update_receive_time(&mut self) {
-        self.last_receive_time = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
+    fn set_status(&mut self, status: Status) {
+        self.status = status;
         self.persist().unwrap_or_default();
     }
 
-    pub fn should_send_test_request(&self) -> bool {
-        let now = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
-        now - self.last_receive_time > self.heartbeat_interval + self.test_request_delay
+    /// Move to `next` if the transition table allows it, returning the action to carry out
+    pub fn transition(&mut self, next: Status) -> Result<TransitionAction> {
+        let action = self.status.transition_to(next).ok_or_else(|| FixError::SessionError(
+            format!("Illegal transition {:?} -> {:?}", self.status, next)
+        ))?;
+        if self.status != next {
+            self.set_status(next);
+        }
+        Ok(action)
     }
 
-    pub fn should_disconnect(&self) -> bool {
-        let now = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
-        match self.status {
-            Status::InitiateLogon => now - self.last_send_time > self.logon_timeout,
-            Status::Connected => {
-                self.test_request_counter >= 2 ||
-                now - self.last_receive_time > 2 * self.heartbeat_interval
-            },
-            _ => false,
-        }
+    pub fn logon_timeout(&self) -> u64 {
+        self.logon_timeout
     }
 
-    pub fn reset_sequence_numbers(&mut self) {
-        self.next_outgoing_seq = 1;
-        self.next_incoming_seq = 1;
-        self.expected_target_seq_num = 1;
-        self.persist().unwrap_or_default();
+    pub fn status(&self) -> &Status {
+        &self.status
     }
 }
 
@@ -213,83 +215,105 @@ mod tests {
     use super::*;
     use tempfile::tempdir;
 
+    // Walk an initiator logon from Created to Connected
+    fn log_on(state: &mut SessionState) {
+        for next in [Status::Connecting, Status::InitiateLogon, Status::LogonReceived, Status::Connected] {
+            state.transition(next).unwrap();
+        }
+    }
+
     #[test]
     fn test_session_state_persistence() {
         let temp_dir = tempdir().unwrap();
-        let session_id = "TEST_SESSION";
+        let session_id = &SessionID::new("FIX.4.2", "TEST", "SESSION");
 
         // Create new state
-        let mut state = SessionState::new(session_id, temp_dir.path().to_path_buf());
-        state.set_status(Status::Connected);
-        state.increment_outgoing_seq();
-        state.increment_incoming_seq();
+        let mut state = SessionState::with_config(session_id, temp_dir.path().to_path_buf(), 15, 30);
+        log_on(&mut state);
+        futures::executor::block_on(state.flush()).unwrap();
 
         // Load persisted state
         let loaded_state = SessionState::load_or_create(session_id, temp_dir.path().to_path_buf()).unwrap();
 
         assert_eq!(loaded_state.status, Status::Recovering);
-        assert_eq!(loaded_state.next_outgoing_seq(), 2);
-        assert_eq!(loaded_state.next_incoming_seq(), 2);
+        assert_eq!(loaded_state.logon_timeout(), 15);
+
+        // Sequence numbers belong to the store, not the state file
+        let json = std::fs::read_to_string(temp_dir.path().join(format!("{}_state.json", session_id.file_name()))).unwrap();
+        assert!(!json.contains("seq_num"), "{}", json);
     }
 
     #[test]
     fn test_session_state_transitions() {
         let temp_dir = tempdir().unwrap();
-        let mut state = SessionState::new("TEST", temp_dir.path().to_path_buf());
+        let mut state = SessionState::new(&SessionID::new("FIX.4.2", "TEST", "STATE"), temp_dir.path().to_path_buf());
         assert_eq!(*state.status(), Status::Created);
 
-        state.set_status(Status::Connecting);
+        state.transition(Status::Connecting).unwrap();
         assert_eq!(*state.status(), Status::Connecting);
 
-        state.set_status(Status::Connected);
-        assert_eq!(*state.status(), Status::Connected);
-    }
-
-    #[test]
-    fn test_sequence_numbers() {
-        let temp_dir = tempdir().unwrap();
-        let mut state = SessionState::new("TEST", temp_dir.path().to_path_buf());
-        assert_eq!(state.next_outgoing_seq(), 1);
-        assert_eq!(state.next_incoming_seq(), 1);
-
-        state.increment_outgoing_seq();
-        state.increment_incoming_seq();
-
-        assert_eq!(state.next_outgoing_seq(), 2);
-        assert_eq!(state.next_incoming_seq(), 2);
-    }
+        // Connected is only reached through a logon
+        assert!(state.transition(Status::Connected).is_err());
+        assert_eq!(*state.status(), Status::Connecting);
 
-    #[test]
-    fn test_test_request_handling() {
-        let temp_dir = tempdir().unwrap();
-        let mut state = SessionState::with_config(
-            "TEST",
-            temp_dir.path().to_path_buf(),
-            10, 30, 2
-        );
-        assert_eq!(state.test_request_counter(), 0);
-
-        state.increment_test_request_counter();
-        assert_eq!(state.test_request_counter(), 1);
-
-        state.reset_test_request_counter();
-        assert_eq!(state.test_request_counter(), 0);
+        log_on(&mut state);
+        assert_eq!(*state.status(), Status::Connected);
     }
 
     #[test]
     fn test_recovery_state() {
         let temp_dir = tempdir().unwrap();
-        let session_id = "TEST_RECOVERY";
+        let session_id = &SessionID::new("FIX.4.2", "TEST", "RECOVERY");
 
         // Create initial state
         let mut state = SessionState::new(session_id, temp_dir.path().to_path_buf());
-        state.set_status(Status::Connected);
-        state.increment_outgoing_seq();
+        log_on(&mut state);
+        futures::executor::block_on(state.flush()).unwrap();
 
         // Simulate crash and recovery
         let recovered_state = SessionState::load_or_create(session_id, temp_dir.path().to_path_buf()).unwrap();
         assert_eq!(recovered_state.status, Status::Recovering);
-        assert_eq!(recovered_state.next_outgoing_seq(), 2);
-        assert!(recovered_state.should_send_test_request());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_transition_table() {
+        let temp_dir = tempdir().unwrap();
+        let mut state = SessionState::new(&SessionID::new("FIX.4.2", "TEST", "TABLE"), temp_dir.path().to_path_buf());
+
+        // Initiator logon, a resend and a logout
+        let path = [
+            (Status::Connecting, TransitionAction::None),
+            (Status::InitiateLogon, TransitionAction::StartLogonTimer),
+            (Status::LogonReceived, TransitionAction::None),
+            (Status::Connected, TransitionAction::StartHeartbeat),
+            (Status::ResendRequest, TransitionAction::None),
+            (Status::Connected, TransitionAction::None),
+            (Status::Disconnecting, TransitionAction::StartLogoutTimer),
+            (Status::Disconnected, TransitionAction::CloseTransport),
+        ];
+        for (next, action) in path {
+            assert_eq!(state.transition(next).unwrap(), action, "to {:?}", next);
+            assert_eq!(*state.status(), next);
+        }
+
+        // Illegal transitions are rejected and leave the state alone
+        assert!(state.transition(Status::Connected).is_err());
+        assert!(state.transition(Status::LogonReceived).is_err());
+        assert_eq!(*state.status(), Status::Disconnected);
+        assert_eq!(state.transition(Status::Disconnected).unwrap(), TransitionAction::None);
+
+        assert_eq!(Status::Created.transition_to(Status::Connected), None);
+        assert_eq!(Status::InitiateLogon.transition_to(Status::Connected), None);
+        assert_eq!(Status::Connecting.transition_to(Status::Error), Some(TransitionAction::CloseTransport));
+        assert_eq!(Status::Recovering.transition_to(Status::Connecting), Some(TransitionAction::None));
+        assert!(Status::ResendRequest.is_logged_on());
+        assert!(!Status::LogonReceived.is_logged_on());
+
+        // Every state can be left for Disconnected
+        for (from, _, _) in TRANSITIONS {
+            for status in from.iter() {
+                assert!(status.transition_to(Status::Disconnected).is_some(), "{:?}", status);
+            }
+        }
+    }
+}
@@ -0,0 +1,265 @@
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use crate::config::{FsyncPolicy, RetentionPolicy, StateSyncPolicy};
+use crate::error::FixError;
+use crate::message::parser::MessageParser;
+use crate::session::persist::{write_atomically, StateWriter};
+use crate::session::SessionID;
+use crate::Result;
+use super::journal::{Journal, Recovery};
+use super::memory::StoreContents;
+use super::{retention, Direction, MessageStoreBackend};
+
+const TIME_FORMAT: &str = "%Y%m%d-%H:%M:%S%.3f";
+
+/// Keeps a session's messages and sequence numbers in its own directory.
+///
+/// The directory holds:
+/// - `body`: a [`Journal`] of the messages as sent
+/// - `header`: its offset index
+/// - `inbound.body` and `inbound.header`: the same for received messages
+/// - `seqnums`: the next sender and target sequence numbers, written behind by a background
+///   thread as often as the [`FsyncPolicy`] syncs the journals
+/// - `session`: when the store was created or last reset
+/// - `lock`: held while the store is open, so a second process cannot open the same session
+/// - `archive`: compressed copies of the sent messages dropped by resets, if the retention policy
+///   asks for them
+#[derive(Debug)]
+pub struct FileStore {
+    dir: PathBuf,
+    session_id: SessionID,
+    journal: Mutex<Journal>,
+    inbound: Mutex<Journal>,
+    // Sequence numbers and creation time; messages are read from the journal
+    contents: Mutex<StoreContents>,
+    seqnums: StateWriter,
+    recovery: Recovery,
+    retention: RetentionPolicy,
+    // Released when the store is dropped
+    _lock: File,
+}
+
+impl FileStore {
+    /// Open the store of `session_id` in its directory under `root`, loading whatever it
+    /// already holds. Fails if the session is already open elsewhere.
+    pub fn open(root: &Path, session_id: &SessionID, fsync: FsyncPolicy) -> Result<Self> {
+        let dir = root.join(session_id.file_name());
+        let lock = lock_session_dir(&dir, session_id)?;
+
+        let (journal, recovery) = Journal::open(&dir.join("body"), &dir.join("header"), fsync)?;
+        let (inbound, inbound_recovery) = Journal::open(&dir.join("inbound.body"), &dir.join("inbound.header"), fsync)?;
+        let recovery = Recovery {
+            reindexed: recovery.reindexed + inbound_recovery.reindexed,
+            truncated: recovery.truncated + inbound_recovery.truncated,
+        };
+        let seqnums = StateWriter::new(dir.join("seqnums"), seqnum_sync(fsync));
+        let store = FileStore {
+            dir,
+            session_id: session_id.clone(),
+            journal: Mutex::new(journal),
+            inbound: Mutex::new(inbound),
+            contents: Mutex::new(StoreContents::new()),
+            seqnums,
+            recovery,
+            retention: RetentionPolicy::default(),
+            _lock: lock,
+        };
+        store.refresh()?;
+        Ok(store)
+    }
+
+    /// Set what compaction drops and whether resets archive the messages first
+    pub fn with_retention(mut self, retention: RetentionPolicy) -> Self {
+        self.retention = retention;
+        self
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// What opening the journals had to repair after a crash
+    pub fn recovery(&self) -> &Recovery {
+        &self.recovery
+    }
+
+    fn journal(&self, direction: Direction) -> &Mutex<Journal> {
+        match direction {
+            Direction::Outbound => &self.journal,
+            Direction::Inbound => &self.inbound,
+        }
+    }
+
+    // Empty the outbound journal, archiving it first if the retention policy asks to
+    fn clear_sent(&self) -> Result<()> {
+        let mut journal = self.journal.lock().unwrap();
+        let max_seq = journal.max_seq();
+        if self.retention.archive_on_reset && max_seq > 0 {
+            let messages = journal.range(1, max_seq)?.into_iter().map(|(_, message)| message).collect::<Vec<_>>();
+            retention::write_archive(&self.dir.join("archive"), &self.session_id, &messages, self.retention.max_archives)?;
+        }
+        journal.clear()
+    }
+
+    // Hand the sequence numbers to the background writer, the caller never waits on the disk
+    fn write_seqnums(&self, contents: &StoreContents) {
+        let seqnums = format!("{} : {}", contents.next_sender_seq, contents.next_target_seq);
+        self.seqnums.update(seqnums.into_bytes());
+    }
+
+    // Wait for the sequence numbers handed over so far to be written
+    fn flush_seqnums(&self) -> Result<()> {
+        futures::executor::block_on(self.seqnums.flush())
+    }
+
+    fn write_creation_time(&self, contents: &StoreContents) -> Result<()> {
+        let creation_time = contents.creation_time.format(TIME_FORMAT).to_string();
+        write_atomically(&self.dir.join("session"), creation_time.as_bytes())
+    }
+
+    fn read_seqnums(&self) -> Result<Option<(u64, u64)>> {
+        let path = self.dir.join("seqnums");
+        if !path.exists() {
+            return Ok(None);
+        }
+        let text = fs::read_to_string(&path)?;
+        let parse = |value: Option<&str>| value.and_then(|value| MessageParser::parse_seq_num(value.trim()).ok());
+        let mut parts = text.split(':');
+        match (parse(parts.next()), parse(parts.next())) {
+            (Some(sender), Some(target)) => Ok(Some((sender, target))),
+            _ => Err(FixError::StoreError(format!("Invalid sequence number file {}", path.display()))),
+        }
+    }
+
+    fn read_creation_time(&self) -> Result<Option<DateTime<Utc>>> {
+        let path = self.dir.join("session");
+        if !path.exists() {
+            return Ok(None);
+        }
+        let text = fs::read_to_string(&path)?;
+        NaiveDateTime::parse_from_str(text.trim(), TIME_FORMAT)
+            .map(|time| Some(time.and_utc()))
+            .map_err(|e| FixError::StoreError(format!("Invalid session file {}: {}", path.display(), e)))
+    }
+}
+
+impl Drop for FileStore {
+    // The lock is only released once the sequence numbers are on disk, so whoever opens the
+    // store next reads the latest
+    fn drop(&mut self) {
+        let _ = self.flush_seqnums();
+    }
+}
+
+// Sequence numbers are written as often as the journals are synced
+fn seqnum_sync(fsync: FsyncPolicy) -> StateSyncPolicy {
+    match fsync {
+        FsyncPolicy::Always => StateSyncPolicy::EveryMessage,
+        FsyncPolicy::Interval { interval_ms } => StateSyncPolicy::Periodic { interval_ms },
+        FsyncPolicy::Never => StateSyncPolicy::OnShutdown,
+    }
+}
+
+/// Create a session's directory and take its `lock` file, released when the returned file is
+/// dropped. Fails if the session is already open elsewhere.
+pub(crate) fn lock_session_dir(dir: &Path, session_id: &SessionID) -> Result<File> {
+    fs::create_dir_all(dir)?;
+    let lock = OpenOptions::new().create(true).truncate(false).write(true).open(dir.join("lock"))?;
+    match lock.try_lock() {
+        Ok(()) => Ok(lock),
+        Err(TryLockError::WouldBlock) => {
+            Err(FixError::StoreError(format!("Store of {} is already open in {}", session_id, dir.display())))
+        },
+        Err(TryLockError::Error(e)) => Err(e.into()),
+    }
+}
+
+impl MessageStoreBackend for FileStore {
+    fn store_message(&self, direction: Direction, seq_num: u64, message: &[u8]) -> Result<()> {
+        self.journal(direction).lock().unwrap().append(seq_num, message)
+    }
+
+    fn fetch_messages(&self, direction: Direction, begin: u64, end: u64) -> Result<Vec<(u64, Vec<u8>)>> {
+        self.journal(direction).lock().unwrap().range(begin, end)
+    }
+
+    fn commit_outgoing(&self, messages: &[(u64, Vec<u8>)]) -> Result<()> {
+        let mut contents = self.contents.lock().unwrap();
+        self.journal.lock().unwrap().append_batch(messages)?;
+        if contents.advance_sender_seq(messages) {
+            self.write_seqnums(&contents);
+        }
+        Ok(())
+    }
+
+    fn next_sender_seq(&self) -> Result<u64> {
+        Ok(self.contents.lock().unwrap().next_sender_seq)
+    }
+
+    fn next_target_seq(&self) -> Result<u64> {
+        Ok(self.contents.lock().unwrap().next_target_seq)
+    }
+
+    fn set_next_sender_seq(&self, seq_num: u64) -> Result<()> {
+        let mut contents = self.contents.lock().unwrap();
+        contents.next_sender_seq = seq_num;
+        self.write_seqnums(&contents);
+        Ok(())
+    }
+
+    fn set_next_target_seq(&self, seq_num: u64) -> Result<()> {
+        let mut contents = self.contents.lock().unwrap();
+        contents.next_target_seq = seq_num;
+        self.write_seqnums(&contents);
+        Ok(())
+    }
+
+    fn reset(&self) -> Result<()> {
+        let mut contents = self.contents.lock().unwrap();
+        *contents = StoreContents::new();
+        self.clear_sent()?;
+        self.inbound.lock().unwrap().clear()?;
+        self.write_seqnums(&contents);
+        self.write_creation_time(&contents)
+    }
+
+    fn reset_sender(&self) -> Result<()> {
+        let mut contents = self.contents.lock().unwrap();
+        contents.next_sender_seq = 1;
+        self.clear_sent()?;
+        self.write_seqnums(&contents);
+        Ok(())
+    }
+
+    /// Reload the sequence numbers and creation time, once any pending write of the sequence
+    /// numbers is done
+    fn refresh(&self) -> Result<()> {
+        self.flush_seqnums()?;
+        let mut loaded = StoreContents::new();
+
+        match self.read_seqnums()? {
+            Some((sender, target)) => {
+                loaded.next_sender_seq = sender;
+                loaded.next_target_seq = target;
+            },
+            None => self.write_seqnums(&loaded),
+        }
+        match self.read_creation_time()? {
+            Some(time) => loaded.creation_time = time,
+            None => self.write_creation_time(&loaded)?,
+        }
+
+        *self.contents.lock().unwrap() = loaded;
+        Ok(())
+    }
+
+    fn creation_time(&self) -> Result<DateTime<Utc>> {
+        Ok(self.contents.lock().unwrap().creation_time)
+    }
+
+    fn compact(&self, resend_floor: u64) -> Result<Vec<u64>> {
+        retention::compact(&mut self.journal.lock().unwrap(), &self.retention, resend_floor)
+    }
+}
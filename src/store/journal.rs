@@ -0,0 +1,479 @@
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use crate::config::FsyncPolicy;
+use crate::error::FixError;
+use crate::Result;
+
+// Payload length, CRC32 of sequence number and payload, sequence number
+const RECORD_HEADER: u64 = 16;
+// Set in the length of every record of a batch but the last
+const BATCH_CONTINUES: u32 = 1 << 31;
+
+/// What opening a journal had to repair
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Recovery {
+    /// Records found past the end of the index and added to it
+    pub reindexed: usize,
+    /// Bytes of a torn or corrupt record cut off the end of the journal
+    pub truncated: u64,
+}
+
+/// Append-only record file with an offset index.
+///
+/// Each record is its payload length, a CRC32 and the sequence number, followed by the
+/// payload. The index file holds one `seq,offset,size` line per record and is loaded on open,
+/// so records are found by sequence number without scanning. Records written after the last
+/// index line are scanned and indexed, and a torn record at the end is cut off along with the
+/// rest of its batch, so a batch is recovered whole or not at all.
+#[derive(Debug)]
+pub struct Journal {
+    path: PathBuf,
+    file: File,
+    index_file: File,
+    // Sequence number to record offset and payload size
+    index: BTreeMap<u64, (u64, u32)>,
+    max_seq: u64,
+    len: u64,
+    fsync: FsyncPolicy,
+    last_sync: Instant,
+    unsynced: bool,
+}
+
+impl Journal {
+    pub fn open(path: &Path, index_path: &Path, fsync: FsyncPolicy) -> Result<(Self, Recovery)> {
+        let file = OpenOptions::new().create(true).read(true).append(true).open(path)?;
+        let index_file = OpenOptions::new().create(true).read(true).append(true).open(index_path)?;
+        let mut journal = Journal {
+            path: path.to_path_buf(),
+            len: file.metadata()?.len(),
+            file,
+            index_file,
+            index: BTreeMap::new(),
+            max_seq: 0,
+            fsync,
+            last_sync: Instant::now(),
+            unsynced: false,
+        };
+        let recovery = journal.recover()?;
+        Ok((journal, recovery))
+    }
+
+    fn recover(&mut self) -> Result<Recovery> {
+        let mut recovery = Recovery::default();
+
+        // Index lines up to the first torn or dangling one
+        let mut index = String::new();
+        self.index_file.seek(SeekFrom::Start(0))?;
+        self.index_file.read_to_string(&mut index)?;
+        let mut indexed_len = 0;
+        let mut scan_from = 0;
+        for line in index.split_inclusive('\n') {
+            let Some((seq_num, offset, size)) = line.strip_suffix('\n').and_then(parse_index_line) else { break };
+            let end = offset + RECORD_HEADER + size as u64;
+            if end > self.len {
+                break;
+            }
+            self.insert(seq_num, offset, size);
+            indexed_len += line.len() as u64;
+            scan_from = scan_from.max(end);
+        }
+        self.index_file.set_len(indexed_len)?;
+
+        // Records written after the last index line, taken a whole batch at a time
+        let mut offset = scan_from;
+        let mut batch = Vec::new();
+        let mut committed = scan_from;
+        while let Some((seq_num, size, continues)) = self.check_record(offset)? {
+            batch.push((seq_num, offset, size));
+            offset += RECORD_HEADER + size as u64;
+            if !continues {
+                for (seq_num, offset, size) in batch.drain(..) {
+                    self.insert(seq_num, offset, size);
+                    writeln!(self.index_file, "{},{},{}", seq_num, offset, size)?;
+                    recovery.reindexed += 1;
+                }
+                committed = offset;
+            }
+        }
+        let offset = committed;
+        if offset < self.len {
+            recovery.truncated = self.len - offset;
+            self.file.set_len(offset)?;
+            self.file.sync_data()?;
+            self.len = offset;
+        }
+        Ok(recovery)
+    }
+
+    // Sequence number, size and batch flag of an intact record at `offset`, `None` if torn or corrupt
+    fn check_record(&mut self, offset: u64) -> Result<Option<(u64, u32, bool)>> {
+        if offset + RECORD_HEADER > self.len {
+            return Ok(None);
+        }
+        let mut header = [0; RECORD_HEADER as usize];
+        self.file.seek(SeekFrom::Start(offset))?;
+        self.file.read_exact(&mut header)?;
+        let (size, crc, seq_num, continues) = decode_header(&header);
+        if offset + RECORD_HEADER + size as u64 > self.len {
+            return Ok(None);
+        }
+        let mut payload = vec![0; size as usize];
+        self.file.read_exact(&mut payload)?;
+        Ok((checksum(seq_num, &payload) == crc).then_some((seq_num, size, continues)))
+    }
+
+    fn insert(&mut self, seq_num: u64, offset: u64, size: u32) {
+        self.index.insert(seq_num, (offset, size));
+        self.max_seq = self.max_seq.max(seq_num);
+    }
+
+    pub fn append(&mut self, seq_num: u64, payload: &[u8]) -> Result<()> {
+        let (size, record) = encode_record(seq_num, payload, false)?;
+
+        // The record goes first, an index line without its record would be dropped on open
+        let offset = self.len;
+        if let Err(e) = self.file.write_all(&record) {
+            self.discard_torn();
+            return Err(e.into());
+        }
+        self.len += record.len() as u64;
+        writeln!(self.index_file, "{},{},{}", seq_num, offset, size)?;
+        self.insert(seq_num, offset, size);
+
+        self.unsynced = true;
+        match self.fsync {
+            FsyncPolicy::Always => self.sync()?,
+            FsyncPolicy::Interval { interval_ms } => {
+                if self.last_sync.elapsed() >= Duration::from_millis(interval_ms) {
+                    self.sync()?;
+                }
+            },
+            FsyncPolicy::Never => {},
+        }
+        Ok(())
+    }
+
+    /// Append records that are recovered all together or not at all. They are forced to disk
+    /// whatever the fsync policy, before they are indexed.
+    pub fn append_batch(&mut self, records: &[(u64, Vec<u8>)]) -> Result<()> {
+        let mut batch = Vec::new();
+        let mut entries = Vec::with_capacity(records.len());
+        for (i, (seq_num, payload)) in records.iter().enumerate() {
+            let (size, record) = encode_record(*seq_num, payload, i + 1 < records.len())?;
+            entries.push((*seq_num, self.len + batch.len() as u64, size));
+            batch.extend_from_slice(&record);
+        }
+        if batch.is_empty() {
+            return Ok(());
+        }
+
+        // Not recovered on open either if it could not be written and synced in full
+        self.unsynced = true;
+        if let Err(e) = self.file.write_all(&batch).map_err(FixError::from).and_then(|_| self.sync()) {
+            self.discard_torn();
+            return Err(e);
+        }
+        self.len += batch.len() as u64;
+        for (seq_num, offset, size) in entries {
+            writeln!(self.index_file, "{},{},{}", seq_num, offset, size)?;
+            self.insert(seq_num, offset, size);
+        }
+        Ok(())
+    }
+
+    // Cut off whatever part of a failed append reached the file, which would otherwise be
+    // taken for records when the journal is next opened
+    fn discard_torn(&mut self) {
+        if self.file.set_len(self.len).is_ok() {
+            let _ = self.file.sync_data();
+        }
+    }
+
+    /// Force appended records to disk
+    pub fn sync(&mut self) -> Result<()> {
+        if self.unsynced {
+            self.file.sync_data()?;
+            self.unsynced = false;
+        }
+        self.last_sync = Instant::now();
+        Ok(())
+    }
+
+    /// Payload of the newest record with this sequence number
+    pub fn read(&mut self, seq_num: u64) -> Result<Option<Vec<u8>>> {
+        let Some(&(offset, size)) = self.index.get(&seq_num) else {
+            return Ok(None);
+        };
+        let mut record = vec![0; (RECORD_HEADER + size as u64) as usize];
+        self.file.seek(SeekFrom::Start(offset))?;
+        self.file.read_exact(&mut record)?;
+
+        let (stored_size, crc, stored_seq, _) = decode_header(&record[..RECORD_HEADER as usize]);
+        let payload = record.split_off(RECORD_HEADER as usize);
+        if stored_size != size || stored_seq != seq_num || checksum(seq_num, &payload) != crc {
+            return Err(FixError::StoreError(format!(
+                "Corrupted record {} at offset {} of {}", seq_num, offset, self.path.display()
+            )));
+        }
+        Ok(Some(payload))
+    }
+
+    /// Records from `begin` to `end` inclusive, in sequence order
+    pub fn range(&mut self, begin: u64, end: u64) -> Result<Vec<(u64, Vec<u8>)>> {
+        if begin > end {
+            return Ok(Vec::new());
+        }
+        let seq_nums: Vec<u64> = self.index.range(begin..=end).map(|(&seq_num, _)| seq_num).collect();
+        let mut records = Vec::with_capacity(seq_nums.len());
+        for seq_num in seq_nums {
+            if let Some(payload) = self.read(seq_num)? {
+                records.push((seq_num, payload));
+            }
+        }
+        Ok(records)
+    }
+
+    pub fn max_seq(&self) -> u64 {
+        self.max_seq
+    }
+
+    /// Sequence numbers of the records, in order
+    pub fn seq_nums(&self) -> Vec<u64> {
+        self.index.keys().copied().collect()
+    }
+
+    /// Rewrite the journal with only the records `keep` accepts, returning the sequence numbers
+    /// of those dropped. Fails without changing anything if a record is corrupt.
+    pub fn retain(&mut self, mut keep: impl FnMut(u64, &[u8]) -> bool) -> Result<Vec<u64>> {
+        let mut kept = Vec::new();
+        let mut dropped = Vec::new();
+        for seq_num in self.seq_nums() {
+            let Some(payload) = self.read(seq_num)? else { continue };
+            if keep(seq_num, &payload) {
+                kept.push((seq_num, payload));
+            } else {
+                dropped.push(seq_num);
+            }
+        }
+        if dropped.is_empty() {
+            return Ok(dropped);
+        }
+
+        // The index is emptied first so a crash part way leaves a journal that is rescanned on open
+        self.index_file.set_len(0)?;
+        self.index_file.sync_data()?;
+        let compacted = self.path.with_extension("compact");
+        let mut file = File::create(&compacted)?;
+        let mut entries = Vec::with_capacity(kept.len());
+        let mut len = 0;
+        for (seq_num, payload) in &kept {
+            let (size, record) = encode_record(*seq_num, payload, false)?;
+            file.write_all(&record)?;
+            entries.push((*seq_num, len, size));
+            len += record.len() as u64;
+        }
+        file.sync_all()?;
+        fs::rename(&compacted, &self.path)?;
+
+        self.file = OpenOptions::new().read(true).append(true).open(&self.path)?;
+        self.index.clear();
+        self.max_seq = 0;
+        self.len = len;
+        for (seq_num, offset, size) in entries {
+            writeln!(self.index_file, "{},{},{}", seq_num, offset, size)?;
+            self.insert(seq_num, offset, size);
+        }
+        self.index_file.sync_data()?;
+        self.unsynced = false;
+        Ok(dropped)
+    }
+
+    /// Drop every record
+    pub fn clear(&mut self) -> Result<()> {
+        self.file.set_len(0)?;
+        self.index_file.set_len(0)?;
+        self.file.sync_data()?;
+        self.index.clear();
+        self.max_seq = 0;
+        self.len = 0;
+        self.unsynced = false;
+        Ok(())
+    }
+}
+
+impl Drop for Journal {
+    fn drop(&mut self) {
+        let _ = self.sync();
+    }
+}
+
+// Payload size and the complete record
+fn encode_record(seq_num: u64, payload: &[u8], continues: bool) -> Result<(u32, Vec<u8>)> {
+    let size = u32::try_from(payload.len()).ok().filter(|size| size & BATCH_CONTINUES == 0)
+        .ok_or_else(|| FixError::StoreError(format!("Record {} is too large", seq_num)))?;
+    let flag = if continues { BATCH_CONTINUES } else { 0 };
+    let mut record = Vec::with_capacity(RECORD_HEADER as usize + payload.len());
+    record.extend_from_slice(&(size | flag).to_le_bytes());
+    record.extend_from_slice(&checksum(seq_num, payload).to_le_bytes());
+    record.extend_from_slice(&seq_num.to_le_bytes());
+    record.extend_from_slice(payload);
+    Ok((size, record))
+}
+
+fn checksum(seq_num: u64, payload: &[u8]) -> u32 {
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(&seq_num.to_le_bytes());
+    hasher.update(payload);
+    hasher.finalize()
+}
+
+fn decode_header(header: &[u8]) -> (u32, u32, u64, bool) {
+    let word = |i: usize| header[i..i + 4].try_into().unwrap();
+    let size = u32::from_le_bytes(word(0));
+    let seq_num = u64::from_le_bytes(header[8..16].try_into().unwrap());
+    (size & !BATCH_CONTINUES, u32::from_le_bytes(word(4)), seq_num, size & BATCH_CONTINUES != 0)
+}
+
+fn parse_index_line(line: &str) -> Option<(u64, u64, u32)> {
+    let mut parts = line.split(',');
+    let entry = (parts.next()?.parse().ok()?, parts.next()?.parse().ok()?, parts.next()?.parse().ok()?);
+    parts.next().is_none().then_some(entry)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_journal_recovery() {
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().join("body");
+        let index_path = temp_dir.path().join("header");
+
+        let (mut journal, recovery) = Journal::open(&path, &index_path, FsyncPolicy::Always).unwrap();
+        assert_eq!(recovery, Recovery::default());
+        journal.append(1, b"first").unwrap();
+        journal.append(2, b"second|with pipe").unwrap();
+        journal.append(3, b"third").unwrap();
+        assert_eq!(journal.read(2).unwrap().unwrap(), b"second|with pipe");
+        assert_eq!(journal.range(2, 10).unwrap().len(), 2);
+        assert!(journal.range(3, 2).unwrap().is_empty());
+        drop(journal);
+
+        // Lose the last index line and tear the last record
+        let index = fs::read_to_string(&index_path).unwrap();
+        fs::write(&index_path, &index[..index.trim_end().rfind('\n').unwrap() + 1]).unwrap();
+        let body = fs::read(&path).unwrap();
+        fs::write(&path, &body[..body.len() - 2]).unwrap();
+        let (mut journal, recovery) = Journal::open(&path, &index_path, FsyncPolicy::Never).unwrap();
+        assert_eq!(recovery, Recovery { reindexed: 0, truncated: RECORD_HEADER + 3 });
+        assert!(journal.read(3).unwrap().is_none());
+        assert_eq!(journal.max_seq(), 2);
+
+        // Records without an index line are picked up again
+        journal.append(3, b"again").unwrap();
+        drop(journal);
+        let index = fs::read_to_string(&index_path).unwrap();
+        fs::write(&index_path, &index[..index.trim_end().rfind('\n').unwrap() + 1]).unwrap();
+        let (mut journal, recovery) = Journal::open(&path, &index_path, FsyncPolicy::Never).unwrap();
+        assert_eq!(recovery, Recovery { reindexed: 1, truncated: 0 });
+        assert_eq!(journal.read(3).unwrap().unwrap(), b"again");
+
+        // A flipped bit inside an indexed record is reported when read
+        drop(journal);
+        let mut body = fs::read(&path).unwrap();
+        body[RECORD_HEADER as usize] ^= 1;
+        fs::write(&path, body).unwrap();
+        let (mut journal, _) = Journal::open(&path, &index_path, FsyncPolicy::Never).unwrap();
+        assert!(matches!(journal.read(1), Err(FixError::StoreError(_))));
+        assert_eq!(journal.read(2).unwrap().unwrap(), b"second|with pipe");
+    }
+
+    #[test]
+    fn test_journal_wide_seq_nums() {
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().join("body");
+        let index_path = temp_dir.path().join("header");
+
+        let seq_num = u32::MAX as u64 + 2;
+        let (mut journal, _) = Journal::open(&path, &index_path, FsyncPolicy::Never).unwrap();
+        journal.append(seq_num, b"wide").unwrap();
+        journal.append(u64::MAX, b"last").unwrap();
+        drop(journal);
+
+        // Both from the index and when rebuilt by scanning
+        for _ in 0..2 {
+            let (mut journal, _) = Journal::open(&path, &index_path, FsyncPolicy::Never).unwrap();
+            assert_eq!(journal.seq_nums(), vec![seq_num, u64::MAX]);
+            assert_eq!(journal.read(seq_num).unwrap().unwrap(), b"wide");
+            assert_eq!(journal.range(seq_num, seq_num + 1).unwrap(), vec![(seq_num, b"wide".to_vec())]);
+            drop(journal);
+            fs::write(&index_path, "").unwrap();
+        }
+    }
+
+    #[test]
+    fn test_journal_retain() {
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().join("body");
+        let index_path = temp_dir.path().join("header");
+
+        let (mut journal, _) = Journal::open(&path, &index_path, FsyncPolicy::Never).unwrap();
+        for seq_num in 1..=5 {
+            journal.append(seq_num, format!("record {}", seq_num).as_bytes()).unwrap();
+        }
+        assert_eq!(journal.retain(|seq_num, _| seq_num >= 3).unwrap(), vec![1, 2]);
+        assert_eq!(journal.seq_nums(), vec![3, 4, 5]);
+        journal.append(6, b"record 6").unwrap();
+        drop(journal);
+
+        let (mut journal, recovery) = Journal::open(&path, &index_path, FsyncPolicy::Never).unwrap();
+        assert_eq!(recovery, Recovery::default());
+        assert!(journal.read(2).unwrap().is_none());
+        assert_eq!(journal.read(3).unwrap().unwrap(), b"record 3");
+        assert_eq!(journal.read(6).unwrap().unwrap(), b"record 6");
+
+        // Without its index the compacted journal is rebuilt by scanning
+        drop(journal);
+        fs::write(&index_path, "").unwrap();
+        let (journal, recovery) = Journal::open(&path, &index_path, FsyncPolicy::Never).unwrap();
+        assert_eq!(recovery.reindexed, 4);
+        assert_eq!(journal.seq_nums(), vec![3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn test_journal_batch_is_all_or_nothing() {
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().join("body");
+        let index_path = temp_dir.path().join("header");
+
+        let (mut journal, _) = Journal::open(&path, &index_path, FsyncPolicy::Never).unwrap();
+        journal.append(1, b"single").unwrap();
+        journal.append_batch(&[(2, b"two".to_vec()), (3, b"three".to_vec())]).unwrap();
+        assert_eq!(journal.read(3).unwrap().unwrap(), b"three");
+        journal.append_batch(&[(4, b"four".to_vec()), (5, b"five".to_vec())]).unwrap();
+        drop(journal);
+
+        // Unindexed complete batches are recovered
+        let index = fs::read_to_string(&index_path).unwrap();
+        fs::write(&index_path, index.lines().next().unwrap().to_string() + "\n").unwrap();
+        let (journal, recovery) = Journal::open(&path, &index_path, FsyncPolicy::Never).unwrap();
+        assert_eq!(recovery, Recovery { reindexed: 4, truncated: 0 });
+        assert_eq!(journal.seq_nums(), vec![1, 2, 3, 4, 5]);
+        drop(journal);
+
+        // A batch torn in its last record loses its first record too
+        fs::write(&index_path, "").unwrap();
+        let body = fs::read(&path).unwrap();
+        fs::write(&path, &body[..body.len() - 1]).unwrap();
+        let (mut journal, recovery) = Journal::open(&path, &index_path, FsyncPolicy::Never).unwrap();
+        assert_eq!(recovery, Recovery { reindexed: 3, truncated: 2 * RECORD_HEADER + 8 - 1 });
+        assert_eq!(journal.seq_nums(), vec![1, 2, 3]);
+        assert_eq!(journal.read(2).unwrap().unwrap(), b"two");
+    }
+}
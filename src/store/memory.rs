@@ -0,0 +1,131 @@
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use chrono::{DateTime, Utc};
+use crate::Result;
+use super::{Direction, MessageStoreBackend};
+
+// Contents of a store, shared with the file store as its cache
+#[derive(Debug)]
+pub(super) struct StoreContents {
+    pub(super) outbound: BTreeMap<u64, Vec<u8>>,
+    pub(super) inbound: BTreeMap<u64, Vec<u8>>,
+    pub(super) next_sender_seq: u64,
+    pub(super) next_target_seq: u64,
+    pub(super) creation_time: DateTime<Utc>,
+}
+
+impl StoreContents {
+    pub(super) fn new() -> Self {
+        StoreContents {
+            outbound: BTreeMap::new(),
+            inbound: BTreeMap::new(),
+            next_sender_seq: 1,
+            next_target_seq: 1,
+            creation_time: Utc::now(),
+        }
+    }
+
+    pub(super) fn messages(&mut self, direction: Direction) -> &mut BTreeMap<u64, Vec<u8>> {
+        match direction {
+            Direction::Outbound => &mut self.outbound,
+            Direction::Inbound => &mut self.inbound,
+        }
+    }
+
+    /// Move the next sender sequence number past `messages`, returning whether it moved
+    pub(super) fn advance_sender_seq(&mut self, messages: &[(u64, Vec<u8>)]) -> bool {
+        match messages.iter().map(|(seq_num, _)| seq_num.saturating_add(1)).max() {
+            Some(next) if next > self.next_sender_seq => {
+                self.next_sender_seq = next;
+                true
+            },
+            _ => false,
+        }
+    }
+
+    pub(super) fn range(&mut self, direction: Direction, begin: u64, end: u64) -> Vec<(u64, Vec<u8>)> {
+        if begin > end {
+            return Vec::new();
+        }
+        self.messages(direction).range(begin..=end).map(|(&seq_num, message)| (seq_num, message.clone())).collect()
+    }
+}
+
+/// Keeps everything in memory, lost when the process exits
+#[derive(Debug)]
+pub struct MemoryStore {
+    contents: Mutex<StoreContents>,
+}
+
+impl Default for MemoryStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        MemoryStore { contents: Mutex::new(StoreContents::new()) }
+    }
+}
+
+impl MessageStoreBackend for MemoryStore {
+    fn store_message(&self, direction: Direction, seq_num: u64, message: &[u8]) -> Result<()> {
+        self.contents.lock().unwrap().messages(direction).insert(seq_num, message.to_vec());
+        Ok(())
+    }
+
+    fn fetch_messages(&self, direction: Direction, begin: u64, end: u64) -> Result<Vec<(u64, Vec<u8>)>> {
+        Ok(self.contents.lock().unwrap().range(direction, begin, end))
+    }
+
+    fn commit_outgoing(&self, messages: &[(u64, Vec<u8>)]) -> Result<()> {
+        let mut contents = self.contents.lock().unwrap();
+        contents.outbound.extend(messages.iter().cloned());
+        contents.advance_sender_seq(messages);
+        Ok(())
+    }
+
+    fn next_sender_seq(&self) -> Result<u64> {
+        Ok(self.contents.lock().unwrap().next_sender_seq)
+    }
+
+    fn next_target_seq(&self) -> Result<u64> {
+        Ok(self.contents.lock().unwrap().next_target_seq)
+    }
+
+    fn set_next_sender_seq(&self, seq_num: u64) -> Result<()> {
+        self.contents.lock().unwrap().next_sender_seq = seq_num;
+        Ok(())
+    }
+
+    fn set_next_target_seq(&self, seq_num: u64) -> Result<()> {
+        self.contents.lock().unwrap().next_target_seq = seq_num;
+        Ok(())
+    }
+
+    fn reset(&self) -> Result<()> {
+        *self.contents.lock().unwrap() = StoreContents::new();
+        Ok(())
+    }
+
+    fn reset_sender(&self) -> Result<()> {
+        let mut contents = self.contents.lock().unwrap();
+        contents.outbound.clear();
+        contents.next_sender_seq = 1;
+        Ok(())
+    }
+
+    fn refresh(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn creation_time(&self) -> Result<DateTime<Utc>> {
+        Ok(self.contents.lock().unwrap().creation_time)
+    }
+
+    /// Nothing is dropped, the messages go with the process
+    fn compact(&self, _resend_floor: u64) -> Result<Vec<u64>> {
+        Ok(Vec::new())
+    }
+}
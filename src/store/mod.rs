@@ -1,54 +1,194 @@
 use std::collections::HashMap;
-use std::sync::Arc;
+use std::sync::{Arc, Weak};
 use tokio::sync::Mutex;
-use std::fs::{self, File, OpenOptions};
-use std::io::{BufRead, BufReader, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use chrono::{DateTime, Utc};
+use crate::config::{FsyncPolicy, RetentionPolicy, StoreKind};
 use crate::message::Message;
+use crate::session::SessionID;
 use crate::Result;
 use crate::error::FixError;
 
+pub mod memory;
+pub mod file;
+pub mod journal;
+pub mod null;
+pub mod retention;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+
+pub use self::file::FileStore;
+pub use self::journal::{Journal, Recovery};
+pub use self::memory::MemoryStore;
+pub use self::null::NullStore;
+#[cfg(feature = "sqlite")]
+pub use self::sqlite::{SqliteStore, StoredMessage};
+
+/// Which way a message went, each direction having its own sequence of stored messages
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Direction {
+    /// Sent by us, kept for answering resend requests
+    Outbound,
+    /// Received from the counterparty, kept for audit if the session asks for it
+    Inbound,
+}
+
+impl Direction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Direction::Outbound => "out",
+            Direction::Inbound => "in",
+        }
+    }
+}
+
+/// Storage of a single session: the messages it sent, for answering resend requests, optionally
+/// those it received, and its sequence numbers. Chosen per session with
+/// [`SessionConfig::store`](crate::config::SessionConfig::store).
+///
+/// Messages are kept as the bytes that went on the wire and handed back unchanged.
+pub trait MessageStoreBackend: Send + Sync {
+    fn store_message(&self, direction: Direction, seq_num: u64, message: &[u8]) -> Result<()>;
+    /// Messages of one direction with sequence numbers from `begin` to `end` inclusive, in
+    /// order. Fails naming the record if one is corrupt.
+    fn fetch_messages(&self, direction: Direction, begin: u64, end: u64) -> Result<Vec<(u64, Vec<u8>)>>;
+
+    fn store_outgoing(&self, seq_num: u64, message: &[u8]) -> Result<()> {
+        self.store_message(Direction::Outbound, seq_num, message)
+    }
+
+    /// Store sent messages together and move the next sender sequence number past the highest
+    /// of them, unless it already is, in one step no other write to the store can come between.
+    /// After a crash all of the messages are kept or none.
+    fn commit_outgoing(&self, messages: &[(u64, Vec<u8>)]) -> Result<()>;
+
+    /// Sent messages from `begin` to `end` inclusive, for resending
+    fn fetch_range(&self, begin: u64, end: u64) -> Result<Vec<(u64, Vec<u8>)>> {
+        self.fetch_messages(Direction::Outbound, begin, end)
+    }
+
+    fn next_sender_seq(&self) -> Result<u64>;
+    fn next_target_seq(&self) -> Result<u64>;
+    fn set_next_sender_seq(&self, seq_num: u64) -> Result<()>;
+    fn set_next_target_seq(&self, seq_num: u64) -> Result<()>;
+    /// Drop every message of both directions, reset both sequence numbers to 1 and restart the
+    /// creation time
+    fn reset(&self) -> Result<()>;
+    /// Drop the sent messages the way [`Self::reset`] does and send from 1 again, keeping the
+    /// received messages and the target sequence number
+    fn reset_sender(&self) -> Result<()>;
+    /// Reload from the underlying storage
+    fn refresh(&self) -> Result<()>;
+    /// When the store was created or last reset
+    fn creation_time(&self) -> Result<DateTime<Utc>>;
+    /// Drop the sent messages the store's [`RetentionPolicy`] no longer keeps, returning their
+    /// sequence numbers. Messages from `resend_floor` on, the lowest sequence number the
+    /// counterparty may still ask to have resent, are always kept.
+    fn compact(&self, resend_floor: u64) -> Result<Vec<u64>>;
+}
+
 // Transaction state for atomic message operations
 #[derive(Debug)]
 struct Transaction {
-    messages: Vec<(i32, Message)>,
+    messages: Vec<(u64, Message)>,
     started: bool,
     version: u64,  // Added version tracking
 }
 
-// Stores FIX messages with persistence and queueing capabilities
+// Messages of a single session keyed by sequence number, with their version
+type SessionMessages = HashMap<u64, (Message, u64)>;
+
+/// Root of the engine's store directory, opening each session's [`MessageStoreBackend`].
+///
+/// It also offers transactions, versioned compare-and-set and compaction over the messages a
+/// session sends. These write through the backend the session opened with [`Self::open`], so
+/// they fail for a session whose store is not open. Committed messages move the backend's next
+/// sender sequence number past them. Versions are only kept in memory: loading a session's
+/// messages gives each of them a fresh version.
 pub struct MessageStore {
     // Messages stored by session and sequence number
-    messages: Arc<Mutex<HashMap<String, HashMap<i32, (Message, u64)>>>>,  // Added version number
-    // Next expected sequence number for each session
-    sequence_numbers: Arc<Mutex<HashMap<String, i32>>>,
+    messages: Arc<Mutex<HashMap<SessionID, SessionMessages>>>,
     // Store directory for persistence
     store_dir: PathBuf,
     // Active transactions
-    transactions: Arc<Mutex<HashMap<String, Transaction>>>,
+    transactions: Arc<Mutex<HashMap<SessionID, Transaction>>>,
     // Version counter for optimistic locking
     version_counter: Arc<Mutex<u64>>,
+    // Backend of each session opened through this store, while it is in use
+    backends: std::sync::Mutex<HashMap<SessionID, Weak<dyn MessageStoreBackend>>>,
+    fsync: FsyncPolicy,
+    retention: RetentionPolicy,
+}
+
+impl Default for MessageStore {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl MessageStore {
+    /// Store under `store` in the working directory, created on first write
     pub fn new() -> Self {
-        let store_dir = PathBuf::from("store");
-        fs::create_dir_all(&store_dir).expect("Failed to create store directory");
-
-        // Create session state directory
-        let session_dir = store_dir.join("sessions");
-        fs::create_dir_all(&session_dir).expect("Failed to create session directory");
+        Self::with_dir(PathBuf::from("store"))
+    }
 
+    pub fn with_dir(store_dir: PathBuf) -> Self {
         MessageStore {
             messages: Arc::new(Mutex::new(HashMap::new())),
-            sequence_numbers: Arc::new(Mutex::new(HashMap::new())),
             store_dir,
             transactions: Arc::new(Mutex::new(HashMap::new())),
             version_counter: Arc::new(Mutex::new(0)),
+            backends: std::sync::Mutex::new(HashMap::new()),
+            fsync: FsyncPolicy::default(),
+            retention: RetentionPolicy::default(),
         }
     }
 
-    pub async fn begin_transaction(&self, session_id: &str) -> Result<()> {
+    /// Set when journal appends are forced to disk
+    pub fn with_fsync(mut self, fsync: FsyncPolicy) -> Self {
+        self.fsync = fsync;
+        self
+    }
+
+    /// Set what compaction drops and whether resets archive the messages first
+    pub fn with_retention(mut self, retention: RetentionPolicy) -> Self {
+        self.retention = retention;
+        self
+    }
+
+    pub fn store_dir(&self) -> &Path {
+        &self.store_dir
+    }
+
+    /// Directory holding everything stored for a session
+    pub fn session_dir(&self, session_id: &SessionID) -> PathBuf {
+        self.store_dir.join(session_id.file_name())
+    }
+
+    /// Open the backend of a session, file stores living in [`Self::session_dir`] and SQLite
+    /// stores sharing `store.db` in the store directory
+    pub fn open(&self, session_id: &SessionID, kind: StoreKind) -> Result<Arc<dyn MessageStoreBackend>> {
+        let backend: Arc<dyn MessageStoreBackend> = match kind {
+            StoreKind::File => Arc::new(FileStore::open(&self.store_dir, session_id, self.fsync)?
+                .with_retention(self.retention.clone())),
+            StoreKind::Memory => Arc::new(MemoryStore::new()),
+            StoreKind::Null => Arc::new(NullStore::new()),
+            #[cfg(feature = "sqlite")]
+            StoreKind::Sqlite => Arc::new(SqliteStore::open(&self.store_dir.join("store.db"), session_id)?
+                .with_retention(self.retention.clone())),
+        };
+        self.backends.lock().unwrap().insert(session_id.clone(), Arc::downgrade(&backend));
+        Ok(backend)
+    }
+
+    // Backend of a session opened with [`Self::open`] and still in use
+    fn backend(&self, session_id: &SessionID) -> Result<Arc<dyn MessageStoreBackend>> {
+        self.backends.lock().unwrap().get(session_id)
+            .and_then(Weak::upgrade)
+            .ok_or_else(|| FixError::StoreError(format!("Store of {} is not open", session_id)))
+    }
+
+    pub async fn begin_transaction(&self, session_id: &SessionID) -> Result<()> {
         let mut transactions = self.transactions.lock().await;
         if transactions.contains_key(session_id) {
             return Err(FixError::StoreError("Transaction already in progress".into()));
@@ -60,7 +200,7 @@ impl MessageStore {
             *counter
         };
 
-        transactions.insert(session_id.to_string(), Transaction {
+        transactions.insert(session_id.clone(), Transaction {
             messages: Vec::new(),
             started: true,
             version,
@@ -69,7 +209,9 @@ impl MessageStore {
         Ok(())
     }
 
-    pub async fn commit_transaction(&self, session_id: &str) -> Result<()> {
+    /// Store the messages of the session's transaction together, see
+    /// [`MessageStoreBackend::commit_outgoing`]
+    pub async fn commit_transaction(&self, session_id: &SessionID) -> Result<()> {
         let mut transactions = self.transactions.lock().await;
         let transaction = transactions.remove(session_id)
             .ok_or_else(|| FixError::StoreError("No transaction in progress".into()))?;
@@ -81,53 +223,22 @@ impl MessageStore {
         // Drop the transactions lock before storing messages
         drop(transactions);
 
-        // Start atomic persistence
-        let file_path = self.store_dir.join(format!("{}.messages", session_id));
-        let temp_path = file_path.with_extension("tmp");
-
-        // Write to temporary file first
-        {
-            let mut temp_file = OpenOptions::new()
-                .create(true)
-                .write(true)
-                .truncate(true)
-                .open(&temp_path)
-                .map_err(|e| FixError::IoError(e))?;
-
-            // Store all messages in the transaction
-            let messages = transaction.messages.clone(); // Clone to avoid move
-            for (seq_num, message) in messages.iter() {
-                let msg_str = message.to_string()?;
-                writeln!(temp_file, "{}|{}|{}", seq_num, transaction.version, msg_str)
-                    .map_err(|e| FixError::IoError(e))?;
-            }
-
-            temp_file.flush().map_err(|e| FixError::IoError(e))?;
-        }
-
-        // Atomic rename of temporary file to actual file
-        fs::rename(&temp_path, &file_path)
-            .map_err(|e| FixError::IoError(e))?;
-
-        // Update in-memory state
-        let mut messages = self.messages.lock().await;
-        let session_messages = messages.entry(session_id.to_string()).or_insert_with(HashMap::new);
-
-        for (seq_num, message) in transaction.messages.iter() {
-            session_messages.insert(*seq_num, (message.clone(), transaction.version));
-        }
-
-        Ok(())
+        let records = transaction.messages.iter()
+            .map(|(seq_num, message)| Ok((*seq_num, message.to_bytes()?)))
+            .collect::<Result<Vec<_>>>()?;
+        let versioned = transaction.messages.into_iter()
+            .map(|(seq_num, message)| (seq_num, message, transaction.version));
+        self.commit(session_id, &records, versioned).await
     }
 
-    pub async fn rollback_transaction(&self, session_id: &str) -> Result<()> {
+    pub async fn rollback_transaction(&self, session_id: &SessionID) -> Result<()> {
         let mut transactions = self.transactions.lock().await;
         transactions.remove(session_id)
             .ok_or_else(|| FixError::StoreError("No transaction in progress".into()))?;
         Ok(())
     }
 
-    pub async fn store_message(&self, session_id: &str, seq_num: i32, message: Message) -> Result<()> {
+    pub async fn store_message(&self, session_id: &SessionID, seq_num: u64, message: Message) -> Result<()> {
         // Check if part of a transaction
         {
             let mut transactions = self.transactions.lock().await;
@@ -144,158 +255,160 @@ impl MessageStore {
             *counter
         };
 
-        // Store in memory with version
-        let mut messages = self.messages.lock().await;
-        let session_messages = messages.entry(session_id.to_string()).or_insert_with(HashMap::new);
-        session_messages.insert(seq_num, (message.clone(), version));
-        drop(messages);
-
-        let mut seq_nums = self.sequence_numbers.lock().await;
-        seq_nums.insert(session_id.to_string(), seq_num + 1);
-        drop(seq_nums);
-
-        // Persist to file with version
-        self.persist_message(session_id, seq_num, &message, version).await?;
-
-        Ok(())
+        let records = [(seq_num, message.to_bytes()?)];
+        self.commit(session_id, &records, [(seq_num, message, version)]).await
     }
 
-    async fn persist_message(&self, session_id: &str, seq_num: i32, message: &Message, version: u64) -> Result<()> {
-        let file_path = self.store_dir.join(format!("{}.messages", session_id));
-        let mut file = OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open(&file_path)
-            .map_err(|e| FixError::IoError(e))?;
-
-        // Format: sequence_number|version|message_string
-        let msg_str = message.to_string()?;
-        writeln!(file, "{}|{}|{}", seq_num, version, msg_str)
-            .map_err(|e| FixError::IoError(e))?;
-
-        // Ensure data is written to disk
-        file.flush()
-            .map_err(|e| FixError::IoError(e))?;
+    // Write `records` through the session's backend, then put their messages in memory. The
+    // messages lock is held throughout, so no other write to the session interleaves.
+    async fn commit(
+        &self,
+        session_id: &SessionID,
+        records: &[(u64, Vec<u8>)],
+        versioned: impl IntoIterator<Item = (u64, Message, u64)>,
+    ) -> Result<()> {
+        let backend = self.backend(session_id)?;
+        let mut messages = self.messages.lock().await;
+        backend.commit_outgoing(records)?;
 
+        let session_messages = messages.entry(session_id.clone()).or_insert_with(HashMap::new);
+        for (seq_num, message, version) in versioned {
+            session_messages.insert(seq_num, (message, version));
+        }
         Ok(())
     }
 
-    pub async fn get_message(&self, session_id: &str, seq_num: i32) -> Result<Option<Message>> {
+    pub async fn get_message(&self, session_id: &SessionID, seq_num: u64) -> Result<Option<Message>> {
         let messages = self.messages.lock().await;
         Ok(messages.get(session_id)
             .and_then(|session_msgs| session_msgs.get(&seq_num))
             .map(|(msg, _)| msg.clone()))
     }
 
-    pub async fn get_message_with_version(&self, session_id: &str, seq_num: i32) -> Result<Option<(Message, u64)>> {
+    pub async fn get_message_with_version(&self, session_id: &SessionID, seq_num: u64) -> Result<Option<(Message, u64)>> {
         let messages = self.messages.lock().await;
         Ok(messages.get(session_id)
             .and_then(|session_msgs| session_msgs.get(&seq_num))
             .map(|(msg, ver)| (msg.clone(), *ver)))
     }
 
-    pub async fn get_messages_range(&self, session_id: &str, start: i32, end: i32) -> Result<Vec<Message>> {
+    /// Replace a stored message only if it is still at `expected_version`, as read with
+    /// [`Self::get_message_with_version`]; a version of 0 expects no message at all. Returns
+    /// the new version, or [`FixError::VersionConflict`] if the message changed meanwhile.
+    /// The new message is written through the session's backend, so it survives a reload.
+    pub async fn compare_and_set(
+        &self,
+        session_id: &SessionID,
+        seq_num: u64,
+        expected_version: u64,
+        message: Message,
+    ) -> Result<u64> {
+        let backend = self.backend(session_id)?;
+        let mut messages = self.messages.lock().await;
+        let current = messages.get(session_id)
+            .and_then(|session_msgs| session_msgs.get(&seq_num))
+            .map_or(0, |(_, version)| *version);
+        if current != expected_version {
+            return Err(FixError::VersionConflict(format!(
+                "message {} of {} is at version {}, expected {}", seq_num, session_id, current, expected_version
+            )));
+        }
+
+        let version = {
+            let mut counter = self.version_counter.lock().await;
+            *counter += 1;
+            *counter
+        };
+        backend.commit_outgoing(&[(seq_num, message.to_bytes()?)])?;
+        messages.entry(session_id.clone()).or_insert_with(HashMap::new).insert(seq_num, (message, version));
+        Ok(version)
+    }
+
+    pub async fn get_messages_range(&self, session_id: &SessionID, start: u64, end: u64) -> Result<Vec<Message>> {
         let messages = self.messages.lock().await;
         let mut result = Vec::new();
 
         if let Some(session_msgs) = messages.get(session_id) {
-            for seq_num in start..=end {
-                if let Some((msg, _)) = session_msgs.get(&seq_num) {
-                    result.push(msg.clone());
-                }
-            }
+            // Sequence numbers can be far apart, so walk the messages rather than the range
+            let mut seq_nums: Vec<u64> = session_msgs.keys().copied().filter(|seq_num| (start..=end).contains(seq_num)).collect();
+            seq_nums.sort_unstable();
+            result.extend(seq_nums.iter().map(|seq_num| session_msgs[seq_num].0.clone()));
         }
 
         Ok(result)
     }
 
-    pub async fn get_next_seq_num(&self, session_id: &str) -> Result<i32> {
-        let seq_nums = self.sequence_numbers.lock().await;
-        Ok(*seq_nums.get(session_id).unwrap_or(&1))
+    /// Reset the session's backend, see [`MessageStoreBackend::reset`], and drop its messages
+    /// from memory
+    pub async fn reset_messages(&self, session_id: &SessionID) -> Result<()> {
+        let backend = self.backend(session_id)?;
+        let mut messages = self.messages.lock().await;
+        backend.reset()?;
+        messages.remove(session_id);
+        Ok(())
     }
 
-    pub async fn reset_sequence_numbers(&self, session_id: &str) -> Result<()> {
-        // Reset in-memory sequence number
-        let mut seq_nums = self.sequence_numbers.lock().await;
-        seq_nums.insert(session_id.to_string(), 1);
-        drop(seq_nums);
+    /// Compact the backend of an open session, see [`MessageStoreBackend::compact`], returning
+    /// how many messages were dropped
+    pub async fn compact(&self, session_id: &SessionID, resend_floor: u64) -> Result<usize> {
+        let dropped = self.backend(session_id)?.compact(resend_floor)?;
 
-        // Clear session messages from memory
         let mut messages = self.messages.lock().await;
-        messages.remove(session_id);
-        drop(messages);
-
-        // Truncate the message file for this session
-        let file_path = self.store_dir.join(format!("{}.messages", session_id));
-        if file_path.exists() {
-            OpenOptions::new()
-                .write(true)
-                .truncate(true)
-                .open(file_path)
-                .map_err(|e| FixError::IoError(e))?;
+        if let Some(session_messages) = messages.get_mut(session_id) {
+            for seq_num in &dropped {
+                session_messages.remove(seq_num);
+            }
         }
-
-        Ok(())
+        Ok(dropped.len())
     }
 
-    pub async fn load_messages(&self, session_id: &str) -> Result<()> {
-        let file_path = self.store_dir.join(format!("{}.messages", session_id));
-        if !file_path.exists() {
-            return Ok(());
-        }
+    /// Load the messages the session's backend holds, each at a new version. Messages that do
+    /// not parse are reported by sequence number once every other one has been loaded; a
+    /// corrupt record fails the load with the backend's error, which names it.
+    pub async fn load_messages(&self, session_id: &SessionID) -> Result<()> {
+        let records = self.backend(session_id)?.fetch_range(1, u64::MAX)?;
 
-        let file = File::open(&file_path)
-            .map_err(|e| FixError::IoError(e))?;
-        let reader = BufReader::new(file);
         let mut messages = self.messages.lock().await;
-        let session_messages = messages.entry(session_id.to_string()).or_insert_with(HashMap::new);
-        let mut max_seq = 0;
-        let mut max_version = 0;
-
-        for line in reader.lines() {
-            let line = line.map_err(|e| FixError::IoError(e))?;
-            let parts: Vec<&str> = line.split('|').collect();
-            if parts.len() != 3 {
+        let mut counter = self.version_counter.lock().await;
+        let session_messages = messages.entry(session_id.clone()).or_insert_with(HashMap::new);
+        let mut corrupted = Vec::new();
+        for (seq_num, record) in records {
+            let Ok(message) = Message::from_bytes(&record) else {
+                corrupted.push(seq_num);
                 continue;
-            }
-
-            if let (Ok(seq_num), Ok(version), Ok(message)) = (
-                parts[0].parse::<i32>(),
-                parts[1].parse::<u64>(),
-                Message::from_string(parts[2])
-            ) {
-                session_messages.insert(seq_num, (message, version));
-                max_seq = max_seq.max(seq_num);
-                max_version = max_version.max(version);
-            }
+            };
+            *counter += 1;
+            session_messages.insert(seq_num, (message, *counter));
         }
 
-        // Update sequence number and version counter
-        drop(messages);
-        if max_seq > 0 {
-            let mut seq_nums = self.sequence_numbers.lock().await;
-            seq_nums.insert(session_id.to_string(), max_seq + 1);
-        }
-        if max_version > 0 {
-            let mut version = self.version_counter.lock().await;
-            *version = max_version;
+        if !corrupted.is_empty() {
+            return Err(FixError::StoreError(format!(
+                "Corrupted records in the store of {}: {:?}", session_id, corrupted
+            )));
         }
-
         Ok(())
     }
+
+    /// A stored message exactly as it was serialized, for resending
+    pub fn get_raw_message(&self, session_id: &SessionID, seq_num: u64) -> Result<Option<Vec<u8>>> {
+        Ok(self.backend(session_id)?.fetch_range(seq_num, seq_num)?.pop().map(|(_, message)| message))
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::message::{Field, field};
+    use std::fs;
     use tempfile::tempdir;
     use tokio::time::{timeout, Duration};
 
     #[tokio::test]
     async fn test_message_transaction() {
-        let store = MessageStore::new();
-        let session_id = "TEST_SESSION";
+        let temp_dir = tempdir().unwrap();
+        let store = MessageStore::with_dir(temp_dir.path().to_path_buf());
+        let session_id = &SessionID::new("FIX.4.2", "TEST", "SESSION");
+        let backend = store.open(session_id, StoreKind::File).unwrap();
 
         let test = async {
             // Begin transaction
@@ -326,6 +439,10 @@ mod tests {
             assert_eq!(msg1_stored.get_field(field::CL_ORD_ID).unwrap().value(), "12345");
             assert_eq!(msg2_stored.get_field(field::CL_ORD_ID).unwrap().value(), "12346");
             assert_eq!(ver1, ver2);  // Same transaction, same version
+
+            // Committed through the session's store, which now sends from 3
+            assert_eq!(backend.fetch_range(1, 2).unwrap().len(), 2);
+            assert_eq!(backend.next_sender_seq().unwrap(), 3);
         };
 
         timeout(Duration::from_secs(5), test).await.unwrap();
@@ -333,8 +450,10 @@ mod tests {
 
     #[tokio::test]
     async fn test_message_transaction_rollback() {
-        let store = MessageStore::new();
-        let session_id = "TEST_SESSION";
+        let temp_dir = tempdir().unwrap();
+        let store = MessageStore::with_dir(temp_dir.path().to_path_buf());
+        let session_id = &SessionID::new("FIX.4.2", "TEST", "SESSION");
+        let backend = store.open(session_id, StoreKind::File).unwrap();
 
         let test = async {
             // Begin transaction
@@ -351,6 +470,8 @@ mod tests {
             // Verify message was not stored
             let message = store.get_message(session_id, 1).await.unwrap();
             assert!(message.is_none());
+            assert!(backend.fetch_range(1, 1).unwrap().is_empty());
+            assert_eq!(backend.next_sender_seq().unwrap(), 1);
         };
 
         timeout(Duration::from_secs(5), test).await.unwrap();
@@ -359,17 +480,16 @@ mod tests {
     #[tokio::test]
     async fn test_message_persistence() {
         let temp_dir = tempdir().unwrap();
-        let store = MessageStore {
-            messages: Arc::new(Mutex::new(HashMap::new())),
-            sequence_numbers: Arc::new(Mutex::new(HashMap::new())),
-            store_dir: temp_dir.path().to_path_buf(),
-            transactions: Arc::new(Mutex::new(HashMap::new())),
-            version_counter: Arc::new(Mutex::new(0)),
-        };
+        let store = MessageStore::with_dir(temp_dir.path().to_path_buf());
 
-        let session_id = "TEST_SESSION";
+        let session_id = &SessionID::new("FIX.4.2", "TEST", "SESSION");
 
         let test = async {
+            // Nothing can be stored until the session's store is open
+            let err = store.store_message(session_id, 1, Message::new(field::values::HEARTBEAT)).await.unwrap_err();
+            assert!(matches!(err, FixError::StoreError(_)));
+            let backend = store.open(session_id, StoreKind::File).unwrap();
+
             // Begin transaction
             store.begin_transaction(session_id).await.unwrap();
 
@@ -383,15 +503,11 @@ mod tests {
 
             // Commit transaction
             store.commit_transaction(session_id).await.unwrap();
+            drop(backend);
 
             // Create new store instance and load messages
-            let new_store = MessageStore {
-                messages: Arc::new(Mutex::new(HashMap::new())),
-                sequence_numbers: Arc::new(Mutex::new(HashMap::new())),
-                store_dir: temp_dir.path().to_path_buf(),
-                transactions: Arc::new(Mutex::new(HashMap::new())),
-                version_counter: Arc::new(Mutex::new(0)),
-            };
+            let new_store = MessageStore::with_dir(temp_dir.path().to_path_buf());
+            let _backend = new_store.open(session_id, StoreKind::File).unwrap();
 
             new_store.load_messages(session_id).await.unwrap();
 
@@ -402,9 +518,454 @@ mod tests {
             let (loaded_msg, loaded_ver) = loaded.unwrap();
             assert_eq!(loaded_msg.msg_type(), field::values::NEW_ORDER_SINGLE);
             assert_eq!(loaded_msg.get_field(field::CL_ORD_ID).unwrap().value(), "12345");
-            assert_eq!(loaded_ver, 1);  // First version should be 1
+            assert_eq!(loaded_ver, 1);  // Versions start afresh on loading
         };
 
         timeout(Duration::from_secs(5), test).await.unwrap();
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_transaction_appends_to_journal() {
+        let temp_dir = tempdir().unwrap();
+        let store = MessageStore::with_dir(temp_dir.path().to_path_buf());
+        let session_id = &SessionID::new("FIX.4.2", "TEST", "APPEND");
+        let backend = store.open(session_id, StoreKind::File).unwrap();
+
+        store.store_message(session_id, 1, Message::new(field::values::HEARTBEAT)).await.unwrap();
+        store.begin_transaction(session_id).await.unwrap();
+        store.store_message(session_id, 2, Message::new(field::values::NEW_ORDER_SINGLE)).await.unwrap();
+        store.commit_transaction(session_id).await.unwrap();
+        drop(backend);
+        drop(store);
+
+        // Committing keeps what was stored before the transaction
+        let reloaded = MessageStore::with_dir(temp_dir.path().to_path_buf());
+        let backend = reloaded.open(session_id, StoreKind::File).unwrap();
+        assert_eq!(backend.next_sender_seq().unwrap(), 3);
+        reloaded.load_messages(session_id).await.unwrap();
+        assert_eq!(reloaded.get_messages_range(session_id, 1, 2).await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_transactions() {
+        let temp_dir = tempdir().unwrap();
+        let store = Arc::new(MessageStore::with_dir(temp_dir.path().to_path_buf()));
+        let first = SessionID::new("FIX.4.2", "TEST", "FIRST");
+        let second = SessionID::new("FIX.4.2", "TEST", "SECOND");
+        let backends = [store.open(&first, StoreKind::File).unwrap(), store.open(&second, StoreKind::File).unwrap()];
+
+        // Transactions of different sessions run side by side
+        store.begin_transaction(&first).await.unwrap();
+        store.begin_transaction(&second).await.unwrap();
+        assert!(store.begin_transaction(&first).await.is_err());
+        let tasks: Vec<_> = [(first.clone(), 3), (second.clone(), 5)].into_iter().map(|(session_id, count)| {
+            let store = store.clone();
+            tokio::spawn(async move {
+                for seq_num in 1..=count {
+                    store.store_message(&session_id, seq_num, Message::new(field::values::NEW_ORDER_SINGLE)).await.unwrap();
+                }
+                // Nothing is visible until the commit
+                assert!(store.get_message(&session_id, 1).await.unwrap().is_none());
+                store.commit_transaction(&session_id).await.unwrap();
+            })
+        }).collect();
+        for task in tasks {
+            task.await.unwrap();
+        }
+        assert_eq!(store.get_messages_range(&first, 1, 10).await.unwrap().len(), 3);
+        assert_eq!(store.get_messages_range(&second, 1, 10).await.unwrap().len(), 5);
+
+        // A rolled back transaction leaves no trace
+        store.begin_transaction(&first).await.unwrap();
+        store.store_message(&first, 4, Message::new(field::values::HEARTBEAT)).await.unwrap();
+        store.rollback_transaction(&first).await.unwrap();
+        assert!(store.get_message(&first, 4).await.unwrap().is_none());
+        assert_eq!(store.get_messages_range(&first, 1, 10).await.unwrap().len(), 3);
+        assert_eq!(backends[0].next_sender_seq().unwrap(), 4);
+        drop(backends);
+        drop(store);
+
+        let reloaded = MessageStore::with_dir(temp_dir.path().to_path_buf());
+        let _backend = reloaded.open(&second, StoreKind::File).unwrap();
+        reloaded.load_messages(&second).await.unwrap();
+        assert_eq!(reloaded.get_messages_range(&second, 1, 5).await.unwrap().len(), 5);
+    }
+
+    #[tokio::test]
+    async fn test_compare_and_set() {
+        let temp_dir = tempdir().unwrap();
+        let store = MessageStore::with_dir(temp_dir.path().to_path_buf());
+        let session_id = &SessionID::new("FIX.4.2", "TEST", "CAS");
+        let backend = store.open(session_id, StoreKind::File).unwrap();
+
+        let first = store.compare_and_set(session_id, 1, 0, Message::new(field::values::NEW_ORDER_SINGLE)).await.unwrap();
+        let err = store.compare_and_set(session_id, 1, 0, Message::new(field::values::HEARTBEAT)).await.unwrap_err();
+        assert!(matches!(err, FixError::VersionConflict(_)));
+
+        let (_, version) = store.get_message_with_version(session_id, 1).await.unwrap().unwrap();
+        assert_eq!(version, first);
+        let mut replacement = Message::new(field::values::NEW_ORDER_SINGLE);
+        replacement.set_field(Field::new(field::CL_ORD_ID, "REPLACED")).unwrap();
+        let second = store.compare_and_set(session_id, 1, version, replacement).await.unwrap();
+        assert!(second > first);
+        assert!(store.compare_and_set(session_id, 1, first, Message::new(field::values::HEARTBEAT)).await.is_err());
+        assert_eq!(backend.next_sender_seq().unwrap(), 2);
+        drop(backend);
+        drop(store);
+
+        // The latest message is what reloads, at a version of its own
+        let reloaded = MessageStore::with_dir(temp_dir.path().to_path_buf());
+        let _backend = reloaded.open(session_id, StoreKind::File).unwrap();
+        reloaded.load_messages(session_id).await.unwrap();
+        let (message, version) = reloaded.get_message_with_version(session_id, 1).await.unwrap().unwrap();
+        assert_eq!(message.get_field(field::CL_ORD_ID).unwrap().value(), "REPLACED");
+        assert!(reloaded.compare_and_set(session_id, 1, version, Message::new(field::values::HEARTBEAT)).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_lossless_reload() {
+        let temp_dir = tempdir().unwrap();
+        let store = MessageStore::with_dir(temp_dir.path().to_path_buf());
+        let session_id = &SessionID::new("FIX.4.2", "TEST", "LOSSLESS");
+        let backend = store.open(session_id, StoreKind::File).unwrap();
+
+        // Text with the old line format's separators survives a reload byte for byte
+        let mut msg = Message::new(field::values::NEW_ORDER_SINGLE);
+        msg.set_field(Field::new(field::TEXT, "left|right\nnext line")).unwrap();
+        let sent = msg.to_bytes().unwrap();
+        store.store_message(session_id, 1, msg).await.unwrap();
+        store.store_message(session_id, 2, Message::new(field::values::HEARTBEAT)).await.unwrap();
+        drop(backend);
+        drop(store);
+
+        let reloaded = MessageStore::with_dir(temp_dir.path().to_path_buf());
+        let backend = reloaded.open(session_id, StoreKind::File).unwrap();
+        reloaded.load_messages(session_id).await.unwrap();
+        assert_eq!(reloaded.get_raw_message(session_id, 1).unwrap().unwrap(), sent);
+        let loaded = reloaded.get_message(session_id, 1).await.unwrap().unwrap();
+        assert_eq!(loaded.get_field(field::TEXT).unwrap().value(), "left|right\nnext line");
+        assert_eq!(loaded.to_bytes().unwrap(), sent);
+        drop(backend);
+        drop(reloaded);
+
+        // A damaged record is reported rather than skipped
+        let path = temp_dir.path().join(session_id.file_name()).join("body");
+        let mut bytes = fs::read(&path).unwrap();
+        bytes[20] ^= 0xff;
+        fs::write(&path, bytes).unwrap();
+        let damaged = MessageStore::with_dir(temp_dir.path().to_path_buf());
+        let _backend = damaged.open(session_id, StoreKind::File).unwrap();
+        let err = damaged.load_messages(session_id).await.unwrap_err();
+        assert!(err.to_string().contains("record 1 "), "{}", err);
+        assert!(damaged.get_raw_message(session_id, 1).is_err());
+        assert!(damaged.get_raw_message(session_id, 2).unwrap().is_some());
+    }
+
+    // A message that went out at `sent`
+    fn sent_message(cl_ord_id: &str, sent: DateTime<Utc>) -> Message {
+        let mut msg = Message::new(field::values::NEW_ORDER_SINGLE);
+        msg.set_field(Field::new(field::CL_ORD_ID, cl_ord_id)).unwrap();
+        msg.set_field(Field::new(field::SENDING_TIME, sent.format("%Y%m%d-%H:%M:%S%.3f").to_string())).unwrap();
+        msg
+    }
+
+    #[test]
+    fn test_compaction_keeps_resend_window() {
+        let temp_dir = tempdir().unwrap();
+        let session_id = &SessionID::new("FIX.4.2", "TEST", "COMPACT");
+        let store = MessageStore::with_dir(temp_dir.path().to_path_buf()).with_retention(RetentionPolicy {
+            max_age_secs: Some(3600),
+            max_messages: Some(4),
+            ..RetentionPolicy::default()
+        });
+        let backend = store.open(session_id, StoreKind::File).unwrap();
+
+        // 1-2 are a day old, 3-8 recent
+        let old = Utc::now() - chrono::Duration::days(1);
+        for seq_num in 1..=8 {
+            let sent = if seq_num <= 2 { old } else { Utc::now() };
+            backend.store_outgoing(seq_num, &sent_message(&format!("ORDER{}", seq_num), sent).to_bytes().unwrap()).unwrap();
+        }
+
+        // The counterparty may still ask for everything from 2 on
+        assert_eq!(backend.compact(2).unwrap(), vec![1]);
+        assert!(backend.fetch_range(1, 1).unwrap().is_empty());
+        assert_eq!(backend.fetch_range(2, 2).unwrap().len(), 1);
+
+        // Once it has everything up to 6, age and count both apply
+        assert_eq!(backend.compact(7).unwrap(), vec![2, 3, 4]);
+        let kept: Vec<_> = backend.fetch_range(1, 8).unwrap().into_iter().map(|(seq_num, _)| seq_num).collect();
+        assert_eq!(kept, vec![5, 6, 7, 8]);
+        drop(backend);
+
+        let reopened = store.open(session_id, StoreKind::File).unwrap();
+        assert_eq!(reopened.fetch_range(1, 8).unwrap().len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_compaction_without_count() {
+        let temp_dir = tempdir().unwrap();
+        let session_id = &SessionID::new("FIX.4.2", "TEST", "COMPACT_ALL");
+        let store = MessageStore::with_dir(temp_dir.path().to_path_buf()).with_retention(RetentionPolicy {
+            max_messages: Some(0),
+            ..RetentionPolicy::default()
+        });
+        assert!(store.compact(session_id, 1).await.is_err());
+        let backend = store.open(session_id, StoreKind::File).unwrap();
+        for seq_num in 1..=4 {
+            backend.store_outgoing(seq_num, &sent_message(&format!("ORDER{}", seq_num), Utc::now()).to_bytes().unwrap()).unwrap();
+        }
+
+        // Everything the counterparty can no longer ask for is dropped
+        assert_eq!(store.compact(session_id, 3).await.unwrap(), 2);
+        assert_eq!(backend.fetch_range(1, 4).unwrap().len(), 2);
+        assert_eq!(store.compact(session_id, 5).await.unwrap(), 2);
+        assert!(backend.fetch_range(1, 4).unwrap().is_empty());
+
+        // Stores that keep nothing on disk have nothing to compact
+        let memory = store.open(&SessionID::new("FIX.4.2", "TEST", "COMPACT_MEMORY"), StoreKind::Memory).unwrap();
+        memory.store_outgoing(1, &sent_message("ORDER1", Utc::now()).to_bytes().unwrap()).unwrap();
+        assert!(memory.compact(5).unwrap().is_empty());
+        assert_eq!(memory.fetch_range(1, 1).unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_archive_on_reset() {
+        let temp_dir = tempdir().unwrap();
+        let session_id = &SessionID::new("FIX.4.2", "TEST", "ARCHIVE");
+        let retention = RetentionPolicy { archive_on_reset: true, max_archives: Some(2), ..RetentionPolicy::default() };
+        let store = MessageStore::with_dir(temp_dir.path().to_path_buf()).with_retention(retention.clone());
+        let archive_dir = store.session_dir(session_id).join("archive");
+        let archives = || {
+            let mut names: Vec<_> = fs::read_dir(&archive_dir).unwrap().map(|entry| entry.unwrap().path()).collect();
+            names.sort();
+            names
+        };
+
+        let backend = store.open(session_id, StoreKind::File).unwrap();
+        let msg = sent_message("ARCHIVED", Utc::now());
+        store.store_message(session_id, 1, msg.clone()).await.unwrap();
+        store.reset_messages(session_id).await.unwrap();
+        let first = archives();
+        assert_eq!(first.len(), 1);
+        let name = first[0].file_name().unwrap().to_str().unwrap();
+        assert!(name.starts_with(&format!("{}-{}", session_id.file_name(), Utc::now().format("%Y%m%d"))), "{}", name);
+        assert_eq!(retention::read_archive(&first[0]).unwrap(), vec![msg.to_bytes().unwrap()]);
+
+        // Nothing to archive after a reset, then only the newest two are kept
+        store.reset_messages(session_id).await.unwrap();
+        assert_eq!(archives().len(), 1);
+        for seq_num in 1..=2 {
+            store.store_message(session_id, seq_num, msg.clone()).await.unwrap();
+            tokio::time::sleep(Duration::from_millis(5)).await;
+            store.reset_messages(session_id).await.unwrap();
+        }
+        let rolled = archives();
+        assert_eq!(rolled.len(), 2);
+        assert!(!rolled.contains(&first[0]));
+        assert_eq!(backend.next_sender_seq().unwrap(), 1);
+        drop(backend);
+        drop(store);
+
+        // Archives carry on in a new store, messages with newlines included, and
+        // resets in the same millisecond do not overwrite each other
+        let backend = MessageStore::with_dir(temp_dir.path().to_path_buf())
+            .with_retention(RetentionPolicy { max_archives: None, ..retention })
+            .open(session_id, StoreKind::File).unwrap();
+        let mut multiline = sent_message("MULTILINE", Utc::now());
+        multiline.set_field(Field::new(field::TEXT, "first\nsecond")).unwrap();
+        let batch = [msg.to_bytes().unwrap(), multiline.to_bytes().unwrap()];
+        for _ in 0..3 {
+            backend.store_outgoing(1, &batch[0]).unwrap();
+            backend.store_outgoing(2, &batch[1]).unwrap();
+            backend.reset().unwrap();
+        }
+        let latest = archives();
+        assert_eq!(latest.len(), 5);
+        for path in &latest[2..] {
+            assert_eq!(retention::read_archive(path).unwrap(), batch);
+        }
+    }
+
+    // The behaviour every backend shares
+    fn check_backend(store: &dyn MessageStoreBackend, keeps_messages: bool) {
+        let mut msg = Message::new(field::values::NEW_ORDER_SINGLE);
+        msg.set_field(Field::new(field::CL_ORD_ID, "ORDER1")).unwrap();
+        msg.set_field(Field::new(field::TEXT, "a|b\nc")).unwrap();
+        let sent = msg.to_bytes().unwrap();
+        store.store_outgoing(1, &sent).unwrap();
+        store.store_outgoing(2, &Message::new(field::values::HEARTBEAT).to_bytes().unwrap()).unwrap();
+        // Received messages share sequence numbers with sent ones without replacing them
+        let received = Message::new(field::values::EXECUTION_REPORT).to_bytes().unwrap();
+        store.store_message(Direction::Inbound, 1, &received).unwrap();
+        store.set_next_sender_seq(3).unwrap();
+        store.set_next_target_seq(5).unwrap();
+
+        let fetched = store.fetch_range(1, 10).unwrap();
+        if keeps_messages {
+            assert_eq!(fetched.len(), 2);
+            assert_eq!(fetched[0], (1, sent.clone()));
+            assert_eq!(Message::from_bytes(&fetched[1].1).unwrap().msg_type(), field::values::HEARTBEAT);
+            assert_eq!(store.fetch_range(2, 2).unwrap().len(), 1);
+            assert_eq!(store.fetch_messages(Direction::Inbound, 1, 10).unwrap(), vec![(1, received.clone())]);
+        } else {
+            assert!(fetched.is_empty());
+            assert!(store.fetch_messages(Direction::Inbound, 1, 10).unwrap().is_empty());
+        }
+        assert_eq!(store.next_sender_seq().unwrap(), 3);
+        assert_eq!(store.next_target_seq().unwrap(), 5);
+
+        let created = store.creation_time().unwrap();
+        store.reset().unwrap();
+        assert!(store.fetch_range(1, 10).unwrap().is_empty());
+        assert!(store.fetch_messages(Direction::Inbound, 1, 10).unwrap().is_empty());
+        assert_eq!(store.next_sender_seq().unwrap(), 1);
+        assert_eq!(store.next_target_seq().unwrap(), 1);
+        assert!(store.creation_time().unwrap() >= created);
+
+        // A committed batch moves the next sender sequence number past it, never back
+        store.commit_outgoing(&[(1, sent.clone()), (2, sent.clone())]).unwrap();
+        assert_eq!(store.next_sender_seq().unwrap(), 3);
+        assert_eq!(store.fetch_range(1, 2).unwrap().len(), if keeps_messages { 2 } else { 0 });
+        store.set_next_sender_seq(10).unwrap();
+        store.commit_outgoing(&[(2, sent)]).unwrap();
+        assert_eq!(store.next_sender_seq().unwrap(), 10);
+
+        // Resetting the sender leaves the received side alone
+        store.store_message(Direction::Inbound, 1, &received).unwrap();
+        store.set_next_target_seq(5).unwrap();
+        store.reset_sender().unwrap();
+        assert!(store.fetch_range(1, 10).unwrap().is_empty());
+        assert_eq!(store.fetch_messages(Direction::Inbound, 1, 10).unwrap().len(), if keeps_messages { 1 } else { 0 });
+        assert_eq!(store.next_sender_seq().unwrap(), 1);
+        assert_eq!(store.next_target_seq().unwrap(), 5);
+        store.reset().unwrap();
+    }
+
+    #[test]
+    fn test_backends() {
+        let temp_dir = tempdir().unwrap();
+        let session_id = &SessionID::new("FIX.4.2", "TEST", "BACKEND");
+        let store = MessageStore::with_dir(temp_dir.path().to_path_buf());
+
+        check_backend(store.open(session_id, StoreKind::Memory).unwrap().as_ref(), true);
+        check_backend(store.open(session_id, StoreKind::Null).unwrap().as_ref(), false);
+        check_backend(store.open(session_id, StoreKind::File).unwrap().as_ref(), true);
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_sqlite_store() {
+        let temp_dir = tempdir().unwrap();
+        let store = MessageStore::with_dir(temp_dir.path().to_path_buf());
+        let session_id = &SessionID::new("FIX.4.2", "TEST", "SQLITE");
+        check_backend(store.open(session_id, StoreKind::Sqlite).unwrap().as_ref(), true);
+
+        // Two sessions in one database, found together by ClOrdID
+        let first = SqliteStore::open(&temp_dir.path().join("store.db"), session_id).unwrap();
+        let other_id = &SessionID::new("FIX.4.2", "TEST", "OTHER");
+        let other = SqliteStore::open(&temp_dir.path().join("store.db"), other_id).unwrap();
+        let mut msg = Message::new(field::values::NEW_ORDER_SINGLE);
+        msg.set_field(Field::new(field::CL_ORD_ID, "SHARED")).unwrap();
+        msg.set_field(Field::new(field::SENDING_TIME, "20240101-10:00:00.000")).unwrap();
+        first.store_outgoing(1, &msg.to_bytes().unwrap()).unwrap();
+        other.store_outgoing(4, &msg.to_bytes().unwrap()).unwrap();
+        other.set_next_sender_seq(5).unwrap();
+
+        let found = first.find_by_cl_ord_id("SHARED").unwrap();
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0].session_id, session_id.to_string());
+        assert_eq!(found[1].seq_num, 4);
+        assert_eq!(found[1].msg_type.as_deref(), Some(field::values::NEW_ORDER_SINGLE));
+        assert_eq!(found[1].time, "20240101-10:00:00.000");
+        assert_eq!(first.next_sender_seq().unwrap(), 1);
+        drop(other);
+
+        // Everything survives reopening, resets only touch their own session
+        let reopened = SqliteStore::open(&temp_dir.path().join("store.db"), other_id).unwrap();
+        assert_eq!(reopened.next_sender_seq().unwrap(), 5);
+        first.reset().unwrap();
+        assert_eq!(reopened.fetch_range(1, 10).unwrap(), vec![(4, msg.to_bytes().unwrap())]);
+        assert_eq!(reopened.find_by_cl_ord_id("SHARED").unwrap().len(), 1);
+        drop(reopened);
+
+        // Compaction drops by count below the resend floor, leaving received messages alone
+        let retention = RetentionPolicy { max_messages: Some(1), ..RetentionPolicy::default() };
+        let compacted = SqliteStore::open(&temp_dir.path().join("store.db"), other_id).unwrap().with_retention(retention);
+        for seq_num in 1..=3 {
+            compacted.store_outgoing(seq_num, &msg.to_bytes().unwrap()).unwrap();
+        }
+        compacted.store_message(Direction::Inbound, 1, &msg.to_bytes().unwrap()).unwrap();
+        assert_eq!(compacted.compact(4).unwrap(), vec![1, 2, 3]);
+        assert_eq!(compacted.fetch_range(1, 10).unwrap().len(), 1);
+        assert_eq!(compacted.fetch_messages(Direction::Inbound, 1, 10).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_file_store_layout_and_lock() {
+        let temp_dir = tempdir().unwrap();
+        let store = MessageStore::with_dir(temp_dir.path().to_path_buf());
+        let session_id = &SessionID::new("FIX.4.2", "TEST", "LAYOUT");
+
+        let backend = store.open(session_id, StoreKind::File).unwrap();
+        backend.store_outgoing(1, b"8=FIX.4.2\x019=5\x0135=0\x0110=161\x01").unwrap();
+        let dir = store.session_dir(session_id);
+        for file in ["body", "header", "inbound.body", "inbound.header", "seqnums", "session", "lock"] {
+            assert!(dir.join(file).exists(), "{}", file);
+        }
+        assert!(fs::read_to_string(dir.join("header")).unwrap().starts_with("1,0,"));
+
+        // Only one opener at a time
+        let err = store.open(session_id, StoreKind::File).err().unwrap();
+        assert!(matches!(err, FixError::StoreError(_)));
+        drop(backend);
+        assert!(store.open(session_id, StoreKind::File).is_ok());
+    }
+
+    #[test]
+    fn test_file_store_reopen() {
+        let temp_dir = tempdir().unwrap();
+        let session_id = &SessionID::new("FIX.4.2", "TEST", "REOPEN");
+
+        let store = FileStore::open(temp_dir.path(), session_id, FsyncPolicy::Always).unwrap();
+        let mut msg = Message::new(field::values::NEW_ORDER_SINGLE);
+        msg.set_field(Field::new(field::CL_ORD_ID, "ORDER7")).unwrap();
+        let sent = msg.to_bytes().unwrap();
+        store.store_outgoing(7, &sent).unwrap();
+        store.set_next_sender_seq(8).unwrap();
+        store.set_next_target_seq(4).unwrap();
+        let created = store.creation_time().unwrap();
+        drop(store);
+
+        let reopened = FileStore::open(temp_dir.path(), session_id, FsyncPolicy::Always).unwrap();
+        let fetched = reopened.fetch_range(1, 7).unwrap();
+        assert_eq!(fetched, vec![(7, sent)]);
+        assert_eq!(reopened.next_sender_seq().unwrap(), 8);
+        assert_eq!(reopened.next_target_seq().unwrap(), 4);
+        assert_eq!(reopened.creation_time().unwrap().timestamp_millis(), created.timestamp_millis());
+        drop(reopened);
+
+        // Sequence numbers are written behind, here only on closing, yet never lost by a reopen
+        let unsynced = FileStore::open(temp_dir.path(), session_id, FsyncPolicy::Never).unwrap();
+        unsynced.set_next_sender_seq(20).unwrap();
+        let seqnums = temp_dir.path().join(session_id.file_name()).join("seqnums");
+        assert_eq!(fs::read_to_string(&seqnums).unwrap(), "8 : 4");
+        drop(unsynced);
+        assert_eq!(fs::read_to_string(&seqnums).unwrap(), "20 : 4");
+        let reopened = FileStore::open(temp_dir.path(), session_id, FsyncPolicy::Always).unwrap();
+        assert_eq!(reopened.next_sender_seq().unwrap(), 20);
+    }
+
+    #[test]
+    fn test_new_does_not_touch_disk() {
+        // A directory that cannot be created only fails once a file store is opened
+        let temp_dir = tempdir().unwrap();
+        let blocked = temp_dir.path().join("file");
+        fs::File::create(&blocked).unwrap();
+        let store = MessageStore::with_dir(blocked.join("store"));
+        assert_eq!(store.store_dir(), blocked.join("store"));
+        let session_id = &SessionID::new("FIX.4.2", "TEST", "NODISK");
+        assert!(store.open(session_id, StoreKind::Memory).is_ok());
+        assert!(store.open(session_id, StoreKind::File).is_err());
+    }
+}
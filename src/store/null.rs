@@ -0,0 +1,83 @@
+use std::sync::Mutex;
+use chrono::{DateTime, Utc};
+use crate::Result;
+use super::{Direction, MessageStoreBackend};
+
+/// Stores no messages, for sessions that never resend. Sequence numbers are still tracked in
+/// memory.
+#[derive(Debug)]
+pub struct NullStore {
+    // Next sender and target sequence numbers, and when they were last reset
+    state: Mutex<(u64, u64, DateTime<Utc>)>,
+}
+
+impl Default for NullStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl NullStore {
+    pub fn new() -> Self {
+        NullStore { state: Mutex::new((1, 1, Utc::now())) }
+    }
+}
+
+impl MessageStoreBackend for NullStore {
+    fn store_message(&self, _direction: Direction, _seq_num: u64, _message: &[u8]) -> Result<()> {
+        Ok(())
+    }
+
+    fn fetch_messages(&self, _direction: Direction, _begin: u64, _end: u64) -> Result<Vec<(u64, Vec<u8>)>> {
+        Ok(Vec::new())
+    }
+
+    /// Only the sequence number moves
+    fn commit_outgoing(&self, messages: &[(u64, Vec<u8>)]) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        if let Some(next) = messages.iter().map(|(seq_num, _)| seq_num.saturating_add(1)).max() {
+            state.0 = state.0.max(next);
+        }
+        Ok(())
+    }
+
+    fn next_sender_seq(&self) -> Result<u64> {
+        Ok(self.state.lock().unwrap().0)
+    }
+
+    fn next_target_seq(&self) -> Result<u64> {
+        Ok(self.state.lock().unwrap().1)
+    }
+
+    fn set_next_sender_seq(&self, seq_num: u64) -> Result<()> {
+        self.state.lock().unwrap().0 = seq_num;
+        Ok(())
+    }
+
+    fn set_next_target_seq(&self, seq_num: u64) -> Result<()> {
+        self.state.lock().unwrap().1 = seq_num;
+        Ok(())
+    }
+
+    fn reset(&self) -> Result<()> {
+        *self.state.lock().unwrap() = (1, 1, Utc::now());
+        Ok(())
+    }
+
+    fn reset_sender(&self) -> Result<()> {
+        self.state.lock().unwrap().0 = 1;
+        Ok(())
+    }
+
+    fn refresh(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn creation_time(&self) -> Result<DateTime<Utc>> {
+        Ok(self.state.lock().unwrap().2)
+    }
+
+    fn compact(&self, _resend_floor: u64) -> Result<Vec<u64>> {
+        Ok(Vec::new())
+    }
+}
@@ -0,0 +1,120 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+use chrono::{Duration, NaiveDateTime, Utc};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use crate::config::RetentionPolicy;
+use crate::error::FixError;
+use crate::message::field;
+use crate::message::parser::MessageParser;
+use crate::session::SessionID;
+use crate::Result;
+use super::journal::Journal;
+
+const SENDING_TIME_FORMAT: &str = "%Y%m%d-%H:%M:%S%.f";
+
+/// Which messages a [`RetentionPolicy`] keeps, given the sequence numbers a store holds
+pub(super) struct Retention {
+    resend_floor: u64,
+    count_floor: u64,
+    cutoff: Option<NaiveDateTime>,
+}
+
+impl Retention {
+    /// Nothing from `resend_floor` on is dropped, as the counterparty may still ask for it to
+    /// be resent. `seq_nums` are those held, in order.
+    pub(super) fn new(policy: &RetentionPolicy, resend_floor: u64, seq_nums: &[u64]) -> Self {
+        let count_floor = match policy.max_messages {
+            Some(0) => u64::MAX,
+            Some(max) if seq_nums.len() > max => seq_nums[seq_nums.len() - max],
+            _ => 0,
+        };
+        let cutoff = policy.max_age_secs.map(|secs| (Utc::now() - Duration::seconds(secs as i64)).naive_utc());
+        Retention { resend_floor, count_floor, cutoff }
+    }
+
+    /// Whether the message is kept. Messages without a readable SendingTime are only dropped
+    /// by count.
+    pub(super) fn keeps(&self, seq_num: u64, message: &[u8]) -> bool {
+        if seq_num >= self.resend_floor {
+            return true;
+        }
+        let expired = self.cutoff.is_some_and(|cutoff| sending_time(message).is_some_and(|time| time < cutoff));
+        seq_num >= self.count_floor && !expired
+    }
+}
+
+/// Apply `policy` to a journal of messages, returning the sequence numbers dropped. See
+/// [`Retention`].
+pub(super) fn compact(journal: &mut Journal, policy: &RetentionPolicy, resend_floor: u64) -> Result<Vec<u64>> {
+    let retention = Retention::new(policy, resend_floor, &journal.seq_nums());
+    journal.retain(|seq_num, message| retention.keeps(seq_num, message))
+}
+
+fn sending_time(message: &[u8]) -> Option<NaiveDateTime> {
+    let view = MessageParser::parse_bytes(message).ok()?;
+    NaiveDateTime::parse_from_str(view.get_str(field::SENDING_TIME)?, SENDING_TIME_FORMAT).ok()
+}
+
+/// Write `messages` to a new gzip archive of the session in `dir`, then delete the session's
+/// oldest archives beyond `max_archives`. Each message is preceded by its length as a 4-byte
+/// little-endian integer, as messages may hold any byte. Archives are named
+/// `<session>-<date>-<time>-<n>.log.gz`, `n` telling apart those made in the same millisecond,
+/// so they sort oldest first.
+pub fn write_archive(
+    dir: &Path,
+    session_id: &SessionID,
+    messages: &[Vec<u8>],
+    max_archives: Option<usize>,
+) -> Result<PathBuf> {
+    fs::create_dir_all(dir)?;
+    let prefix = format!("{}-", session_id.file_name());
+    let stamp = Utc::now().format("%Y%m%d-%H%M%S%3f");
+    let (path, file) = (0..)
+        .map(|n| dir.join(format!("{}{}-{:04}.log.gz", prefix, stamp, n)))
+        .find_map(|path| match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => None,
+            file => Some((path, file)),
+        })
+        .expect("archive names are unbounded");
+    let mut encoder = GzEncoder::new(file?, Compression::default());
+    for message in messages {
+        let len = u32::try_from(message.len())
+            .map_err(|_| FixError::StoreError(format!("Message of {} bytes too large to archive", message.len())))?;
+        encoder.write_all(&len.to_le_bytes())?;
+        encoder.write_all(message)?;
+    }
+    encoder.finish()?.sync_all()?;
+
+    if let Some(max) = max_archives {
+        let mut archives: Vec<PathBuf> = fs::read_dir(dir)?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.file_name().and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with(&prefix) && name.ends_with(".log.gz")))
+            .collect();
+        archives.sort();
+        let excess = archives.len().saturating_sub(max);
+        for old in &archives[..excess] {
+            fs::remove_file(old)?;
+        }
+    }
+    Ok(path)
+}
+
+/// Messages of an archive written by [`write_archive`], in order
+pub fn read_archive(path: &Path) -> Result<Vec<Vec<u8>>> {
+    let mut contents = Vec::new();
+    GzDecoder::new(File::open(path)?).read_to_end(&mut contents)?;
+    let mut messages = Vec::new();
+    let mut rest = contents.as_slice();
+    while !rest.is_empty() {
+        let truncated = || FixError::StoreError(format!("Truncated archive {}", path.display()));
+        let (len, tail) = rest.split_first_chunk::<4>().ok_or_else(truncated)?;
+        let (message, tail) = tail.split_at_checked(u32::from_le_bytes(*len) as usize).ok_or_else(truncated)?;
+        messages.push(message.to_vec());
+        rest = tail;
+    }
+    Ok(messages)
+}
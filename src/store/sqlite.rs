@@ -0,0 +1,283 @@
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use rusqlite::{params, Connection, OptionalExtension};
+use crate::config::RetentionPolicy;
+use crate::error::FixError;
+use crate::message::field;
+use crate::message::parser::MessageParser;
+use crate::session::SessionID;
+use crate::Result;
+use super::retention::Retention;
+use super::{Direction, MessageStoreBackend};
+
+const TIME_FORMAT: &str = "%Y%m%d-%H:%M:%S%.3f";
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS sessions (
+        session_id TEXT PRIMARY KEY,
+        creation_time TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS seqnums (
+        session_id TEXT PRIMARY KEY REFERENCES sessions(session_id),
+        next_sender_seq INTEGER NOT NULL,
+        next_target_seq INTEGER NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS messages (
+        session_id TEXT NOT NULL REFERENCES sessions(session_id),
+        direction TEXT NOT NULL,
+        seq_num INTEGER NOT NULL,
+        msg_type TEXT,
+        cl_ord_id TEXT,
+        time TEXT NOT NULL,
+        raw BLOB NOT NULL,
+        PRIMARY KEY (session_id, direction, seq_num)
+    );
+    CREATE INDEX IF NOT EXISTS messages_msg_type ON messages(msg_type);
+    CREATE INDEX IF NOT EXISTS messages_cl_ord_id ON messages(cl_ord_id);
+    CREATE INDEX IF NOT EXISTS messages_time ON messages(time);
+";
+
+/// A message found by [`SqliteStore::find_by_cl_ord_id`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct StoredMessage {
+    pub session_id: String,
+    pub direction: Direction,
+    pub seq_num: u64,
+    pub msg_type: Option<String>,
+    pub time: String,
+    pub raw: Vec<u8>,
+}
+
+/// Keeps sessions in a single SQLite database shared by every session of the store root.
+///
+/// Tables:
+/// - `sessions`: each session and when its store was created or last reset
+/// - `seqnums`: the next sender and target sequence numbers of each session
+/// - `messages`: messages as raw bytes by direction (`out` or `in`), with their MsgType, ClOrdID and SendingTime
+///   (or the time they were stored) in indexed columns for querying
+#[derive(Debug)]
+pub struct SqliteStore {
+    path: PathBuf,
+    session_id: String,
+    conn: Mutex<Connection>,
+    retention: RetentionPolicy,
+}
+
+impl SqliteStore {
+    /// Open the database at `path`, creating it and the session's rows if needed
+    pub fn open(path: &Path, session_id: &SessionID) -> Result<Self> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let conn = Connection::open(path).map_err(store_error)?;
+        // Sessions of one engine write to the same file
+        conn.busy_timeout(Duration::from_secs(5)).map_err(store_error)?;
+        conn.pragma_update(None, "journal_mode", "WAL").map_err(store_error)?;
+        conn.execute_batch(SCHEMA).map_err(store_error)?;
+
+        let store = SqliteStore {
+            path: path.to_path_buf(),
+            session_id: session_id.to_string(),
+            conn: Mutex::new(conn),
+            retention: RetentionPolicy::default(),
+        };
+        store.refresh()?;
+        Ok(store)
+    }
+
+    /// Set what compaction drops
+    pub fn with_retention(mut self, retention: RetentionPolicy) -> Self {
+        self.retention = retention;
+        self
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Messages of every session in the database carrying this ClOrdID, oldest first
+    pub fn find_by_cl_ord_id(&self, cl_ord_id: &str) -> Result<Vec<StoredMessage>> {
+        let conn = self.conn.lock().unwrap();
+        let mut statement = conn.prepare(
+            "SELECT session_id, direction, seq_num, msg_type, time, raw FROM messages WHERE cl_ord_id = ?1
+             ORDER BY time, seq_num"
+        ).map_err(store_error)?;
+        let rows = statement.query_map(params![cl_ord_id], |row| Ok(StoredMessage {
+            session_id: row.get(0)?,
+            direction: if row.get_ref(1)?.as_str()? == Direction::Inbound.as_str() { Direction::Inbound } else { Direction::Outbound },
+            seq_num: row.get(2)?,
+            msg_type: row.get(3)?,
+            time: row.get(4)?,
+            raw: row.get(5)?,
+        })).map_err(store_error)?;
+        rows.collect::<rusqlite::Result<Vec<_>>>().map_err(store_error)
+    }
+
+    fn seqnum(&self, column: &str) -> Result<u64> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            &format!("SELECT {} FROM seqnums WHERE session_id = ?1", column),
+            params![self.session_id],
+            |row| row.get(0),
+        ).map_err(store_error)
+    }
+
+    // Add a message through `conn`, which may be a transaction
+    fn insert(&self, conn: &Connection, direction: Direction, seq_num: u64, message: &[u8]) -> Result<()> {
+        // Messages that do not parse are still kept, just without the indexed columns
+        let view = MessageParser::parse_bytes(message).ok();
+        let msg_type = view.as_ref().map(|view| view.msg_type());
+        let cl_ord_id = view.as_ref().and_then(|view| view.get_str(field::CL_ORD_ID));
+        let time = view.as_ref()
+            .and_then(|view| view.get_str(field::SENDING_TIME))
+            .map(str::to_string)
+            .unwrap_or_else(|| Utc::now().format(TIME_FORMAT).to_string());
+
+        conn.execute(
+            "INSERT OR REPLACE INTO messages (session_id, direction, seq_num, msg_type, cl_ord_id, time, raw)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![self.session_id, direction.as_str(), seq_num, msg_type, cl_ord_id, time, message],
+        ).map_err(store_error)?;
+        Ok(())
+    }
+
+    fn set_seqnum(&self, column: &str, seq_num: u64) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            &format!("UPDATE seqnums SET {} = ?2 WHERE session_id = ?1", column),
+            params![self.session_id, seq_num],
+        ).map_err(store_error)?;
+        Ok(())
+    }
+}
+
+impl MessageStoreBackend for SqliteStore {
+    fn store_message(&self, direction: Direction, seq_num: u64, message: &[u8]) -> Result<()> {
+        self.insert(&self.conn.lock().unwrap(), direction, seq_num, message)
+    }
+
+    fn commit_outgoing(&self, messages: &[(u64, Vec<u8>)]) -> Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction().map_err(store_error)?;
+        for (seq_num, message) in messages {
+            self.insert(&tx, Direction::Outbound, *seq_num, message)?;
+        }
+        if let Some(next) = messages.iter().map(|(seq_num, _)| seq_num.saturating_add(1)).max() {
+            tx.execute(
+                "UPDATE seqnums SET next_sender_seq = MAX(next_sender_seq, ?2) WHERE session_id = ?1",
+                params![self.session_id, next],
+            ).map_err(store_error)?;
+        }
+        tx.commit().map_err(store_error)
+    }
+
+    fn fetch_messages(&self, direction: Direction, begin: u64, end: u64) -> Result<Vec<(u64, Vec<u8>)>> {
+        // SQLite integers are signed, so no stored sequence number is above i64::MAX
+        let end = end.min(i64::MAX as u64);
+        let conn = self.conn.lock().unwrap();
+        let mut statement = conn.prepare(
+            "SELECT seq_num, raw FROM messages WHERE session_id = ?1 AND direction = ?2 AND seq_num BETWEEN ?3 AND ?4
+             ORDER BY seq_num"
+        ).map_err(store_error)?;
+        let rows = statement.query_map(params![self.session_id, direction.as_str(), begin, end], |row| Ok((row.get(0)?, row.get(1)?)))
+            .map_err(store_error)?;
+        rows.collect::<rusqlite::Result<Vec<_>>>().map_err(store_error)
+    }
+
+    fn next_sender_seq(&self) -> Result<u64> {
+        self.seqnum("next_sender_seq")
+    }
+
+    fn next_target_seq(&self) -> Result<u64> {
+        self.seqnum("next_target_seq")
+    }
+
+    fn set_next_sender_seq(&self, seq_num: u64) -> Result<()> {
+        self.set_seqnum("next_sender_seq", seq_num)
+    }
+
+    fn set_next_target_seq(&self, seq_num: u64) -> Result<()> {
+        self.set_seqnum("next_target_seq", seq_num)
+    }
+
+    fn reset(&self) -> Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction().map_err(store_error)?;
+        tx.execute("DELETE FROM messages WHERE session_id = ?1", params![self.session_id]).map_err(store_error)?;
+        tx.execute(
+            "UPDATE seqnums SET next_sender_seq = 1, next_target_seq = 1 WHERE session_id = ?1",
+            params![self.session_id],
+        ).map_err(store_error)?;
+        tx.execute(
+            "UPDATE sessions SET creation_time = ?2 WHERE session_id = ?1",
+            params![self.session_id, Utc::now().format(TIME_FORMAT).to_string()],
+        ).map_err(store_error)?;
+        tx.commit().map_err(store_error)
+    }
+
+    fn reset_sender(&self) -> Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction().map_err(store_error)?;
+        tx.execute(
+            "DELETE FROM messages WHERE session_id = ?1 AND direction = ?2",
+            params![self.session_id, Direction::Outbound.as_str()],
+        ).map_err(store_error)?;
+        tx.execute("UPDATE seqnums SET next_sender_seq = 1 WHERE session_id = ?1", params![self.session_id])
+            .map_err(store_error)?;
+        tx.commit().map_err(store_error)
+    }
+
+    /// Add the session's rows if they are missing; everything else is read on demand
+    fn refresh(&self) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT OR IGNORE INTO sessions (session_id, creation_time) VALUES (?1, ?2)",
+            params![self.session_id, Utc::now().format(TIME_FORMAT).to_string()],
+        ).map_err(store_error)?;
+        conn.execute(
+            "INSERT OR IGNORE INTO seqnums (session_id, next_sender_seq, next_target_seq) VALUES (?1, 1, 1)",
+            params![self.session_id],
+        ).map_err(store_error)?;
+        Ok(())
+    }
+
+    fn creation_time(&self) -> Result<DateTime<Utc>> {
+        let conn = self.conn.lock().unwrap();
+        let text: Option<String> = conn.query_row(
+            "SELECT creation_time FROM sessions WHERE session_id = ?1",
+            params![self.session_id],
+            |row| row.get(0),
+        ).optional().map_err(store_error)?;
+        let text = text.ok_or_else(|| FixError::StoreError(format!("Session {} missing from {}", self.session_id, self.path.display())))?;
+        NaiveDateTime::parse_from_str(&text, TIME_FORMAT)
+            .map(|time| time.and_utc())
+            .map_err(|e| FixError::StoreError(format!("Invalid creation time of {}: {}", self.session_id, e)))
+    }
+
+    fn compact(&self, resend_floor: u64) -> Result<Vec<u64>> {
+        let messages = self.fetch_messages(Direction::Outbound, 1, u64::MAX)?;
+        let seq_nums: Vec<u64> = messages.iter().map(|(seq_num, _)| *seq_num).collect();
+        let retention = Retention::new(&self.retention, resend_floor, &seq_nums);
+        let dropped: Vec<u64> = messages.iter()
+            .filter(|(seq_num, message)| !retention.keeps(*seq_num, message))
+            .map(|(seq_num, _)| *seq_num)
+            .collect();
+
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction().map_err(store_error)?;
+        for seq_num in &dropped {
+            tx.execute(
+                "DELETE FROM messages WHERE session_id = ?1 AND direction = ?2 AND seq_num = ?3",
+                params![self.session_id, Direction::Outbound.as_str(), seq_num],
+            ).map_err(store_error)?;
+        }
+        tx.commit().map_err(store_error)?;
+        Ok(dropped)
+    }
+}
+
+fn store_error(err: rusqlite::Error) -> FixError {
+    FixError::StoreError(err.to_string())
+}
@@ -10,11 +10,16 @@ use std::fs::File;
 use std::io::BufReader;
 use serde::{Serialize, Deserialize};
 use std::time::Duration;
-use crate::message::Message;
-use crate::message::parser::MessageParser;
+use crate::message::{Message, MessageSerializer, MessageView};
+use crate::message::parser::{MessageParser, MAX_FRAME_SIZE};
 use crate::Result;
 use crate::error::FixError;
 
+mod split;
+
+pub use self::split::{FixReader, FixWriter};
+use self::split::{ReadConnection, WriteConnection};
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TransportConfig {
     pub use_ssl: bool,
@@ -64,13 +69,16 @@ impl Default for TransportConfig {
 
 enum ConnectionType {
     Plain(TcpStream),
-    Tls(TlsStream<TcpStream>),
+    Tls(Box<TlsStream<TcpStream>>),
 }
 
 pub struct Transport {
     connection: Option<ConnectionType>,
     config: TransportConfig,
     buffer: Vec<u8>,
+    // Outbound frames are serialized here and reused across sends
+    write_buffer: Vec<u8>,
+    serializer: MessageSerializer,
 }
 
 impl Transport {
@@ -79,6 +87,8 @@ impl Transport {
             connection: None,
             config: config.clone(),
             buffer: Vec::with_capacity(config.buffer_size),
+            write_buffer: Vec::with_capacity(config.buffer_size),
+            serializer: MessageSerializer::new(),
         }
     }
 
@@ -87,6 +97,8 @@ impl Transport {
             connection: Some(ConnectionType::Plain(stream)),
             config: TransportConfig::default(),
             buffer: Vec::with_capacity(4096),
+            write_buffer: Vec::with_capacity(4096),
+            serializer: MessageSerializer::new(),
         }
     }
 
@@ -95,7 +107,7 @@ impl Transport {
 
         if self.config.use_ssl {
             let tls_stream = self.establish_tls(stream).await?;
-            self.connection = Some(ConnectionType::Tls(tls_stream));
+            self.connection = Some(ConnectionType::Tls(Box::new(tls_stream)));
         } else {
             self.connection = Some(ConnectionType::Plain(stream));
         }
@@ -160,16 +172,50 @@ impl Transport {
         Ok(tls_stream)
     }
 
+    /// Split into a reader and a writer that can be driven from separate tasks, so writes
+    /// never wait on a pending read. Input already buffered moves to the reader.
+    pub fn split(self) -> Result<(FixReader, FixWriter)> {
+        let (read, write) = match self.connection {
+            Some(ConnectionType::Plain(stream)) => {
+                let (read, write) = stream.into_split();
+                (ReadConnection::Plain(read), WriteConnection::Plain(write))
+            },
+            Some(ConnectionType::Tls(stream)) => {
+                let (read, write) = tokio::io::split(stream);
+                (ReadConnection::Tls(read), WriteConnection::Tls(write))
+            },
+            None => return Err(FixError::TransportError("Not connected".to_string())),
+        };
+
+        Ok((
+            FixReader::new(read, self.buffer, self.config.buffer_size),
+            FixWriter::new(write, self.write_buffer, self.config.buffer_size, self.serializer),
+        ))
+    }
+
     pub async fn send(&mut self, message: &Message) -> Result<()> {
-        let msg_str = message.to_string()?;
+        self.write_buffer.clear();
+        self.serializer.serialize_into(message, &mut self.write_buffer)?;
+        self.flush_write_buffer().await
+    }
 
+    /// Serialize several messages back to back and write them with a single write call
+    pub async fn send_batch(&mut self, messages: &[Message]) -> Result<()> {
+        self.write_buffer.clear();
+        for message in messages {
+            self.serializer.serialize_into(message, &mut self.write_buffer)?;
+        }
+        self.flush_write_buffer().await
+    }
+
+    async fn flush_write_buffer(&mut self) -> Result<()> {
         match self.connection.as_mut() {
             Some(ConnectionType::Plain(stream)) => {
-                stream.write_all(msg_str.as_bytes()).await?;
+                stream.write_all(&self.write_buffer).await?;
                 stream.flush().await?;
             },
             Some(ConnectionType::Tls(stream)) => {
-                stream.write_all(msg_str.as_bytes()).await?;
+                stream.write_all(&self.write_buffer).await?;
                 stream.flush().await?;
             },
             None => return Err(FixError::TransportError("Not connected".to_string())),
@@ -179,11 +225,21 @@ impl Transport {
     }
 
     pub async fn receive(&mut self) -> Result<Option<Message>> {
-        let mut temp_buf = vec![0u8; self.config.buffer_size];
+        self.receive_with(|view| view.to_message()).await
+    }
+
+    /// Receive the next frame and hand its borrowed view to `parse`, e.g. to run checks that
+    /// need the fields in wire order before building a `Message`
+    pub async fn receive_with<T>(&mut self, mut parse: impl FnMut(MessageView<'_>) -> Result<T>) -> Result<Option<T>> {
+        // A previous read may already have buffered a complete message
+        if let Some(message) = self.take_buffered_message(&mut parse)? {
+            return Ok(Some(message));
+        }
 
+        self.buffer.reserve(self.config.buffer_size);
         let n = match self.connection.as_mut() {
-            Some(ConnectionType::Plain(stream)) => stream.read(&mut temp_buf).await?,
-            Some(ConnectionType::Tls(stream)) => stream.read(&mut temp_buf).await?,
+            Some(ConnectionType::Plain(stream)) => stream.read_buf(&mut self.buffer).await?,
+            Some(ConnectionType::Tls(stream)) => stream.read_buf(&mut self.buffer).await?,
             None => return Err(FixError::TransportError("Not connected".to_string())),
         };
 
@@ -191,18 +247,11 @@ impl Transport {
             return Ok(None); // Connection closed
         }
 
-        self.buffer.extend_from_slice(&temp_buf[..n]);
-
-        if let Some((msg_str, consumed)) = MessageParser::extract_complete_message(&self.buffer) {
-            // Remove consumed bytes from buffer
-            self.buffer.drain(..consumed);
+        self.take_buffered_message(&mut parse)
+    }
 
-            // Parse the message
-            let message = MessageParser::parse(&msg_str)?;
-            Ok(Some(message))
-        } else {
-            Ok(None)
-        }
+    fn take_buffered_message<T>(&mut self, parse: &mut impl FnMut(MessageView<'_>) -> Result<T>) -> Result<Option<T>> {
+        take_frame(&mut self.buffer, parse)
     }
 
     pub async fn disconnect(&mut self) -> Result<()> {
@@ -212,6 +261,26 @@ impl Transport {
     }
 }
 
+// Parse the first complete frame in `buffer`, consuming its bytes. Fails once more than
+// `MAX_FRAME_SIZE` bytes are buffered without a complete frame.
+fn take_frame<T>(buffer: &mut Vec<u8>, parse: &mut impl FnMut(MessageView<'_>) -> Result<T>) -> Result<Option<T>> {
+    match MessageParser::find_message_boundary(buffer) {
+        Some((start, end)) => {
+            let message = MessageParser::parse_bytes(&buffer[start..end])
+                .and_then(&mut *parse);
+            // Remove consumed bytes from buffer, even if the frame was malformed
+            buffer.drain(..end);
+            message.map(Some)
+        },
+        None if buffer.len() > MAX_FRAME_SIZE => {
+            let buffered = buffer.len();
+            buffer.clear();
+            Err(FixError::ParseError(format!("No complete frame in {} buffered bytes", buffered)))
+        },
+        None => Ok(None),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -264,6 +333,34 @@ mod tests {
         assert!(client_result.is_some());
     }
 
+    #[tokio::test]
+    async fn test_transport_send_batch() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let client = tokio::spawn(async move {
+            let stream = TcpStream::connect(addr).await.unwrap();
+            let mut transport = Transport::new(stream);
+            let messages: Vec<Message> = (1..=3).map(|seq| {
+                let mut msg = Message::new(field::values::HEARTBEAT);
+                let _ = msg.set_field(Field::new(field::MSG_SEQ_NUM, seq.to_string()));
+                msg
+            }).collect();
+            transport.send_batch(&messages).await.unwrap();
+            transport
+        });
+
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut transport = Transport::new(stream);
+        let _client = client.await.unwrap();
+
+        // All three frames arrive together and are returned one at a time
+        for seq in 1..=3 {
+            let msg = transport.receive().await.unwrap().unwrap();
+            assert_eq!(msg.get_field(field::MSG_SEQ_NUM).unwrap().value(), seq.to_string());
+        }
+    }
+
     #[tokio::test]
     async fn test_transport_ssl_connection() {
         // Setup test certificates
@@ -293,6 +390,17 @@ mod tests {
         assert_eq!(transport.buffer.len(), 0);
     }
 
+    #[test]
+    fn test_take_frame_gives_up_past_max_frame_size() {
+        let mut buffer = b"8=FIX.4.2\x019=500\x0135=0\x01".to_vec();
+        let mut parse = |view: MessageView<'_>| view.to_message();
+        assert!(take_frame(&mut buffer, &mut parse).unwrap().is_none());
+
+        buffer.resize(MAX_FRAME_SIZE + 1, b'x');
+        assert!(matches!(take_frame(&mut buffer, &mut parse), Err(FixError::ParseError(_))));
+        assert!(buffer.is_empty());
+    }
+
     #[tokio::test]
     async fn test_transport_connection_timeout() {
         let config = TransportConfig {
@@ -322,4 +430,64 @@ mod tests {
         transport.disconnect().await.unwrap();
         assert!(transport.connection.is_none());
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_transport_split() {
+        use futures::{SinkExt, StreamExt};
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let client = tokio::spawn(async move {
+            let stream = TcpStream::connect(addr).await.unwrap();
+            let (mut reader, mut writer) = Transport::new(stream).split().unwrap();
+
+            // A pending read does not hold up the writer
+            let read = tokio::spawn(async move { reader.next().await });
+            writer.send(Message::new(field::values::TEST_REQUEST)).await.unwrap();
+            let reply = read.await.unwrap().unwrap().unwrap();
+            writer.close().await.unwrap();
+            reply
+        });
+
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut transport = Transport::new(stream);
+        let request = transport.receive().await.unwrap().unwrap();
+        assert_eq!(request.msg_type(), field::values::TEST_REQUEST);
+        transport.send(&Message::new(field::values::HEARTBEAT)).await.unwrap();
+        assert_eq!(client.await.unwrap().msg_type(), field::values::HEARTBEAT);
+
+        // The stream ends when the peer shuts down its writer
+        let (reader, _writer) = transport.split().unwrap();
+        assert!(reader.collect::<Vec<_>>().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_fix_writer_sink() {
+        use futures::{SinkExt, StreamExt};
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let client = tokio::spawn(async move {
+            let stream = TcpStream::connect(addr).await.unwrap();
+            let (_reader, mut writer) = Transport::new(stream).split().unwrap();
+            let messages = (1..=50).map(|seq| {
+                let mut msg = Message::new(field::values::HEARTBEAT);
+                let _ = msg.set_field(Field::new(field::MSG_SEQ_NUM, seq.to_string()));
+                Ok(msg)
+            });
+            writer.send_all(&mut futures::stream::iter(messages)).await.unwrap();
+            writer.close().await.unwrap();
+        });
+
+        let (stream, _) = listener.accept().await.unwrap();
+        let (reader, _writer) = Transport::new(stream).split().unwrap();
+        let received: Vec<Message> = reader.map(|msg| msg.unwrap()).collect().await;
+        client.await.unwrap();
+
+        assert_eq!(received.len(), 50);
+        for (i, msg) in received.iter().enumerate() {
+            assert_eq!(msg.get_field(field::MSG_SEQ_NUM).unwrap().value(), (i + 1).to_string());
+        }
+    }
+}
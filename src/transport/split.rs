@@ -0,0 +1,192 @@
+use std::io;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use futures::{Sink, Stream};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadBuf, ReadHalf, WriteHalf};
+use tokio::net::TcpStream;
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio_rustls::client::TlsStream;
+use crate::error::FixError;
+use crate::message::{Message, MessageSerializer, MessageView};
+use crate::Result;
+use super::take_frame;
+
+pub(super) enum ReadConnection {
+    Plain(OwnedReadHalf),
+    Tls(ReadHalf<Box<TlsStream<TcpStream>>>),
+}
+
+pub(super) enum WriteConnection {
+    Plain(OwnedWriteHalf),
+    Tls(WriteHalf<Box<TlsStream<TcpStream>>>),
+}
+
+impl AsyncRead for ReadConnection {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            ReadConnection::Plain(stream) => Pin::new(stream).poll_read(cx, buf),
+            ReadConnection::Tls(stream) => Pin::new(stream).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for WriteConnection {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            WriteConnection::Plain(stream) => Pin::new(stream).poll_write(cx, buf),
+            WriteConnection::Tls(stream) => Pin::new(stream).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            WriteConnection::Plain(stream) => Pin::new(stream).poll_flush(cx),
+            WriteConnection::Tls(stream) => Pin::new(stream).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            WriteConnection::Plain(stream) => Pin::new(stream).poll_shutdown(cx),
+            WriteConnection::Tls(stream) => Pin::new(stream).poll_shutdown(cx),
+        }
+    }
+}
+
+/// Receiving half of a [`Transport`](super::Transport), see [`Transport::split`](super::Transport::split).
+///
+/// Frames and parses the byte stream; as a [`Stream`] it yields one message per frame and ends
+/// when the peer closes the connection.
+pub struct FixReader {
+    connection: ReadConnection,
+    buffer: Vec<u8>,
+    buffer_size: usize,
+}
+
+impl FixReader {
+    pub(super) fn new(connection: ReadConnection, buffer: Vec<u8>, buffer_size: usize) -> Self {
+        FixReader { connection, buffer, buffer_size }
+    }
+
+    pub async fn receive(&mut self) -> Result<Option<Message>> {
+        self.receive_with(|view| view.to_message()).await
+    }
+
+    /// Read until a complete frame is buffered and hand its view to `parse`. Unlike
+    /// [`Transport::receive_with`](super::Transport::receive_with), `None` is only returned once
+    /// the peer closed the connection.
+    pub async fn receive_with<T>(&mut self, mut parse: impl FnMut(MessageView<'_>) -> Result<T>) -> Result<Option<T>> {
+        loop {
+            if let Some(message) = take_frame(&mut self.buffer, &mut parse)? {
+                return Ok(Some(message));
+            }
+
+            self.buffer.reserve(self.buffer_size);
+            if self.connection.read_buf(&mut self.buffer).await? == 0 {
+                return Ok(None);
+            }
+        }
+    }
+}
+
+impl Stream for FixReader {
+    type Item = Result<Message>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            match take_frame(&mut this.buffer, &mut |view| view.to_message()) {
+                Ok(Some(message)) => return Poll::Ready(Some(Ok(message))),
+                Ok(None) => {},
+                Err(e) => return Poll::Ready(Some(Err(e))),
+            }
+
+            // Read into the spare tail of the buffer, dropping whatever was not filled
+            let filled = this.buffer.len();
+            this.buffer.resize(filled + this.buffer_size, 0);
+            let mut read_buf = ReadBuf::new(&mut this.buffer[filled..]);
+            let polled = Pin::new(&mut this.connection).poll_read(cx, &mut read_buf);
+            let n = read_buf.filled().len();
+            this.buffer.truncate(filled + n);
+
+            match polled {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(Err(e)) => return Poll::Ready(Some(Err(e.into()))),
+                Poll::Ready(Ok(())) if n == 0 => return Poll::Ready(None),
+                Poll::Ready(Ok(())) => {},
+            }
+        }
+    }
+}
+
+/// Sending half of a [`Transport`](super::Transport), see [`Transport::split`](super::Transport::split).
+///
+/// As a [`Sink`], messages are serialized into a write buffer as they are fed and written out on
+/// flush, or once a buffer's worth is waiting.
+pub struct FixWriter {
+    connection: WriteConnection,
+    write_buffer: Vec<u8>,
+    // Bytes of `write_buffer` already handed to the connection
+    written: usize,
+    buffer_size: usize,
+    serializer: MessageSerializer,
+}
+
+impl FixWriter {
+    pub(super) fn new(connection: WriteConnection, write_buffer: Vec<u8>, buffer_size: usize, serializer: MessageSerializer) -> Self {
+        FixWriter { connection, write_buffer, written: 0, buffer_size, serializer }
+    }
+
+    /// Serialize several messages back to back and write them with a single write call
+    pub async fn send_batch(&mut self, messages: &[Message]) -> Result<()> {
+        for message in messages {
+            self.serializer.serialize_into(message, &mut self.write_buffer)?;
+        }
+        std::future::poll_fn(|cx| self.poll_write_buffer(cx)).await?;
+        self.connection.flush().await?;
+        Ok(())
+    }
+
+    fn poll_write_buffer(&mut self, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        while self.written < self.write_buffer.len() {
+            let n = ready!(Pin::new(&mut self.connection).poll_write(cx, &self.write_buffer[self.written..]))?;
+            if n == 0 {
+                return Poll::Ready(Err(io::Error::from(io::ErrorKind::WriteZero).into()));
+            }
+            self.written += n;
+        }
+        self.write_buffer.clear();
+        self.written = 0;
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl Sink<Message> for FixWriter {
+    type Error = FixError;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        let this = self.get_mut();
+        if this.write_buffer.len() >= this.buffer_size {
+            this.poll_write_buffer(cx)
+        } else {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    fn start_send(self: Pin<&mut Self>, message: Message) -> Result<()> {
+        let this = self.get_mut();
+        this.serializer.serialize_into(&message, &mut this.write_buffer)?;
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_write_buffer(cx))?;
+        Pin::new(&mut this.connection).poll_flush(cx).map_err(Into::into)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        ready!(self.as_mut().poll_flush(cx))?;
+        Pin::new(&mut self.get_mut().connection).poll_shutdown(cx).map_err(Into::into)
+    }
+}
@@ -4,6 +4,7 @@ use goldfix::{
     message::{Message, Field},
 };
 use std::path::PathBuf;
+use tempfile::TempDir;
 use tokio::time::Duration;
 
 mod test_utils;
@@ -11,7 +12,7 @@ use test_utils::*;
 
 #[tokio::test]
 async fn test_engine_startup() {
-    let config = create_test_engine_config();
+    let (_store_dir, config) = create_test_engine_config();
     let engine = FixEngine::new(config);
 
     let result = engine.start().await;
@@ -23,8 +24,11 @@ async fn test_engine_startup() {
 
 #[tokio::test]
 async fn test_initiator_session() {
-    let mut config = create_test_engine_config();
-    config.sessions = vec![create_test_initiator_config()];
+    let (_listener, addr) = create_test_listener().await.unwrap();
+    let mut initiator_config = create_test_initiator_config();
+    initiator_config.target_addr = addr;
+    let (_store_dir, mut config) = create_test_engine_config();
+    config.sessions = vec![initiator_config];
     let engine = FixEngine::new(config);
 
     let result = engine.start().await;
@@ -37,9 +41,24 @@ async fn test_initiator_session() {
     let _ = engine.stop().await;
 }
 
+#[tokio::test]
+async fn test_initiator_session_unreachable() {
+    let (listener, addr) = create_test_listener().await.unwrap();
+    drop(listener);
+    let mut initiator_config = create_test_initiator_config();
+    initiator_config.target_addr = addr;
+    let (_store_dir, mut config) = create_test_engine_config();
+    config.sessions = vec![initiator_config];
+    let engine = FixEngine::new(config);
+
+    // The failed start is reported and the session is not left registered
+    assert!(engine.start().await.is_err());
+    assert!(engine.get_session_ids().await.is_empty());
+}
+
 #[tokio::test]
 async fn test_acceptor_session() {
-    let mut config = create_test_engine_config();
+    let (_store_dir, mut config) = create_test_engine_config();
     config.sessions = vec![create_test_acceptor_config()];
     let engine = FixEngine::new(config);
 
@@ -82,7 +101,7 @@ async fn test_session_roles() {
 async fn test_session_connection() {
     let (initiator_config, acceptor_config) = create_test_session_pair().await.unwrap();
 
-    let mut engine_config = create_test_engine_config();
+    let (_store_dir, mut engine_config) = create_test_engine_config();
     engine_config.sessions = vec![initiator_config, acceptor_config];
 
     let engine = FixEngine::new(engine_config);
@@ -99,14 +118,20 @@ async fn test_session_connection() {
     let _ = engine.stop().await;
 }
 
-fn create_test_engine_config() -> EngineConfig {
-    EngineConfig {
+// The store lives in a temporary directory that is removed when the returned `TempDir` is dropped
+fn create_test_engine_config() -> (TempDir, EngineConfig) {
+    let store_dir = tempfile::tempdir().unwrap();
+    let config = EngineConfig {
         log_config: LogConfig {
             log_directory: PathBuf::from("/tmp"),
             log_level: "INFO".to_string(),
             log_events: true,
             log_messages: true,
         },
+        store_directory: store_dir.path().to_path_buf(),
+        store_fsync: Default::default(),
+        store_retention: Default::default(),
         sessions: vec![], // Initialize with an empty vector
-    }
+    };
+    (store_dir, config)
 }
\ No newline at end of file
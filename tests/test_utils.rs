@@ -2,37 +2,31 @@ use goldfix::{
     config::{SessionConfig, SessionRole},
 };
 use tokio::net::TcpListener;
-use std::time::Duration;
 
 /// Creates a test configuration for an initiator (client) session
 pub fn create_test_initiator_config() -> SessionConfig {
     SessionConfig {
-        begin_string: "FIX.4.2".to_string(),
         sender_comp_id: "TEST_INITIATOR".to_string(),
         target_comp_id: "TEST_ACCEPTOR".to_string(),
         target_addr: "127.0.0.1:0".to_string(),
-        heart_bt_int: 30,
         reset_on_logon: true,
         reset_on_logout: true,
         reset_on_disconnect: true,
-        transport_config: None,
-        role: SessionRole::Initiator,
+        ..Default::default()
     }
 }
 
 /// Creates a test configuration for an acceptor (server) session
 pub fn create_test_acceptor_config() -> SessionConfig {
     SessionConfig {
-        begin_string: "FIX.4.2".to_string(),
         sender_comp_id: "TEST_ACCEPTOR".to_string(),
         target_comp_id: "TEST_INITIATOR".to_string(),
         target_addr: "127.0.0.1:0".to_string(),
-        heart_bt_int: 30,
         reset_on_logon: true,
         reset_on_logout: true,
         reset_on_disconnect: true,
-        transport_config: None,
         role: SessionRole::Acceptor,
+        ..Default::default()
     }
 }
 